@@ -0,0 +1,84 @@
+//! Benchmark of the memory and the speed of the occupancy grid against the area
+//!
+//! ```
+//! $ cargo run --release --example occupancy_benchmark -- [ROWS] [COLS] [OBSTACLES] [LINES]
+//! ```
+//!
+//! Defaults to a 10000x10000 map with 2000 random rectangles, 10000 line-of-sight tests and 10000 column runs.
+
+use std::env;
+use std::mem::size_of;
+
+use pathfinder::{Instant, thread_rng, Rng};
+use pathfinder::occupancy::{self, OccupancyGrid};
+use pathfinder::tree::is_line_free;
+
+const MEGABYTE: f64 = 1024.0 * 1024.0;
+
+fn main() {
+	let args: Vec<usize> = env::args().skip(1).map(|arg| arg.parse().unwrap_or_else(|_| panic!("Invalid number: {}", arg))).collect();
+	let rows = *args.first().unwrap_or(&10000);
+	let cols = *args.get(1).unwrap_or(&10000);
+	let obstacles = *args.get(2).unwrap_or(&2000);
+	let lines = *args.get(3).unwrap_or(&10000);
+	let mut rng = thread_rng();
+
+	// Random rectangles up to a hundredth of the size, a square of that size in the corners stays free for the search
+	let (start, end) = ((0, 0), (rows - 1, cols - 1));
+	let (height, width) = (rows.div_ceil(100), cols.div_ceil(100));
+	let is_corner = |((first_row, last_row), (first_col, last_col)): &((usize, usize), (usize, usize))| {
+		(*first_row < height && *first_col < width) || (*last_row > rows - height && *last_col > cols - width)
+	};
+	let rectangles: Vec<((usize, usize), (usize, usize))> = std::iter::repeat_with(|| {
+		let (row, col) = (rng.gen_range(0..rows), rng.gen_range(0..cols));
+		((row, usize::min(rows, row + rng.gen_range(1..=height))), (col, usize::min(cols, col + rng.gen_range(1..=width))))
+	}).filter(|rectangle| !is_corner(rectangle)).take(obstacles).collect();
+	println!("Field Size: {}x{}", rows, cols);
+	println!("Obstacles:  {}\n", obstacles);
+
+	let benchmark = Instant::now();
+	let mut area = vec![0u64; rows * cols];
+	for ((first_row, last_row), (first_col, last_col)) in &rectangles {
+		for col in *first_col..*last_col {
+			area[(col * rows) + first_row..(col * rows) + last_row].fill(u64::MAX);
+		}
+	}
+	println!("Area Build: {:.6?}", benchmark.elapsed());
+	println!("Area Memory: {:.2} MB", (area.len() * size_of::<u64>()) as f64 / MEGABYTE);
+
+	let benchmark = Instant::now();
+	let mut grid = OccupancyGrid::new(rows, cols);
+	for ((first_row, last_row), (first_col, last_col)) in &rectangles {
+		grid.fill(*first_row..*last_row, *first_col..*last_col);
+	}
+	println!("Occupancy Build: {:.6?}", benchmark.elapsed());
+	println!("Occupancy Memory: {:.2} MB", grid.size_in_bytes() as f64 / MEGABYTE);
+	assert_eq!(grid.count_obstacles(), area.iter().filter(|value| **value == u64::MAX).count());
+
+	// Line of sight between random fields
+	let pairs: Vec<((usize, usize), (usize, usize))> = (0..lines)
+		.map(|_| ((rng.gen_range(0..rows), rng.gen_range(0..cols)), (rng.gen_range(0..rows), rng.gen_range(0..cols))))
+		.collect();
+	let benchmark = Instant::now();
+	let area_free: Vec<bool> = pairs.iter().map(|(p1, p2)| is_line_free(&area, &rows, *p1, *p2)).collect();
+	println!("\nArea Lines: {} tests: {:.6?}", lines, benchmark.elapsed());
+	let benchmark = Instant::now();
+	let grid_free: Vec<bool> = pairs.iter().map(|(p1, p2)| grid.is_line_free(*p1, *p2)).collect();
+	println!("Occupancy Lines: {} tests: {:.6?}", lines, benchmark.elapsed());
+	assert_eq!(area_free, grid_free);
+
+	// Free runs in random columns, from a random row to the next obstacle
+	let runs: Vec<(usize, usize)> = (0..lines).map(|_| (rng.gen_range(0..cols), rng.gen_range(0..rows))).collect();
+	let benchmark = Instant::now();
+	let area_runs: Vec<Option<usize>> = runs.iter().map(|(col, row)| (*row..rows).find(|row| area[(col * rows) + row] == u64::MAX)).collect();
+	println!("Area Runs: {} tests: {:.6?}", lines, benchmark.elapsed());
+	let benchmark = Instant::now();
+	let grid_runs: Vec<Option<usize>> = runs.iter().map(|(col, row)| grid.next_obstacle(*col, *row)).collect();
+	println!("Occupancy Runs: {} tests: {:.6?}", lines, benchmark.elapsed());
+	assert_eq!(area_runs, grid_runs);
+	drop(area);
+
+	// A* over the whole map: A dense scratch needs a cost and a parent for every field
+	println!("\nDense Scratch Memory: {:.2} MB", (rows * cols * (size_of::<f64>() + size_of::<usize>())) as f64 / MEGABYTE);
+	occupancy::a_star(&grid, start, end);
+}
@@ -0,0 +1,174 @@
+//! Batch mode to plan many queries with many algorithms in parallel
+//!
+//! Each combination of a query and an algorithm is planned on its own copy of the area. All combinations are
+//! distributed over the threads of rayon, the results are in the order of the queries and then the algorithms.
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use rayon::prelude::*;
+
+use crate::Instant;
+use crate::tree::{self, RrtConfig};
+use crate::{a_star, grassfire};
+
+/// Start and end position (row, col) of a query
+pub type Query = ((usize, usize), (usize, usize));
+
+/// The grid algorithms which can run in a batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+	/// Grassfire looping through the whole area, 4-Neighborhood
+	GrassfireV1,
+	/// Grassfire with the next fields cached, 4-Neighborhood
+	GrassfireV2,
+	/// Grassfire with the next fields cached, 8-Neighborhood
+	GrassfireV3,
+	/// Grassfire with a 4-Neighborhood calculation and an 8-Neighborhood path
+	GrassfireV4,
+	/// Grassfire v2 with the neighbours searched on all threads
+	GrassfireV2Parallel,
+	/// Grassfire v3 with the neighbours searched on all threads
+	GrassfireV3Parallel,
+	/// A* on the grid
+	AStar,
+	/// RRT - Rapidly-Exploring Random Tree
+	RrtV1,
+	/// RRT* - Rapidly-Exploring Random Tree with rewiring
+	RrtV2,
+	/// Informed RRT* - Rewiring and sampling inside the ellipse of the best path
+	RrtV3,
+	/// RRT-Connect - Two Rapidly-Exploring Random Trees from the start and the end
+	RrtConnect,
+}
+
+impl Algorithm {
+	/// All algorithms in the order of the binary
+	pub const ALL: [Algorithm; 11] = [
+		Algorithm::GrassfireV1,
+		Algorithm::GrassfireV2,
+		Algorithm::GrassfireV3,
+		Algorithm::GrassfireV4,
+		Algorithm::GrassfireV2Parallel,
+		Algorithm::GrassfireV3Parallel,
+		Algorithm::AStar,
+		Algorithm::RrtV1,
+		Algorithm::RrtV2,
+		Algorithm::RrtV3,
+		Algorithm::RrtConnect,
+	];
+
+	/// Returns the name of the algorithm as it is parsed
+	pub fn name(&self) -> &'static str {
+		match self {
+			Algorithm::GrassfireV1 => "grassfire-v1",
+			Algorithm::GrassfireV2 => "grassfire-v2",
+			Algorithm::GrassfireV3 => "grassfire-v3",
+			Algorithm::GrassfireV4 => "grassfire-v4",
+			Algorithm::GrassfireV2Parallel => "grassfire-v2-parallel",
+			Algorithm::GrassfireV3Parallel => "grassfire-v3-parallel",
+			Algorithm::AStar => "a-star",
+			Algorithm::RrtV1 => "rrt-v1",
+			Algorithm::RrtV2 => "rrt-v2",
+			Algorithm::RrtV3 => "rrt-v3",
+			Algorithm::RrtConnect => "rrt-connect",
+		}
+	}
+
+	/// Plans the path from the start to the end on the area
+	///
+	/// # Panics
+	///
+	/// If a tree algorithm gets an invalid configuration, see [`RrtConfig::validate`]
+	pub fn plan(&self, area: &mut [u64], rows: &usize, cols: &usize, start: (usize, usize), end: (usize, usize), config: &RrtConfig) -> Vec<(usize, usize)> {
+		self.search(area, rows, cols, start, end, config).0
+	}
+
+	/// Plans the path from the start to the end on the area and returns the number of nodes of the tree algorithms
+	///
+	/// The grid algorithms return `None` for the nodes, they mark each visited field in the area instead.
+	///
+	/// # Panics
+	///
+	/// If a tree algorithm gets an invalid configuration, see [`RrtConfig::validate`]
+	pub fn search(&self, area: &mut [u64], rows: &usize, cols: &usize, start: (usize, usize), end: (usize, usize), config: &RrtConfig) -> (Vec<(usize, usize)>, Option<usize>) {
+		let nodes = |result: tree::TreeResult| (result.path, Some(result.tree.len()));
+		match self {
+			Algorithm::GrassfireV1 => (grassfire::v1(area, rows, cols, start, end), None),
+			Algorithm::GrassfireV2 => (grassfire::v2(area, rows, cols, start, end), None),
+			Algorithm::GrassfireV3 => (grassfire::v3(area, rows, cols, start, end), None),
+			Algorithm::GrassfireV4 => (grassfire::v4(area, rows, cols, start, end), None),
+			Algorithm::GrassfireV2Parallel => (grassfire::v2_parallel(area, rows, cols, start, end), None),
+			Algorithm::GrassfireV3Parallel => (grassfire::v3_parallel(area, rows, cols, start, end), None),
+			Algorithm::AStar => (a_star::calculate(area, rows, cols, start, end), None),
+			Algorithm::RrtV1 => nodes(tree::rrt_v1(area, rows, cols, start, end, config)),
+			Algorithm::RrtV2 => nodes(tree::rrt_v2(area, rows, cols, start, end, config)),
+			Algorithm::RrtV3 => nodes(tree::rrt_v3(area, rows, cols, start, end, config)),
+			Algorithm::RrtConnect => nodes(tree::rrt_connect(area, rows, cols, start, end, config)),
+		}
+	}
+}
+
+impl FromStr for Algorithm {
+	type Err = String;
+
+	/// Parses an algorithm like `grassfire-v2`, `a-star` or `rrt-connect`
+	fn from_str(value: &str) -> Result<Self, Self::Err> {
+		Algorithm::ALL.into_iter()
+			.find(|algorithm| algorithm.name() == value.to_lowercase())
+			.ok_or_else(|| format!("Unknown algorithm: {}", value))
+	}
+}
+
+/// The result of one query with one algorithm
+#[derive(Debug, Clone)]
+pub struct BatchResult {
+	/// The algorithm which planned the path
+	pub algorithm: Algorithm,
+	/// Start and end position of the path
+	pub query: Query,
+	/// The path as returned by the algorithm
+	pub path: Vec<(usize, usize)>,
+	/// Length of the path through the middle of the fields
+	pub length: f64,
+	/// Time of the calculation
+	pub duration: Duration,
+}
+
+/// Plans all queries with all algorithms in parallel
+///
+/// # Arguments
+///
+/// * `area` - The play field as a one-dimensional vector, each calculation works on its own copy
+/// * `rows` - number of rows
+/// * `cols` - number of cols
+/// * `queries` - The start and end positions to plan
+/// * `algorithms` - The algorithms to plan each query with
+/// * `config` - The configuration of the tree algorithms
+///
+/// # Result
+///
+/// One result for each query and algorithm, ordered by the queries and then the algorithms
+///
+/// # Panics
+///
+/// If a tree algorithm gets an invalid configuration, see [`RrtConfig::validate`]
+pub fn run(area: &[u64], rows: &usize, cols: &usize, queries: &[Query], algorithms: &[Algorithm], config: &RrtConfig) -> Vec<BatchResult> {
+	let benchmark = Instant::now();
+	let results: Vec<BatchResult> = queries.par_iter()
+		.flat_map(|query| algorithms.par_iter().map(move |algorithm| (*query, *algorithm)))
+		.map(|((start, end), algorithm)| {
+			let calculation = Instant::now();
+			let mut field = area.to_vec();
+			let path = algorithm.plan(&mut field, rows, cols, start, end, config);
+			let duration = calculation.elapsed();
+			let length = path.windows(2)
+				.map(|pair| f64::hypot(pair[1].0 as f64 - pair[0].0 as f64, pair[1].1 as f64 - pair[0].1 as f64))
+				.sum();
+			BatchResult { algorithm, query: (start, end), path, length, duration }
+		})
+		.collect();
+
+	println!("Batch Calc: {} queries with {} algorithms: {:.6?}", queries.len(), algorithms.len(), benchmark.elapsed());
+	results
+}
@@ -0,0 +1,356 @@
+//! Benchmark of the grid algorithms over many maps with statistics
+//!
+//! 1. **Queries:** Random pairs of free fields are drawn on each map, only pairs which are connected are kept.
+//!    The optimum of each query is the shortest path of *Dijkstra* over the 8-neighbourhood with the costs 1 and √2.
+//! 2. **Runs:** Each algorithm plans each query on its own copy of the map. The runs are one after the other,
+//!    so the runtimes do not disturb each other.
+//! 3. **Statistics:** Mean, median and 95th percentile of the runtime, expansions, path length and suboptimality
+//!    (path length / optimum) per algorithm, the path length and suboptimality only over the successful runs.
+//!
+//! The expansions are the visited fields of the grid algorithms or the nodes of the trees.
+//! The trees are not bound to the grid, their suboptimality may be below 1.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::f64::consts::SQRT_2;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::{Instant, Rng};
+use crate::batch::{Algorithm, Query};
+use crate::tree::RrtConfig;
+
+const MAX_ATTEMPTS: usize = 10; // Random pairs to draw per query before giving up on a map with few connected fields
+
+/// A query with the length of its optimal path
+pub type Task = (Query, f64);
+
+/// The result of one algorithm on one query
+#[derive(Debug, Clone)]
+pub struct Record {
+	/// Number of the map
+	pub map: usize,
+	pub algorithm: Algorithm,
+	pub query: Query,
+	/// If the path connects the start and the end
+	pub success: bool,
+	/// Time of the calculation
+	pub duration: Duration,
+	/// Visited fields of the grid algorithms or nodes of the trees
+	pub expansions: usize,
+	/// Length of the path through the middle of the fields
+	pub length: f64,
+	/// Length of the optimal path on the grid
+	pub optimum: f64,
+}
+
+impl Record {
+	/// Returns the path length relative to the optimum, 1 is optimal
+	pub fn suboptimality(&self) -> f64 {
+		if self.optimum > 0.0 { self.length / self.optimum } else { 1.0 }
+	}
+}
+
+/// Mean, median and 95th percentile of a set of values
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Statistics {
+	pub mean: f64,
+	pub median: f64,
+	pub p95: f64,
+}
+
+impl Statistics {
+	/// Calculates the statistics of the values or `None` if there are no values
+	pub fn new(mut values: Vec<f64>) -> Option<Self> {
+		if values.is_empty() {
+			return None;
+		}
+		values.sort_by(f64::total_cmp);
+		let count = values.len();
+		let median = if count.is_multiple_of(2) { (values[(count / 2) - 1] + values[count / 2]) / 2.0 } else { values[count / 2] };
+		// Nearest rank: the smallest value which is greater or equal than 95% of the values
+		let p95 = values[((count as f64 * 0.95).ceil() as usize).clamp(1, count) - 1];
+		Some(Statistics {
+			mean: values.iter().sum::<f64>() / count as f64,
+			median,
+			p95,
+		})
+	}
+}
+
+/// The statistics of one algorithm over all maps and queries
+#[derive(Debug, Clone)]
+pub struct Summary {
+	pub algorithm: Algorithm,
+	/// Number of planned queries
+	pub runs: usize,
+	/// Part of the queries with a path, 0..1
+	pub success_rate: f64,
+	/// Runtime in milliseconds
+	pub runtime: Option<Statistics>,
+	pub expansions: Option<Statistics>,
+	/// Path length of the successful runs
+	pub length: Option<Statistics>,
+	/// Suboptimality of the successful runs
+	pub suboptimality: Option<Statistics>,
+}
+
+/// Entry of the open list, ordered by the lowest cost first
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Open {
+	cost: f64,
+	index: usize,
+}
+
+impl Eq for Open {}
+
+impl Ord for Open {
+	fn cmp(&self, other: &Self) -> Ordering {
+		other.cost.total_cmp(&self.cost)
+	}
+}
+
+impl PartialOrd for Open {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+/// Calculates the length of the shortest path with Dijkstra over the 8-neighbourhood
+///
+/// # Arguments
+///
+/// * `area` - The play field as a one-dimensional vector
+/// * `rows` - number of rows
+/// * `cols` - number of cols
+/// * `start` - start position (row, col)
+/// * `end` - end position (row, col)
+///
+/// # Result
+///
+/// The length of the path or `None` if the end can not be reached
+pub fn get_optimum(area: &[u64], rows: &usize, cols: &usize, start: (usize, usize), end: (usize, usize)) -> Option<f64> {
+	let goal = (end.1 * rows) + end.0;
+	let mut costs = vec![f64::MAX; area.len()];
+	let mut open = BinaryHeap::new();
+	costs[(start.1 * rows) + start.0] = 0.0;
+	open.push(Open { cost: 0.0, index: (start.1 * rows) + start.0 });
+
+	while let Some(Open { cost, index }) = open.pop() {
+		if index == goal {
+			return Some(cost);
+		}
+		if cost > costs[index] {
+			continue;
+		}
+		let (row, col) = (index % rows, index / rows);
+		for (d_row, d_col) in [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)] {
+			let (Some(row), Some(col)) = (row.checked_add_signed(d_row), col.checked_add_signed(d_col)) else { continue };
+			if row >= *rows || col >= *cols || area[(col * rows) + row] == u64::MAX {
+				continue;
+			}
+			let next = (col * rows) + row;
+			let next_cost = cost + if d_row != 0 && d_col != 0 { SQRT_2 } else { 1.0 };
+			if next_cost < costs[next] {
+				costs[next] = next_cost;
+				open.push(Open { cost: next_cost, index: next });
+			}
+		}
+	}
+	None
+}
+
+/// Draws random queries between connected free fields
+///
+/// # Arguments
+///
+/// * `rng` - Random number generator, a seeded one repeats the queries
+/// * `area` - The play field as a one-dimensional vector
+/// * `rows` - number of rows
+/// * `cols` - number of cols
+/// * `count` - Number of queries
+///
+/// # Result
+///
+/// The queries with their optimum, less than `count` if too many random pairs are not connected
+pub fn get_tasks<R: Rng>(rng: &mut R, area: &[u64], rows: &usize, cols: &usize, count: usize) -> Vec<Task> {
+	let free: Vec<usize> = (0..area.len()).filter(|index| area[*index] != u64::MAX).collect();
+	if free.is_empty() {
+		return vec![];
+	}
+	let mut random_field = || {
+		let index = free[rng.gen_range(0..free.len())];
+		(index % rows, index / rows)
+	};
+
+	let mut tasks = Vec::with_capacity(count);
+	for _ in 0..(count * MAX_ATTEMPTS) {
+		if tasks.len() == count {
+			break;
+		}
+		let (start, end) = (random_field(), random_field());
+		if let Some(optimum) = get_optimum(area, rows, cols, start, end) {
+			tasks.push(((start, end), optimum));
+		}
+	}
+	tasks
+}
+
+/// Plans all tasks with all algorithms, one after the other
+///
+/// # Arguments
+///
+/// * `map` - Number of the map for the records
+/// * `area` - The play field as a one-dimensional vector, each run works on its own copy
+/// * `rows` - number of rows
+/// * `cols` - number of cols
+/// * `tasks` - The queries with their optimum
+/// * `algorithms` - The algorithms to plan each query with
+/// * `config` - The configuration of the tree algorithms
+///
+/// # Result
+///
+/// One record for each task and algorithm, ordered by the tasks and then the algorithms
+pub fn run(map: usize, area: &[u64], rows: &usize, cols: &usize, tasks: &[Task], algorithms: &[Algorithm], config: &RrtConfig) -> Vec<Record> {
+	let mut records = Vec::with_capacity(tasks.len() * algorithms.len());
+	for ((start, end), optimum) in tasks {
+		for algorithm in algorithms {
+			let mut field = area.to_vec();
+			let calculation = Instant::now();
+			let (path, nodes) = algorithm.search(&mut field, rows, cols, *start, *end, config);
+			let duration = calculation.elapsed();
+
+			let expansions = nodes.unwrap_or_else(|| field.iter().filter(|val| **val != 0 && **val != u64::MAX).count());
+			let ends = (path.first().copied(), path.last().copied());
+			let success = ends == (Some(*start), Some(*end)) || ends == (Some(*end), Some(*start));
+			let length = path.windows(2)
+				.map(|pair| f64::hypot(pair[1].0 as f64 - pair[0].0 as f64, pair[1].1 as f64 - pair[0].1 as f64))
+				.sum();
+			records.push(Record { map, algorithm: *algorithm, query: (*start, *end), success, duration, expansions, length, optimum: *optimum });
+		}
+	}
+	records
+}
+
+/// Calculates the statistics of each algorithm
+///
+/// # Arguments
+///
+/// * `records` - The records of all maps
+/// * `algorithms` - The algorithms in the order of the summaries
+///
+/// # Result
+///
+/// One summary per algorithm
+pub fn summarize(records: &[Record], algorithms: &[Algorithm]) -> Vec<Summary> {
+	algorithms.iter()
+		.map(|algorithm| {
+			let records: Vec<&Record> = records.iter().filter(|record| record.algorithm == *algorithm).collect();
+			let successful: Vec<&&Record> = records.iter().filter(|record| record.success).collect();
+			Summary {
+				algorithm: *algorithm,
+				runs: records.len(),
+				success_rate: if records.is_empty() { 0.0 } else { successful.len() as f64 / records.len() as f64 },
+				runtime: Statistics::new(records.iter().map(|record| record.duration.as_secs_f64() * 1000.0).collect()),
+				expansions: Statistics::new(records.iter().map(|record| record.expansions as f64).collect()),
+				length: Statistics::new(successful.iter().map(|record| record.length).collect()),
+				suboptimality: Statistics::new(successful.iter().map(|record| record.suboptimality()).collect()),
+			}
+		})
+		.collect()
+}
+
+/// Writes the records as CSV with a header line
+///
+/// # Arguments:
+///
+/// * `records` - The records to write
+/// * `writer` - Where to write to, e.g. a `File`
+///
+/// # Result:
+///
+/// Error from writing
+pub fn write_csv<W: Write>(records: &[Record], mut writer: W) -> io::Result<()> {
+	writeln!(writer, "map,algorithm,start_row,start_col,end_row,end_col,success,runtime_ms,expansions,length,optimum,suboptimality")?;
+	for record in records {
+		let ((start, end), length) = (record.query, if record.success { format!("{:.3}", record.length) } else { String::new() });
+		let suboptimality = if record.success { format!("{:.4}", record.suboptimality()) } else { String::new() };
+		writeln!(writer, "{},{},{},{},{},{},{},{:.6},{},{},{:.3},{}",
+			record.map, record.algorithm.name(), start.0, start.1, end.0, end.1, record.success,
+			record.duration.as_secs_f64() * 1000.0, record.expansions, length, record.optimum, suboptimality)?;
+	}
+	Ok(())
+}
+
+/// Writes the summaries as a Markdown table, each statistic as `mean / median / p95`
+///
+/// # Arguments:
+///
+/// * `summaries` - The summaries to write
+/// * `writer` - Where to write to, e.g. `stdout`
+///
+/// # Result:
+///
+/// Error from writing
+pub fn write_table<W: Write>(summaries: &[Summary], mut writer: W) -> io::Result<()> {
+	let format = |statistics: &Option<Statistics>, precision: usize| match statistics {
+		Some(statistics) => format!("{:.*} / {:.*} / {:.*}", precision, statistics.mean, precision, statistics.median, precision, statistics.p95),
+		None => "-".to_string(),
+	};
+	writeln!(writer, "| Algorithm | Runs | Success | Runtime ms | Expansions | Length | Suboptimality |")?;
+	writeln!(writer, "|---|---|---|---|---|---|---|")?;
+	for summary in summaries {
+		writeln!(writer, "| {} | {} | {:.1}% | {} | {} | {} | {} |",
+			summary.algorithm.name(), summary.runs, summary.success_rate * 100.0, format(&summary.runtime, 2),
+			format(&summary.expansions, 0), format(&summary.length, 1), format(&summary.suboptimality, 3))?;
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::thread_rng;
+
+	/// A* is optimal on the grid, the statistics and the failed runs are counted
+	#[test]
+	fn algorithms_are_compared_to_the_optimum() {
+		let statistics = Statistics::new(vec![4.0, 1.0, 3.0, 2.0]).unwrap();
+		assert_eq!(statistics, Statistics { mean: 2.5, median: 2.5, p95: 4.0 });
+		assert!(Statistics::new(vec![]).is_none());
+
+		// A wall with a gap at the bottom
+		let (rows, cols) = (50, 50);
+		let mut area = vec![0; rows * cols];
+		for row in 0..45 {
+			area[(25 * rows) + row] = u64::MAX;
+		}
+		// Diagonal down to (44, 24), around the end of the wall over (45, 25) to (44, 26) and diagonal up to the end
+		let optimum = get_optimum(&area, &rows, &cols, (0, 0), (0, 49)).unwrap();
+		assert!((optimum - (41.0 + 49.0 * SQRT_2)).abs() < 1e-9, "{}", optimum);
+
+		let tasks = get_tasks(&mut thread_rng(), &area, &rows, &cols, 5);
+		assert_eq!(tasks.len(), 5);
+		let algorithms = [Algorithm::AStar, Algorithm::GrassfireV2];
+		let records = run(0, &area, &rows, &cols, &tasks, &algorithms, &RrtConfig::default());
+		assert_eq!(records.len(), 10);
+		assert!(records.iter().all(|record| record.success && record.expansions > 0));
+		assert!(records.iter().all(|record| record.suboptimality() >= 1.0 - 1e-9));
+
+		let summaries = summarize(&records, &algorithms);
+		assert!(summaries.iter().all(|summary| summary.runs == 5 && summary.success_rate == 1.0));
+		// A* is optimal in the 8-neighbourhood, Grassfire-V2 has only 4 neighbours
+		assert!(summaries[0].suboptimality.unwrap().p95 < 1.0 + 1e-9);
+		assert!(summaries[1].suboptimality.unwrap().mean >= summaries[0].suboptimality.unwrap().mean);
+
+		// A closed wall: no optimum and no success
+		area[(25 * rows) + 49] = u64::MAX;
+		for row in 45..49 {
+			area[(25 * rows) + row] = u64::MAX;
+		}
+		assert!(get_optimum(&area, &rows, &cols, (0, 0), (0, 49)).is_none());
+		let records = run(0, &area, &rows, &cols, &[(((0, 0), (0, 49)), 100.0)], &[Algorithm::AStar], &RrtConfig::default());
+		assert!(!records[0].success);
+		assert_eq!(summarize(&records, &[Algorithm::AStar])[0].success_rate, 0.0);
+	}
+}
@@ -0,0 +1,458 @@
+//! Command line interface of the binary
+//!
+//! `pathfinder COMMAND [OPTIONS]`, all options are named: `--name=value` or `--name` for flags.
+//! Invalid arguments are returned as errors with a message for the user instead of a panic.
+
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use image::ImageFormat;
+
+use pathfinder::batch::Algorithm;
+use pathfinder::sampler::Sampling;
+use pathfinder::smoothing::Spline;
+use pathfinder::tree::RrtConfig;
+use pathfinder::world::World;
+
+pub const USAGE: &str = "Usage: pathfinder COMMAND [OPTIONS]
+
+Commands:
+   plan       Plan from the start to the goal with each algorithm and export an image per algorithm
+   generate   Create a random map and save it as an image
+   bench      Compare the grid and tree algorithms on random queries over many maps with statistics
+   render     Export an image of the map with the start and goal
+   help       Show this message
+
+Options for the map (plan, generate, bench, render):
+   --rows=ROWS: (1000) Number of rows of a random map
+   --cols=COLS: (1000) Number of columns of a random map
+   --obstacles=NUMBER: (100) Number of random walls/obstacles, each at most ROWS/10 x COLS/10
+   --seed=SEED: (random) Seed for the random map and queries, printed on each run
+   --map=FILE: Load the map from an image instead, dark pixels are obstacles; bench loads all images of a directory
+   --world=FILE: Polygons, circles and segments as obstacles instead of the random ones, checked exactly by the trees
+
+Options for the positions (plan, bench, render):
+   --start=ROW,COL: Position of the robot, counted from 1
+   --goal=ROW,COL: The position to reach, counted from 1
+
+Options for the output:
+   --format=FORMAT: (png) Format of the images: png, bmp, jpg, tga, tiff, ppm, ... or none for no images
+   --output=NAME: (map, render) Name of the image without the extension for generate and render
+
+Options for plan and bench:
+   --algorithm=LIST: (all) Comma separated algorithms: grassfire-v1, grassfire-v2, grassfire-v3, grassfire-v4,
+        grassfire-v2-parallel, grassfire-v3-parallel, a-star, potential-field, navigation-function, coverage,
+        multi-agent, space-time, dwa, simulation, rrt-v1, rrt-v2, rrt-v3, rrt-connect, rrt-kinodynamic,
+        hybrid-a-star, visibility-graph (only with --world); bench runs only the grassfire, a-star and rrt ones
+   --step=DISTANCE: (100) Maximum distance between two nodes
+   --nodes=NUMBER: (area / step, max 16383) Maximum number of nodes
+   --goal-radius=RADIUS: (5) Radius around the end to catch the end position
+   --rewire=RADIUS|gamma:GAMMA: (200) Fixed radius or gamma * sqrt(log(n) / n) for the rewiring of RRT*
+   --steering=line|dubins:RADIUS|reeds-shepp:RADIUS: (line) Connect the nodes of RRT and RRT* with curves
+   --sampling=STRATEGY: (uniform) Random points for the trees: uniform, goal:P, gaussian:SIGMA, bridge:SIGMA, halton, sobol
+   --first: Stop as soon as the end is reached the first time
+
+Options for plan:
+   --frames: Export an image for each time step of the Space-Time-A*
+   --smooth[=SPLINE]: (catmull-rom) Shortcut and smooth the paths of the grassfire, a-star and rrt algorithms with a spline:
+        catmull-rom or b-spline, export them as NAME_smooth and their trajectory as NAME_trajectory.csv
+   --waypoints: Print the paths of the grassfire, a-star and rrt algorithms as waypoints (x, y, turn radius) for the robot
+
+Options for bench:
+   --maps=NUMBER: (10) Number of random maps, one after the other from the seed
+   --queries=NUMBER: (10) Number of queries per map, the start and goal if given and random connected ones
+   --csv=FILE: (bench.csv) File for the result of each run, the summary is printed as a table
+   --parallel: Plan the queries of each map in parallel and print one line per query and algorithm instead of the statistics";
+
+/// The subcommand to run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+	Plan,
+	Generate,
+	Bench,
+	Render,
+	Help,
+}
+
+impl Command {
+	/// Returns the name of the command as it is parsed
+	pub fn name(&self) -> &'static str {
+		match self {
+			Command::Plan => "plan",
+			Command::Generate => "generate",
+			Command::Bench => "bench",
+			Command::Render => "render",
+			Command::Help => "help",
+		}
+	}
+
+	/// Returns the options the command accepts
+	fn options(&self) -> &'static [&'static str] {
+		match self {
+			Command::Plan => &["--rows", "--cols", "--obstacles", "--seed", "--map", "--world", "--start", "--goal", "--format",
+				"--algorithm", "--step", "--nodes", "--goal-radius", "--rewire", "--steering", "--sampling", "--first", "--frames", "--smooth", "--waypoints"],
+			Command::Generate => &["--rows", "--cols", "--obstacles", "--seed", "--format", "--output"],
+			Command::Bench => &["--rows", "--cols", "--obstacles", "--seed", "--map", "--world", "--start", "--goal",
+				"--algorithm", "--step", "--nodes", "--goal-radius", "--rewire", "--steering", "--sampling", "--first", "--maps", "--queries", "--csv", "--parallel"],
+			Command::Render => &["--rows", "--cols", "--obstacles", "--seed", "--map", "--world", "--start", "--goal", "--format", "--output"],
+			Command::Help => &[],
+		}
+	}
+}
+
+impl FromStr for Command {
+	type Err = String;
+
+	/// Parses a command like `plan`, `generate`, `bench`, `render` or `help`
+	fn from_str(value: &str) -> Result<Self, Self::Err> {
+		match value.to_lowercase().as_str() {
+			"plan" => Ok(Command::Plan),
+			"generate" => Ok(Command::Generate),
+			"bench" => Ok(Command::Bench),
+			"render" => Ok(Command::Render),
+			"help" | "--help" | "-h" => Ok(Command::Help),
+			_ => Err(format!("Unknown command: {}", value)),
+		}
+	}
+}
+
+/// Where the map comes from
+#[derive(Debug, Clone)]
+pub enum MapSource {
+	/// Random rectangles on a free area
+	Random { rows: usize, cols: usize, obstacles: usize },
+	/// An image file, dark pixels are obstacles
+	Image(String),
+	/// A directory of image files, one map per image for bench
+	Directory(String),
+	/// The rasterized shapes of a world file
+	World { world: Arc<World>, rows: usize, cols: usize },
+}
+
+/// An algorithm the plan command can run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Planner {
+	/// The grid and tree algorithms, which can run in a batch as well
+	Grid(Algorithm),
+	PotentialField,
+	NavigationFunction,
+	Coverage,
+	MultiAgent,
+	SpaceTime,
+	Dwa,
+	Simulation,
+	RrtKinodynamic,
+	HybridAStar,
+	VisibilityGraph,
+}
+
+impl Planner {
+	/// All planners in the order they run
+	pub const ALL: [Planner; 21] = [
+		Planner::Grid(Algorithm::GrassfireV1),
+		Planner::Grid(Algorithm::GrassfireV2),
+		Planner::Grid(Algorithm::GrassfireV3),
+		Planner::Grid(Algorithm::GrassfireV4),
+		Planner::Grid(Algorithm::GrassfireV2Parallel),
+		Planner::Grid(Algorithm::GrassfireV3Parallel),
+		Planner::Grid(Algorithm::AStar),
+		Planner::PotentialField,
+		Planner::NavigationFunction,
+		Planner::Coverage,
+		Planner::MultiAgent,
+		Planner::SpaceTime,
+		Planner::Dwa,
+		Planner::Simulation,
+		Planner::Grid(Algorithm::RrtV1),
+		Planner::Grid(Algorithm::RrtV2),
+		Planner::Grid(Algorithm::RrtV3),
+		Planner::Grid(Algorithm::RrtConnect),
+		Planner::RrtKinodynamic,
+		Planner::HybridAStar,
+		Planner::VisibilityGraph,
+	];
+
+	/// Returns the name of the planner as it is parsed
+	pub fn name(&self) -> &'static str {
+		match self {
+			Planner::Grid(algorithm) => algorithm.name(),
+			Planner::PotentialField => "potential-field",
+			Planner::NavigationFunction => "navigation-function",
+			Planner::Coverage => "coverage",
+			Planner::MultiAgent => "multi-agent",
+			Planner::SpaceTime => "space-time",
+			Planner::Dwa => "dwa",
+			Planner::Simulation => "simulation",
+			Planner::RrtKinodynamic => "rrt-kinodynamic",
+			Planner::HybridAStar => "hybrid-a-star",
+			Planner::VisibilityGraph => "visibility-graph",
+		}
+	}
+}
+
+impl FromStr for Planner {
+	type Err = String;
+
+	/// Parses a planner like `a-star`, `dwa` or `rrt-kinodynamic`
+	fn from_str(value: &str) -> Result<Self, Self::Err> {
+		Planner::ALL.into_iter()
+			.find(|planner| planner.name() == value.to_lowercase())
+			.ok_or_else(|| format!("Unknown algorithm: {}", value))
+	}
+}
+
+/// All arguments of the command line
+#[derive(Debug, Clone)]
+pub struct Args {
+	pub command: Command,
+	pub map: MapSource,
+	/// Seed for the random map and queries, `None` for a random one
+	pub seed: Option<u64>,
+	/// Start position (row, col), counted from 0
+	pub start: Option<(usize, usize)>,
+	/// Goal position (row, col), counted from 0
+	pub goal: Option<(usize, usize)>,
+	/// The selected planners in the order they run
+	pub planners: Vec<Planner>,
+	/// Format of the images, `None` to export no images
+	pub format: Option<ImageFormat>,
+	/// Name of the image for generate and render
+	pub output: String,
+	/// Number of random maps for bench
+	pub maps: usize,
+	/// Number of queries per map for bench
+	pub queries: usize,
+	/// File for the records of bench
+	pub csv: String,
+	/// Plan the queries of bench in parallel without statistics
+	pub parallel: bool,
+	/// Export an image for each time step of the Space-Time-A*
+	pub frames: bool,
+	/// Smooth the paths of the grid and tree algorithms with this spline
+	pub smooth: Option<Spline>,
+	/// Print the paths of the grid and tree algorithms as waypoints
+	pub waypoints: bool,
+	/// Configuration of the tree algorithms, including the world
+	pub tree: RrtConfig,
+}
+
+impl Args {
+	/// Parses the arguments without the name of the binary
+	///
+	/// # Arguments
+	///
+	/// * `args` - The command followed by options in the form `--name=value` or `--name`
+	///
+	/// # Result
+	///
+	/// The arguments with defaults for the missing options or a message what is wrong
+	pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Args, String> {
+		let mut args = args.into_iter();
+		let command: Command = args.next().ok_or("Missing command")?.parse()?;
+
+		// Split into name and value, every option is allowed once
+		let mut options: Vec<(String, String)> = vec![];
+		for arg in args {
+			let Some(option) = arg.strip_prefix("--") else { return Err(format!("Unexpected argument: {}", arg)) };
+			let (name, value) = option.split_once('=').unwrap_or((option, ""));
+			let name = format!("--{}", name);
+			if !command.options().contains(&name.as_str()) {
+				return Err(format!("Unknown option for {}: {}", command.name(), name));
+			}
+			if options.iter().any(|(other, _)| *other == name) {
+				return Err(format!("Option given twice: {}", name));
+			}
+			options.push((name, value.to_string()));
+		}
+		let value = |name: &str| options.iter().find(|(other, _)| other == name).map(|(_, value)| value.as_str());
+
+		// The map
+		let rows = parse_option(value("--rows"), "--rows")?.unwrap_or(1000);
+		let cols = parse_option(value("--cols"), "--cols")?.unwrap_or(1000);
+		if rows == 0 || cols == 0 {
+			return Err(format!("The map has no fields: {}x{}", rows, cols));
+		}
+		let map = match (value("--map"), value("--world")) {
+			(Some(_), Some(_)) => return Err("Use either --map or --world".to_string()),
+			(Some(_), _) if value("--rows").is_some() || value("--cols").is_some() || value("--obstacles").is_some() =>
+				return Err("The size and obstacles come from the map file, remove --rows, --cols and --obstacles".to_string()),
+			(Some(dir), None) if Path::new(dir).is_dir() => match command {
+				Command::Bench if value("--maps").is_some() => return Err("The number of maps is the number of images in the directory, remove --maps".to_string()),
+				Command::Bench => MapSource::Directory(dir.to_string()),
+				_ => return Err(format!("The map is a directory, only bench loads all its images: {}", dir)),
+			},
+			(Some(file), None) => MapSource::Image(file.to_string()),
+			(None, Some(file)) => MapSource::World { world: Arc::new(World::load(file)?), rows, cols },
+			(None, None) => MapSource::Random { rows, cols, obstacles: parse_option(value("--obstacles"), "--obstacles")?.unwrap_or(100) },
+		};
+
+		// The tree algorithms
+		let mut tree = RrtConfig::default();
+		if let Some(step) = parse_option(value("--step"), "--step")? { tree.step_distance = step; }
+		if let Some(nodes) = parse_option(value("--nodes"), "--nodes")? { tree.max_nodes = Some(nodes); }
+		if let Some(radius) = parse_option(value("--goal-radius"), "--goal-radius")? { tree.goal_radius = radius; }
+		if let Some(rewire) = parse_option(value("--rewire"), "--rewire")? { tree.rewire_radius = rewire; }
+		if let Some(steering) = parse_option(value("--steering"), "--steering")? { tree.steering = steering; }
+		if let Some(sampling) = parse_option::<Sampling>(value("--sampling"), "--sampling")? { tree.sampling = sampling; }
+		tree.stop_on_first_solution = value("--first").is_some();
+		if let MapSource::World { world, .. } = &map {
+			tree.world = Some(world.clone());
+		}
+		tree.validate()?;
+
+		// The algorithms, all which are able to run on this map by default
+		let planners = match value("--algorithm") {
+			Some("all") | None => Planner::ALL.into_iter()
+				.filter(|planner| command != Command::Bench || matches!(planner, Planner::Grid(_)))
+				.filter(|planner| *planner != Planner::VisibilityGraph || tree.world.is_some())
+				.collect(),
+			Some(list) => list.split(',').map(|name| name.trim().parse()).collect::<Result<Vec<Planner>, String>>()?,
+		};
+		if planners.is_empty() {
+			return Err("No algorithm selected".to_string());
+		}
+		if command == Command::Bench {
+			if let Some(planner) = planners.iter().find(|planner| !matches!(planner, Planner::Grid(_))) {
+				return Err(format!("The algorithm {} can not run in a batch", planner.name()));
+			}
+		}
+		if planners.contains(&Planner::VisibilityGraph) && tree.world.is_none() {
+			return Err("The algorithm visibility-graph needs a world: --world=FILE".to_string());
+		}
+
+		// The output
+		let format = match value("--format") {
+			Some("none") => None,
+			Some(format) => match ImageFormat::from_extension(format) {
+				Some(image) if image.can_write() => Some(image),
+				_ => return Err(format!("Unknown image format: {}", format)),
+			},
+			None => Some(ImageFormat::Png),
+		};
+		if format.is_none() && [Command::Generate, Command::Render].contains(&command) {
+			return Err(format!("The format none is not possible for {}", command.name()));
+		}
+		let output = match value("--output") {
+			Some("") => return Err("Missing value for --output".to_string()),
+			Some(output) => output.to_string(),
+			None if command == Command::Generate => "map".to_string(),
+			None => "render".to_string(),
+		};
+
+		let start = value("--start").map(|value| parse_position(value, "--start")).transpose()?;
+		let goal = value("--goal").map(|value| parse_position(value, "--goal")).transpose()?;
+		if command == Command::Plan && (start.is_none() || goal.is_none()) {
+			return Err("The plan command needs a start and a goal: --start=ROW,COL --goal=ROW,COL".to_string());
+		}
+		if command == Command::Bench && start.is_some() != goal.is_some() {
+			return Err("The bench command needs both or none of --start and --goal".to_string());
+		}
+		let maps = parse_option(value("--maps"), "--maps")?.unwrap_or(10);
+		let queries = parse_option(value("--queries"), "--queries")?.unwrap_or(10);
+		if maps == 0 || queries == 0 {
+			return Err(format!("The bench command needs at least one map and query: {} maps, {} queries", maps, queries));
+		}
+		let parallel = value("--parallel").is_some();
+		if parallel && value("--csv").is_some() {
+			return Err("The parallel runs disturb each others runtime, they are not written to --csv".to_string());
+		}
+		let csv = match value("--csv") {
+			Some("") => return Err("Missing value for --csv".to_string()),
+			Some(csv) => csv.to_string(),
+			None => "bench.csv".to_string(),
+		};
+
+		Ok(Args {
+			command,
+			map,
+			seed: parse_option(value("--seed"), "--seed")?,
+			start,
+			goal,
+			planners,
+			format,
+			output,
+			maps,
+			queries,
+			csv,
+			parallel,
+			frames: value("--frames").is_some(),
+			smooth: match value("--smooth") {
+				Some("") => Some(Spline::default()),
+				value => parse_option(value, "--smooth")?,
+			},
+			waypoints: value("--waypoints").is_some(),
+			tree,
+		})
+	}
+}
+
+/// Parses the value of an option if it is given
+///
+/// # Arguments
+///
+/// * `value` - The value of the option or `None` if it is not given
+/// * `name` - Name of the option for the error message
+///
+/// # Result
+///
+/// The parsed value, `None` if it is not given or an error if it is invalid
+fn parse_option<T: FromStr>(value: Option<&str>, name: &str) -> Result<Option<T>, String> {
+	value.map(|value| value.parse().map_err(|_| format!("Invalid value for {}: {}", name, value))).transpose()
+}
+
+/// Parses a position `ROW,COL` counted from 1 into a field counted from 0
+fn parse_position(value: &str, name: &str) -> Result<(usize, usize), String> {
+	let invalid = || format!("Invalid position for {}: {}, expected ROW,COL counted from 1", name, value);
+	let (row, col) = value.split_once(',').ok_or_else(invalid)?;
+	let row = row.trim().parse::<usize>().map_err(|_| invalid())?;
+	let col = col.trim().parse::<usize>().map_err(|_| invalid())?;
+	if row == 0 || col == 0 {
+		return Err(invalid());
+	}
+	Ok((row - 1, col - 1))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse(args: &str) -> Result<Args, String> {
+		Args::parse(args.split_whitespace().map(str::to_string))
+	}
+
+	/// Positions are counted from 1 on the command line, invalid arguments are errors instead of panics
+	#[test]
+	fn arguments_are_parsed_or_rejected() {
+		let args = parse("plan --start=12,99 --goal=800,750 --algorithm=a-star,rrt-connect --seed=7 --format=bmp --nodes=500 --smooth=b-spline --waypoints").unwrap();
+		assert_eq!(args.command, Command::Plan);
+		assert_eq!((args.start, args.goal), (Some((11, 98)), Some((799, 749))));
+		assert_eq!(args.planners, vec![Planner::Grid(Algorithm::AStar), Planner::Grid(Algorithm::RrtConnect)]);
+		assert_eq!((args.seed, args.format, args.tree.max_nodes), (Some(7), Some(ImageFormat::Bmp), Some(500)));
+		assert!(matches!(args.map, MapSource::Random { rows: 1000, cols: 1000, obstacles: 100 }));
+		assert_eq!((args.smooth, args.waypoints), (Some(Spline::BSpline), true));
+		assert_eq!(parse("plan --start=1,1 --goal=2,2 --smooth").unwrap().smooth, Some(Spline::CatmullRom));
+
+		// Bench runs only the grid algorithms by default
+		let args = parse("bench --queries=20").unwrap();
+		assert!(args.planners.iter().all(|planner| matches!(planner, Planner::Grid(_))));
+		assert_eq!(args.planners.len(), Algorithm::ALL.len());
+		assert_eq!((args.maps, args.queries, args.csv.as_str()), (10, 20, "bench.csv"));
+
+		// A directory is a set of maps for bench only
+		let dir = env!("CARGO_MANIFEST_DIR");
+		assert!(matches!(parse(&format!("bench --map={}", dir)).unwrap().map, MapSource::Directory(_)));
+		assert!(parse(&format!("bench --map={} --maps=3", dir)).is_err());
+		assert!(parse(&format!("render --map={}", dir)).is_err());
+
+		for invalid in ["", "fly", "plan --start=1,1", "plan --start=0,1 --goal=2,2", "plan --start=1,1 --goal=2,2 --algorithm=bogus",
+			"plan --start=1,1 --goal=2,2 --rows=x", "generate --start=1,1", "bench --algorithm=dwa", "bench --start=1,1",
+			"render --format=none", "render --map=a.png --rows=10", "plan --start=1,1 --goal=2,2 --algorithm=visibility-graph",
+			"plan --start=1,1 --goal=2,2 plan", "render --seed=1 --seed=2", "bench --maps=0", "bench --csv=", "bench --parallel --csv=a.csv",
+			"plan --start=1,1 --goal=2,2 --step=0", "plan --start=1,1 --goal=2,2 --goal-radius=-1", "plan --start=1,1 --goal=2,2 --maps=2", "plan --start=1,1 --goal=2,2 --smooth=bezier",
+			"plan --start=1,1 --goal=2,2 --rewire=0", "plan --start=1,1 --goal=2,2 --rewire=-5", "plan --start=1,1 --goal=2,2 --rewire=NaN",
+			"plan --start=1,1 --goal=2,2 --rewire=gamma:-1", "plan --start=1,1 --goal=2,2 --rewire=gamma:NaN"] {
+			assert!(parse(invalid).is_err(), "{} should be rejected", invalid);
+		}
+	}
+}
@@ -0,0 +1,278 @@
+//! Coverage path planning to sweep the whole free area
+//!
+//! # Boustrophedon decomposition
+//!
+//! The area is swept column by column. Each column has intervals of free rows. An interval continues the cell of the
+//! interval in the previous column if they overlap and neither of them overlaps an other interval.
+//! On each split or merge around an obstacle, new cells begin.
+//!
+//! # Lawnmower path
+//!
+//! Each cell is covered by sweeps along the columns, one tool width apart, in alternating directions.
+//! Between two sweeps the path follows the cell boundary, so it never leaves the cell.
+//! From a covered cell, the next one is the nearest unvisited cell reached by a grassfire over the free fields.
+
+use std::collections::VecDeque;
+
+use crate::Instant;
+
+/// The result of the coverage planner
+#[derive(Debug)]
+pub struct CoverageResult {
+	/// The path from the start over all reachable cells
+	pub path: Vec<(usize, usize)>,
+	/// Number of cells of the boustrophedon decomposition
+	pub cells: usize,
+	/// Percentage of the free fields covered by the tool
+	pub coverage: f64,
+	/// Percentage of the swept area which was covered more than once
+	pub overlap: f64,
+}
+
+/// Inner type representing a cell: The interval of free rows `(col, first row, last row)` for each column
+type Cell = Vec<(usize, usize, usize)>;
+
+/// Inner type representing the entry of a cell: The cell, if it is swept from its last column and if the first sweep goes to lower rows
+type Entry = (usize, bool, bool);
+
+/// Calculates a path to cover the whole free area with a tool
+///
+/// # Arguments
+///
+/// * `area` - The play field as a one-dimensional vector, the number of the cell is written into each free field
+/// * `rows` - number of rows
+/// * `cols` - number of cols
+/// * `start` - start position (row, col)
+/// * `tool_width` - Width of the tool in fields, the distance between two sweeps
+///
+/// # Result
+///
+/// The path with the number of cells, the coverage and the overlap
+pub fn calculate(area: &mut [u64], rows: &usize, cols: &usize, start: (usize, usize), tool_width: usize) -> CoverageResult {
+	let benchmark = Instant::now();
+	let tool_width = usize::max(1, tool_width);
+	let free: Vec<bool> = area.iter().map(|value| *value != u64::MAX).collect();
+
+	// 1. Decompose the free area into cells
+	let cells = decompose(&free, rows, cols);
+	for (key, cell) in cells.iter().enumerate() {
+		for (col, first, last) in cell {
+			for row in *first..=*last {
+				area[(col * rows) + row] = key as u64 + 1;
+			}
+		}
+	}
+	println!("Coverage Cells: {}: {:.6?}", cells.len(), benchmark.elapsed());
+
+	// 2. Cover the nearest unvisited cell until no one is reachable anymore
+	let mut path = vec![];
+	let mut visited = vec![false; cells.len()];
+	if free[(start.1 * rows) + start.0] {
+		path.push(start);
+		while let Some((connection, (key, reverse, upwards))) = find_next_cell(&free, rows, cols, &cells, &visited, *path.last().unwrap_or(&start), tool_width) {
+			path.extend(connection.into_iter().skip(1));
+			path.extend(sweep_cell(&cells[key], reverse, upwards, tool_width).into_iter().skip(1));
+			visited[key] = true;
+		}
+	}
+	println!("Coverage Calc: {:.6?}", benchmark.elapsed());
+
+	// 3. Coverage and overlap of the tool along the path
+	let mut covered = vec![false; area.len()];
+	let (before, after) = ((tool_width - 1) / 2, tool_width / 2);
+	for (row, col) in &path {
+		for c in col.saturating_sub(before)..=usize::min(cols - 1, col + after) {
+			for r in row.saturating_sub(before)..=usize::min(rows - 1, row + after) {
+				covered[(c * rows) + r] |= free[(c * rows) + r];
+			}
+		}
+	}
+	let free_count = free.iter().filter(|free| **free).count();
+	let covered_count = covered.iter().filter(|covered| **covered).count();
+	let length: f64 = path.windows(2).map(|pair| f64::hypot(pair[1].0 as f64 - pair[0].0 as f64, pair[1].1 as f64 - pair[0].1 as f64)).sum();
+	let swept = (length + 1.0) * tool_width as f64;
+	let coverage = if free_count == 0 { 0.0 } else { covered_count as f64 * 100.0 / free_count as f64 };
+	let overlap = if path.is_empty() { 0.0 } else { f64::max(0.0, (swept - covered_count as f64) * 100.0 / swept) };
+
+	println!("Coverage Path length: {}", path.len());
+	println!("Coverage Covered: {:.2}% Overlap: {:.2}%", coverage, overlap);
+	CoverageResult {
+		path,
+		cells: cells.len(),
+		coverage,
+		overlap,
+	}
+}
+
+/// Decomposes the free fields into boustrophedon cells
+fn decompose(free: &[bool], rows: &usize, cols: &usize) -> Vec<Cell> {
+	let mut cells: Vec<Cell> = vec![];
+	// Intervals of the previous column with the index of their cell
+	let mut previous: Vec<(usize, usize, usize)> = vec![];
+
+	for col in 0..*cols {
+		let mut intervals: Vec<(usize, usize)> = vec![];
+		let mut row = 0;
+		while row < *rows {
+			if free[(col * rows) + row] {
+				let first = row;
+				while row + 1 < *rows && free[(col * rows) + row + 1] {
+					row += 1;
+				}
+				intervals.push((first, row));
+			}
+			row += 1;
+		}
+
+		let overlaps = |a: (usize, usize), b: (usize, usize)| a.0 <= b.1 && b.0 <= a.1;
+		let mut current = vec![];
+		for interval in &intervals {
+			let left: Vec<&(usize, usize, usize)> = previous.iter().filter(|prev| overlaps((prev.0, prev.1), *interval)).collect();
+			let key = match left.as_slice() {
+				[prev] if intervals.iter().filter(|other| overlaps((prev.0, prev.1), **other)).count() == 1 => prev.2,
+				_ => {
+					cells.push(vec![]);
+					cells.len() - 1
+				},
+			};
+			cells[key].push((col, interval.0, interval.1));
+			current.push((interval.0, interval.1, key));
+		}
+		previous = current;
+	}
+	cells
+}
+
+/// Searches the nearest entry of an unvisited cell with a grassfire over the free fields
+///
+/// Each cell can be entered on both ends of the first and the last sweep.
+///
+/// # Result
+///
+/// The path to the entry and the entry
+fn find_next_cell(free: &[bool], rows: &usize, cols: &usize, cells: &[Cell], visited: &[bool], from: (usize, usize), tool_width: usize) -> Option<(Vec<(usize, usize)>, Entry)> {
+	let mut entries: Vec<Option<Entry>> = vec![None; free.len()];
+	for (key, cell) in cells.iter().enumerate().filter(|(key, _)| !visited[*key]) {
+		for reverse in [false, true] {
+			let columns = get_sweeps(cell, reverse, tool_width);
+			let (col, first, last) = cell[columns[0]];
+			entries[(col * rows) + last] = Some((key, reverse, true));
+			entries[(col * rows) + first] = Some((key, reverse, false));
+		}
+	}
+
+	let mut parents = vec![usize::MAX; free.len()];
+	let mut queue = VecDeque::from([from]);
+	parents[(from.1 * rows) + from.0] = (from.1 * rows) + from.0;
+	while let Some(field) = queue.pop_front() {
+		let index = (field.1 * rows) + field.0;
+		if let Some(entry) = entries[index] {
+			let mut connection = vec![field];
+			let mut current = index;
+			while parents[current] != current {
+				current = parents[current];
+				connection.push((current % rows, current / rows));
+			}
+			connection.reverse();
+			return Some((connection, entry));
+		}
+		for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (-1, 1), (1, -1), (1, 1)] {
+			let (x, y) = (field.0 as i64 + dx, field.1 as i64 + dy);
+			if x < 0 || y < 0 || x >= *rows as i64 || y >= *cols as i64 {
+				continue;
+			}
+			let next = (y as usize * rows) + x as usize;
+			if free[next] && parents[next] == usize::MAX {
+				parents[next] = index;
+				queue.push_back((x as usize, y as usize));
+			}
+		}
+	}
+	None
+}
+
+/// Returns the indexes of the columns of the cell to sweep, one tool width apart and the last one near the end of the cell
+fn get_sweeps(cell: &Cell, reverse: bool, tool_width: usize) -> Vec<usize> {
+	let last = cell.len() - 1;
+	let mut sweeps = vec![usize::min((tool_width - 1) / 2, last)];
+	while sweeps[sweeps.len() - 1] + (tool_width / 2) < last {
+		let previous = sweeps[sweeps.len() - 1];
+		sweeps.push(usize::max(previous + 1, usize::min(previous + tool_width, last - (tool_width / 2))));
+	}
+	if reverse {
+		sweeps.iter().map(|sweep| last - sweep).collect()
+	} else {
+		sweeps
+	}
+}
+
+/// Returns the lawnmower path through the cell
+///
+/// # Arguments
+///
+/// * `cell` - The cell to sweep
+/// * `reverse` - Sweep from the last column to the first one
+/// * `upwards` - The first sweep goes from the last to the first row of its interval
+/// * `tool_width` - Distance between two sweeps
+fn sweep_cell(cell: &Cell, reverse: bool, upwards: bool, tool_width: usize) -> Vec<(usize, usize)> {
+	let mut path: Vec<(usize, usize)> = vec![];
+	let mut upwards = upwards;
+	for sweep in get_sweeps(cell, reverse, tool_width) {
+		// Follow the cell boundary column by column to the next sweep
+		if let Some(&(row, col)) = path.last() {
+			let mut index = cell.iter().position(|(c, _, _)| *c == col).unwrap_or(sweep);
+			let mut row = row;
+			while index != sweep {
+				let next = if sweep > index { index + 1 } else { index - 1 };
+				let target = row.clamp(usize::max(cell[index].1, cell[next].1), usize::min(cell[index].2, cell[next].2));
+				path.extend(get_column(cell[index].0, row, target).skip(1));
+				path.push((target, cell[next].0));
+				row = target;
+				index = next;
+			}
+		}
+
+		// Sweep along the column from one end of the interval to the other one
+		let (col, first, last) = cell[sweep];
+		let (from, to) = if upwards { (last, first) } else { (first, last) };
+		let begin = path.last().map_or(from, |(row, _)| *row);
+		path.extend(get_column(col, begin, from).skip(usize::from(!path.is_empty())));
+		path.extend(get_column(col, from, to).skip(1));
+		upwards = !upwards;
+	}
+	path
+}
+
+/// Returns the fields along a column from one row to an other one, both included
+fn get_column(col: usize, from: usize, to: usize) -> Box<dyn Iterator<Item = (usize, usize)>> {
+	if from <= to {
+		Box::new((from..=to).map(move |row| (row, col)))
+	} else {
+		Box::new((to..=from).rev().map(move |row| (row, col)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Around a single obstacle, the free area is split into four cells which are covered completely
+	#[test]
+	fn covers_all_cells() {
+		let (rows, cols) = (40, 40);
+		let mut area = vec![0; rows * cols];
+		for col in 15..25 {
+			for row in 10..30 {
+				area[(col * rows) + row] = u64::MAX;
+			}
+		}
+
+		let result = calculate(&mut area.clone(), &rows, &cols, (0, 0), 3);
+		assert_eq!(result.cells, 4);
+		assert!((result.coverage - 100.0).abs() < 1e-9, "Only {}% covered", result.coverage);
+		for pair in result.path.windows(2) {
+			assert!(pair[0].0.abs_diff(pair[1].0) <= 1 && pair[0].1.abs_diff(pair[1].1) <= 1, "Jump from {:?} to {:?}", pair[0], pair[1]);
+			assert_ne!(area[(pair[1].1 * rows) + pair[1].0], u64::MAX);
+		}
+	}
+}
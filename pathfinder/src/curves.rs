@@ -0,0 +1,524 @@
+//! Curves for vehicles with a minimum turning radius
+//!
+//! # Dubins
+//!
+//! The shortest path between two poses for a vehicle which can only drive forward is one of six words,
+//! each built from three segments: a left turn (L), a right turn (R) or a straight line (S) with the minimum turning radius.
+//! `LSL`, `RSR`, `LSR`, `RSL`, `RLR` and `LRL`
+//!
+//! # Reeds-Shepp
+//!
+//! If the vehicle may also drive backwards, the shortest path is one of 48 words with up to five segments (Reeds and Shepp, 1990).
+//! They are derived from nine base formulas by driving them backwards in time (timeflip), mirroring them (reflect)
+//! and driving them from the end to the start (backwards).
+//!
+//! # Steering
+//!
+//! Both curves can be used as the steering function of the tree algorithms instead of straight lines, see [`Steering`].
+
+use std::f64::consts::PI;
+use std::str::FromStr;
+use crate::robot::{normalize_angle, Pose};
+use crate::tree::is_line_free;
+
+const ZERO: f64 = 10.0 * f64::EPSILON;
+
+/// Direction of a segment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Turn {
+	/// Turn left with the minimum turning radius
+	Left,
+	/// Drive straight
+	Straight,
+	/// Turn right with the minimum turning radius
+	Right,
+}
+
+/// A segment of a curve
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segment {
+	/// Direction of the segment
+	pub turn: Turn,
+	/// Length of the segment, negative if driven backwards
+	pub length: f64,
+}
+
+/// A curve from one pose to an other one, built from segments with the minimum turning radius
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve {
+	/// Start pose of the curve
+	pub start: Pose,
+	/// Minimum turning radius
+	pub radius: f64,
+	/// All segments
+	pub segments: Vec<Segment>,
+}
+
+impl Curve {
+	/// Total length of the curve
+	pub fn length(&self) -> f64 {
+		self.segments.iter().map(|segment| segment.length.abs()).sum()
+	}
+
+	/// Returns the pose after the given distance on the curve
+	pub fn pose_at(&self, distance: f64) -> Pose {
+		let mut pose = self.start;
+		let mut remaining = distance;
+		for segment in &self.segments {
+			let length = f64::min(remaining, segment.length.abs());
+			pose = move_on_segment(&pose, segment, length, self.radius);
+			remaining -= length;
+			if remaining <= 0.0 {
+				break;
+			}
+		}
+		pose
+	}
+
+	/// Checks the curve against the obstacles of the area, sampled with one pose per field
+	pub fn is_free(&self, area: &[u64], rows: &usize, cols: &usize) -> bool {
+		is_trajectory_free(area, rows, cols, &self.sample(1.0))
+	}
+
+	/// Returns poses along the curve, at most `step` apart, including the start and the end
+	pub fn sample(&self, step: f64) -> Vec<Pose> {
+		let mut result = vec![self.start];
+		let mut pose = self.start;
+		for segment in &self.segments {
+			let length = segment.length.abs();
+			let steps = f64::max(1.0, (length / step).ceil()) as usize;
+			let start = pose;
+			for i in 1..=steps {
+				pose = move_on_segment(&start, segment, length * i as f64 / steps as f64, self.radius);
+				result.push(pose);
+			}
+		}
+		result
+	}
+}
+
+/// Steering function which connects two poses
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Steering {
+	/// A straight line, the heading of both poses is ignored
+	#[default]
+	Line,
+	/// Dubins curve with the given minimum turning radius
+	Dubins(f64),
+	/// Reeds-Shepp curve with the given minimum turning radius
+	ReedsShepp(f64),
+}
+
+impl FromStr for Steering {
+	type Err = String;
+
+	/// Parses a steering function like `line`, `dubins:20` or `reeds-shepp:20`
+	fn from_str(value: &str) -> Result<Self, Self::Err> {
+		let radius = |val: &str| match val.parse::<f64>() {
+			Ok(radius) if radius > 0.0 => Ok(radius),
+			_ => Err(format!("Invalid turning radius: {}", value)),
+		};
+		match value.split_once(':') {
+			None if value == "line" => Ok(Steering::Line),
+			Some(("dubins", val)) => Ok(Steering::Dubins(radius(val)?)),
+			Some(("reeds-shepp", val)) => Ok(Steering::ReedsShepp(radius(val)?)),
+			_ => Err(format!("Unknown steering function: {}", value)),
+		}
+	}
+}
+
+impl Steering {
+	/// Returns the curve from one pose to the other one
+	///
+	/// A line starts with the heading towards the end and is built from one straight segment
+	pub fn curve(&self, from: &Pose, to: &Pose) -> Curve {
+		match self {
+			Steering::Line => Curve {
+				start: Pose { heading: (to.y - from.y).atan2(to.x - from.x), ..*from },
+				radius: f64::INFINITY,
+				segments: vec![Segment { turn: Turn::Straight, length: from.distance(to) }],
+			},
+			Steering::Dubins(radius) => dubins(from, to, *radius),
+			Steering::ReedsShepp(radius) => reeds_shepp(from, to, *radius),
+		}
+	}
+}
+
+/// Checks if the lines between all poses are free of obstacles and inside the area
+///
+/// # Arguments:
+///
+/// * `area` - The area as a vector of u64 where every obstacle is u64::MAX
+/// * `rows` - Number of rows, where rows x cols is the size of the area
+/// * `cols` - Number of columns, where rows x cols is the size of the area
+/// * `poses` - The poses to check, e.g. sampled from a curve
+///
+/// # Result:
+///
+/// If no line between two poses hits an obstacle or leaves the area
+pub fn is_trajectory_free(area: &[u64], rows: &usize, cols: &usize, poses: &[Pose]) -> bool {
+	poses.windows(2).all(|pair| match (pair[0].field(rows, cols), pair[1].field(rows, cols)) {
+		(Some(from), Some(to)) => is_line_free(area, rows, from, to),
+		_ => false,
+	})
+}
+
+/// Moves the pose along a segment for the given (positive) distance
+fn move_on_segment(pose: &Pose, segment: &Segment, distance: f64, radius: f64) -> Pose {
+	let velocity = if segment.length < 0.0 { -1.0 } else { 1.0 };
+	let angular_velocity = match segment.turn {
+		Turn::Left => velocity / radius,
+		Turn::Straight => 0.0,
+		Turn::Right => -velocity / radius,
+	};
+	pose.simulate(velocity, angular_velocity, distance)
+}
+
+/// Calculates the shortest Dubins curve between two poses
+///
+/// # Arguments:
+///
+/// * `from` - Start pose
+/// * `to` - End pose
+/// * `radius` - Minimum turning radius
+///
+/// # Result:
+///
+/// The shortest of all six Dubins words
+pub fn dubins(from: &Pose, to: &Pose, radius: f64) -> Curve {
+	// Normalize the problem: start in (0, 0) and the end on the x-axis in a distance `d` of turning radii
+	let dx = to.x - from.x;
+	let dy = to.y - from.y;
+	let d = f64::hypot(dx, dy) / radius;
+	let theta = mod2pi(dy.atan2(dx));
+	let alpha = mod2pi(from.heading - theta);
+	let beta = mod2pi(to.heading - theta);
+
+	let words: [(Word, [Turn; 3]); 6] = [
+		(dubins_lsl, [Turn::Left, Turn::Straight, Turn::Left]),
+		(dubins_rsr, [Turn::Right, Turn::Straight, Turn::Right]),
+		(dubins_lsr, [Turn::Left, Turn::Straight, Turn::Right]),
+		(dubins_rsl, [Turn::Right, Turn::Straight, Turn::Left]),
+		(dubins_rlr, [Turn::Right, Turn::Left, Turn::Right]),
+		(dubins_lrl, [Turn::Left, Turn::Right, Turn::Left]),
+	];
+
+	let (lengths, turns) = words.iter()
+		.filter_map(|(word, turns)| word(alpha, beta, d).map(|lengths| (lengths, *turns)))
+		.min_by(|a, b| (a.0.0 + a.0.1 + a.0.2).total_cmp(&(b.0.0 + b.0.1 + b.0.2)))
+		.unwrap_or(((0.0, 0.0, 0.0), [Turn::Straight; 3]));
+
+	Curve {
+		start: *from,
+		radius,
+		segments: vec![
+			Segment { turn: turns[0], length: lengths.0 * radius },
+			Segment { turn: turns[1], length: lengths.1 * radius },
+			Segment { turn: turns[2], length: lengths.2 * radius },
+		],
+	}
+}
+
+/// Calculates the normalized lengths of the three segments of a word from `alpha`, `beta` and `d` or `None` if impossible
+type Word = fn(f64, f64, f64) -> Option<(f64, f64, f64)>;
+
+/// Normalizes an angle to the range 0..2π
+fn mod2pi(angle: f64) -> f64 {
+	angle.rem_euclid(2.0 * PI)
+}
+
+fn dubins_lsl(alpha: f64, beta: f64, d: f64) -> Option<(f64, f64, f64)> {
+	let (sa, ca, sb, cb) = (alpha.sin(), alpha.cos(), beta.sin(), beta.cos());
+	let p_squared = 2.0 + (d * d) - (2.0 * (alpha - beta).cos()) + (2.0 * d * (sa - sb));
+	if p_squared < 0.0 {
+		return None;
+	}
+	let tmp = (cb - ca).atan2(d + sa - sb);
+	Some((mod2pi(-alpha + tmp), p_squared.sqrt(), mod2pi(beta - tmp)))
+}
+
+fn dubins_rsr(alpha: f64, beta: f64, d: f64) -> Option<(f64, f64, f64)> {
+	let (sa, ca, sb, cb) = (alpha.sin(), alpha.cos(), beta.sin(), beta.cos());
+	let p_squared = 2.0 + (d * d) - (2.0 * (alpha - beta).cos()) + (2.0 * d * (sb - sa));
+	if p_squared < 0.0 {
+		return None;
+	}
+	let tmp = (ca - cb).atan2(d - sa + sb);
+	Some((mod2pi(alpha - tmp), p_squared.sqrt(), mod2pi(-beta + tmp)))
+}
+
+fn dubins_lsr(alpha: f64, beta: f64, d: f64) -> Option<(f64, f64, f64)> {
+	let (sa, ca, sb, cb) = (alpha.sin(), alpha.cos(), beta.sin(), beta.cos());
+	let p_squared = -2.0 + (d * d) + (2.0 * (alpha - beta).cos()) + (2.0 * d * (sa + sb));
+	if p_squared < 0.0 {
+		return None;
+	}
+	let p = p_squared.sqrt();
+	let tmp = (-ca - cb).atan2(d + sa + sb) - (-2.0f64).atan2(p);
+	Some((mod2pi(-alpha + tmp), p, mod2pi(-beta + tmp)))
+}
+
+fn dubins_rsl(alpha: f64, beta: f64, d: f64) -> Option<(f64, f64, f64)> {
+	let (sa, ca, sb, cb) = (alpha.sin(), alpha.cos(), beta.sin(), beta.cos());
+	let p_squared = -2.0 + (d * d) + (2.0 * (alpha - beta).cos()) - (2.0 * d * (sa + sb));
+	if p_squared < 0.0 {
+		return None;
+	}
+	let p = p_squared.sqrt();
+	let tmp = (ca + cb).atan2(d - sa - sb) - 2.0f64.atan2(p);
+	Some((mod2pi(alpha - tmp), p, mod2pi(beta - tmp)))
+}
+
+fn dubins_rlr(alpha: f64, beta: f64, d: f64) -> Option<(f64, f64, f64)> {
+	let (sa, ca, sb, cb) = (alpha.sin(), alpha.cos(), beta.sin(), beta.cos());
+	let tmp = (6.0 - (d * d) + (2.0 * (alpha - beta).cos()) + (2.0 * d * (sa - sb))) / 8.0;
+	if tmp.abs() > 1.0 {
+		return None;
+	}
+	let p = mod2pi((2.0 * PI) - tmp.acos());
+	let t = mod2pi(alpha - (ca - cb).atan2(d - sa + sb) + (p / 2.0));
+	Some((t, p, mod2pi(alpha - beta - t + p)))
+}
+
+fn dubins_lrl(alpha: f64, beta: f64, d: f64) -> Option<(f64, f64, f64)> {
+	let (sa, ca, sb, cb) = (alpha.sin(), alpha.cos(), beta.sin(), beta.cos());
+	let tmp = (6.0 - (d * d) + (2.0 * (alpha - beta).cos()) + (2.0 * d * (sb - sa))) / 8.0;
+	if tmp.abs() > 1.0 {
+		return None;
+	}
+	let p = mod2pi((2.0 * PI) - tmp.acos());
+	let t = mod2pi(-alpha - (ca - cb).atan2(d + sa - sb) + (p / 2.0));
+	Some((t, p, mod2pi(beta - alpha - t + p)))
+}
+
+/// Calculates the shortest Reeds-Shepp curve between two poses
+///
+/// # Arguments:
+///
+/// * `from` - Start pose
+/// * `to` - End pose
+/// * `radius` - Minimum turning radius
+///
+/// # Result:
+///
+/// The shortest of all 48 Reeds-Shepp words, backward segments have a negative length
+pub fn reeds_shepp(from: &Pose, to: &Pose, radius: f64) -> Curve {
+	// Normalize the problem: start in (0, 0) with heading 0 and distances in turning radii
+	let dx = to.x - from.x;
+	let dy = to.y - from.y;
+	let (sin, cos) = from.heading.sin_cos();
+	let x = ((cos * dx) + (sin * dy)) / radius;
+	let y = ((cos * dy) - (sin * dx)) / radius;
+	let phi = normalize_angle(to.heading - from.heading);
+
+	use Turn::{Left as L, Right as R, Straight as S};
+	let words: [(ReedsSheppWord, &[Turn], bool); 12] = [
+		// CSC
+		(lp_sp_lp, &[L, S, L], false),
+		(lp_sp_rp, &[L, S, R], false),
+		// CCC
+		(lp_rm_l, &[L, R, L], false),
+		(lp_rm_l, &[L, R, L], true),
+		// CCCC
+		(lp_rup_lum_rm, &[L, R, L, R], false),
+		(lp_rum_lum_rp, &[L, R, L, R], false),
+		// CCSC
+		(lp_rm_sm_lm, &[L, R, S, L], false),
+		(lp_rm_sm_rm, &[L, R, S, R], false),
+		(lp_rm_sm_lm, &[L, R, S, L], true),
+		(lp_rm_sm_rm, &[L, R, S, R], true),
+		// CCSCC
+		(lp_rm_s_lm_rp, &[L, R, S, L, R], false),
+		(lp_rm_s_lm_rp, &[L, R, S, L, R], true),
+	];
+
+	let mut best: Option<(f64, Vec<Segment>)> = None;
+	for (word, turns, backwards) in words {
+		// Driving the word from the end to the start
+		let (x, y) = if backwards { ((x * phi.cos()) + (y * phi.sin()), (x * phi.sin()) - (y * phi.cos())) } else { (x, y) };
+
+		// Timeflip and reflect the base formula
+		for (timeflip, reflect) in [(false, false), (true, false), (false, true), (true, true)] {
+			let x = if timeflip { -x } else { x };
+			let y = if reflect { -y } else { y };
+			let phi = if timeflip != reflect { -phi } else { phi };
+			let Some(lengths) = word(x, y, phi) else { continue };
+
+			let length: f64 = lengths.iter().map(|length| length.abs()).sum();
+			if best.as_ref().is_some_and(|(best, _)| *best <= length) {
+				continue;
+			}
+
+			let mut segments: Vec<Segment> = turns.iter().zip(lengths.iter())
+				.map(|(turn, length)| Segment {
+					turn: match (turn, reflect) {
+						(Turn::Left, true) => Turn::Right,
+						(Turn::Right, true) => Turn::Left,
+						(turn, _) => *turn,
+					},
+					length: if timeflip { -length * radius } else { length * radius },
+				})
+				.collect();
+			if backwards {
+				segments.reverse();
+			}
+			best = Some((length, segments));
+		}
+	}
+
+	Curve {
+		start: *from,
+		radius,
+		segments: best.map(|(_, segments)| segments).unwrap_or_default(),
+	}
+}
+
+/// Calculates the normalized lengths of all segments of a base formula from `x`, `y` and `phi` or `None` if impossible
+type ReedsSheppWord = fn(f64, f64, f64) -> Option<Vec<f64>>;
+
+/// Returns the distance and angle of the given vector
+fn polar(x: f64, y: f64) -> (f64, f64) {
+	(f64::hypot(x, y), y.atan2(x))
+}
+
+/// Helper for the formulas 8.7 and 8.8
+fn tau_omega(u: f64, v: f64, xi: f64, eta: f64, phi: f64) -> (f64, f64) {
+	let delta = normalize_angle(u - v);
+	let a = u.sin() - delta.sin();
+	let b = u.cos() - delta.cos() - 1.0;
+	let t1 = ((eta * a) - (xi * b)).atan2((xi * a) + (eta * b));
+	let t2 = (2.0 * (delta.cos() - v.cos() - u.cos())) + 3.0;
+	let tau = if t2 < 0.0 { normalize_angle(t1 + PI) } else { normalize_angle(t1) };
+	(tau, normalize_angle(tau - u + v - phi))
+}
+
+/// Formula 8.1: L+ S+ L+
+fn lp_sp_lp(x: f64, y: f64, phi: f64) -> Option<Vec<f64>> {
+	let (u, t) = polar(x - phi.sin(), y - 1.0 + phi.cos());
+	let v = normalize_angle(phi - t);
+	(t >= -ZERO && v >= -ZERO).then(|| vec![t, u, v])
+}
+
+/// Formula 8.2: L+ S+ R+
+fn lp_sp_rp(x: f64, y: f64, phi: f64) -> Option<Vec<f64>> {
+	let (u1, t1) = polar(x + phi.sin(), y - 1.0 - phi.cos());
+	let u1 = u1 * u1;
+	if u1 < 4.0 {
+		return None;
+	}
+	let u = (u1 - 4.0).sqrt();
+	let t = normalize_angle(t1 + 2.0f64.atan2(u));
+	let v = normalize_angle(t - phi);
+	(t >= -ZERO && v >= -ZERO).then(|| vec![t, u, v])
+}
+
+/// Formula 8.3: L+ R- L
+fn lp_rm_l(x: f64, y: f64, phi: f64) -> Option<Vec<f64>> {
+	let (u1, theta) = polar(x - phi.sin(), y - 1.0 + phi.cos());
+	if u1 > 4.0 {
+		return None;
+	}
+	let u = -2.0 * (0.25 * u1).asin();
+	let t = normalize_angle(theta + (0.5 * u) + PI);
+	let v = normalize_angle(phi - t + u);
+	(t >= -ZERO && u <= ZERO).then(|| vec![t, u, v])
+}
+
+/// Formula 8.7: L+ R+ L- R-
+fn lp_rup_lum_rm(x: f64, y: f64, phi: f64) -> Option<Vec<f64>> {
+	let xi = x + phi.sin();
+	let eta = y - 1.0 - phi.cos();
+	let rho = 0.25 * (2.0 + f64::hypot(xi, eta));
+	if rho > 1.0 {
+		return None;
+	}
+	let u = rho.acos();
+	let (t, v) = tau_omega(u, -u, xi, eta, phi);
+	(t >= -ZERO && v <= ZERO).then(|| vec![t, u, -u, v])
+}
+
+/// Formula 8.8: L+ R- L- R+
+fn lp_rum_lum_rp(x: f64, y: f64, phi: f64) -> Option<Vec<f64>> {
+	let xi = x + phi.sin();
+	let eta = y - 1.0 - phi.cos();
+	let rho = (20.0 - (xi * xi) - (eta * eta)) / 16.0;
+	if !(0.0..=1.0).contains(&rho) {
+		return None;
+	}
+	let u = -rho.acos();
+	if u < -0.5 * PI {
+		return None;
+	}
+	let (t, v) = tau_omega(u, u, xi, eta, phi);
+	(t >= -ZERO && v >= -ZERO).then(|| vec![t, u, u, v])
+}
+
+/// Formula 8.9: L+ R-(π/2) S- L-
+fn lp_rm_sm_lm(x: f64, y: f64, phi: f64) -> Option<Vec<f64>> {
+	let (rho, theta) = polar(x - phi.sin(), y - 1.0 + phi.cos());
+	if rho < 2.0 {
+		return None;
+	}
+	let r = ((rho * rho) - 4.0).sqrt();
+	let u = 2.0 - r;
+	let t = normalize_angle(theta + r.atan2(-2.0));
+	let v = normalize_angle(phi - (0.5 * PI) - t);
+	(t >= -ZERO && u <= ZERO && v <= ZERO).then(|| vec![t, -0.5 * PI, u, v])
+}
+
+/// Formula 8.10: L+ R-(π/2) S- R-
+fn lp_rm_sm_rm(x: f64, y: f64, phi: f64) -> Option<Vec<f64>> {
+	let xi = x + phi.sin();
+	let eta = y - 1.0 - phi.cos();
+	let (rho, theta) = polar(-eta, xi);
+	if rho < 2.0 {
+		return None;
+	}
+	let t = theta;
+	let u = 2.0 - rho;
+	let v = normalize_angle(t + (0.5 * PI) - phi);
+	(t >= -ZERO && u <= ZERO && v <= ZERO).then(|| vec![t, -0.5 * PI, u, v])
+}
+
+/// Formula 8.11: L+ R-(π/2) S- L-(π/2) R+
+fn lp_rm_s_lm_rp(x: f64, y: f64, phi: f64) -> Option<Vec<f64>> {
+	let xi = x + phi.sin();
+	let eta = y - 1.0 - phi.cos();
+	let (rho, _) = polar(xi, eta);
+	if rho < 2.0 {
+		return None;
+	}
+	let u = 4.0 - ((rho * rho) - 4.0).sqrt();
+	if u > ZERO {
+		return None;
+	}
+	let t = normalize_angle((((4.0 - u) * xi) - (2.0 * eta)).atan2((-2.0 * xi) + ((u - 4.0) * eta)));
+	let v = normalize_angle(t - phi);
+	(t >= -ZERO && v >= -ZERO).then(|| vec![t, -0.5 * PI, u, -0.5 * PI, v])
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{thread_rng, Rng};
+
+	/// Every curve has to end exactly on the end pose and a Reeds-Shepp curve is never longer than a Dubins curve
+	#[test]
+	fn curves_reach_the_end_pose() {
+		let mut rng = thread_rng();
+		for _ in 0..10000 {
+			let from = Pose::new(rng.gen_range(-50.0..50.0), rng.gen_range(-50.0..50.0), rng.gen_range(-PI..PI));
+			let to = Pose::new(rng.gen_range(-50.0..50.0), rng.gen_range(-50.0..50.0), rng.gen_range(-PI..PI));
+			let radius = rng.gen_range(1.0..30.0);
+
+			let dubins = dubins(&from, &to, radius);
+			let reeds_shepp = reeds_shepp(&from, &to, radius);
+			for curve in [&dubins, &reeds_shepp] {
+				let end = curve.pose_at(curve.length());
+				assert!(end.distance(&to) < 1e-6, "{:?} ends on {:?} instead of {:?}", curve, end, to);
+				assert!(normalize_angle(end.heading - to.heading).abs() < 1e-6, "{:?} ends on {:?} instead of {:?}", curve, end, to);
+			}
+			assert!(reeds_shepp.length() <= dubins.length() + 1e-6);
+		}
+	}
+}
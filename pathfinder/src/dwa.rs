@@ -0,0 +1,362 @@
+//! Dynamic Window Approach (DWA) as local planner for a differential-drive robot
+//!
+//! The global paths only know the obstacles of the map. While driving, the robot follows the global path with a
+//! local planner which avoids the obstacles of a local grid, like the ones seen by its sensors. In each control cycle:
+//!
+//! 1. **Dynamic window:** Only the velocities `(v, ω)` reachable from the current ones within one cycle with the
+//!    maximum acceleration and admissible for both wheels are sampled.
+//! 2. **Clearance:** Each velocity drives on an arc. It is admissible only if the robot is able to stop after this cycle
+//!    on the free part of the arc, where it keeps its radius to all obstacles.
+//! 3. **Score:** The admissible velocities are simulated for a short time and scored by the heading to the next waypoint
+//!    of the global path, the free distance along the arc and the speed. The best one is driven for one cycle.
+
+use crate::Instant;
+use crate::kinodynamic::Control;
+use crate::robot::{normalize_angle, DiffDrive, Pose};
+
+use std::f64::consts::PI;
+
+const ARC_STEP: f64 = 1.0; // Distance between the checked poses on an arc
+const BISECTIONS: usize = 8; // Refinements of the free distance between the last free and the first blocked pose
+
+/// Configuration of the robot, the sampling and the score of the DWA
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DwaConfig {
+	/// The robot to plan for
+	pub robot: DiffDrive,
+	/// Maximum forward velocity, below the one of the wheels to leave room for turning
+	pub max_velocity: f64,
+	/// Time of a control cycle in seconds
+	pub time_step: f64,
+	/// Time to simulate each velocity in seconds
+	pub predict_time: f64,
+	/// Number of forward velocities in the dynamic window
+	pub velocity_samples: usize,
+	/// Number of angular velocities in the dynamic window
+	pub angular_samples: usize,
+	/// Radius of the robot, the minimum distance to all obstacles
+	pub radius: f64,
+	/// Free distance along the arc from which on the clearance gets the full score
+	pub max_distance: f64,
+	/// Weight of the heading to the next waypoint
+	pub heading_weight: f64,
+	/// Weight of the free distance along the arc
+	pub clearance_weight: f64,
+	/// Weight of the forward velocity
+	pub velocity_weight: f64,
+	/// Distance along the global path to the next waypoint
+	pub lookahead: f64,
+	/// Radius around the end to catch the end position
+	pub goal_radius: f64,
+	/// Maximum time to reach the end in seconds
+	pub max_time: f64,
+}
+
+impl Default for DwaConfig {
+	fn default() -> Self {
+		DwaConfig {
+			robot: DiffDrive::default(),
+			max_velocity: 20.0,
+			time_step: 0.1,
+			predict_time: 1.0,
+			velocity_samples: 7,
+			angular_samples: 15,
+			radius: 10.0,
+			max_distance: 50.0,
+			heading_weight: 0.5,
+			clearance_weight: 1.0,
+			velocity_weight: 0.5,
+			lookahead: 30.0,
+			goal_radius: 5.0,
+			max_time: 120.0,
+		}
+	}
+}
+
+/// A sampled velocity of the dynamic window with its simulated trajectory
+#[derive(Debug, Clone)]
+pub struct Candidate {
+	/// The velocities to drive for one control cycle
+	pub control: Control,
+	/// All simulated poses of the prediction
+	pub trajectory: Vec<Pose>,
+	/// Weighted sum of the heading, clearance and velocity scores
+	pub score: f64,
+}
+
+/// The result of following a global path with the DWA
+#[derive(Debug)]
+pub struct DwaResult {
+	/// The driven path from the end to the start as fields
+	pub path: Vec<(usize, usize)>,
+	/// The pose of the robot after each control cycle from the start on
+	pub poses: Vec<Pose>,
+	/// The control of each cycle
+	pub controls: Vec<Control>,
+	/// If the end was reached
+	pub reached: bool,
+}
+
+/// Chooses the velocities for the next control cycle
+///
+/// # Arguments
+///
+/// * `area` - The local obstacle grid as a one-dimensional vector
+/// * `rows` - number of rows
+/// * `cols` - number of cols
+/// * `pose` - The current pose of the robot
+/// * `velocity` - The current forward and angular velocity of the robot
+/// * `goal` - The point (x, y) to drive to, usually the next waypoint of the global path
+/// * `config` - The robot, the sampling and the weights of the score
+///
+/// # Result
+///
+/// The admissible velocity with the best score or `None` if the robot is not able to avoid a collision
+#[allow(clippy::too_many_arguments)]
+pub fn plan(area: &[u64], rows: &usize, cols: &usize, pose: &Pose, velocity: (f64, f64), goal: (f64, f64), config: &DwaConfig) -> Option<Candidate> {
+	let robot = &config.robot;
+	let acceleration = robot.max_acceleration;
+	let angular_acceleration = robot.max_acceleration * 2.0 / robot.wheel_distance;
+
+	// The dynamic window: Reachable within one cycle, the robot drives only forward
+	let min_velocity = f64::max(0.0, velocity.0 - (acceleration * config.time_step));
+	let max_velocity = f64::max(min_velocity, f64::min(config.max_velocity, velocity.0 + (acceleration * config.time_step)));
+	let min_angular = velocity.1 - (angular_acceleration * config.time_step);
+	let max_angular = velocity.1 + (angular_acceleration * config.time_step);
+
+	// The samples of the window and braking on the current arc, which stays admissible after an admissible cycle
+	let braking = (min_velocity, if velocity.0 > 0.0 { velocity.1 * min_velocity / velocity.0 } else { velocity.1 });
+	let samples = (0..usize::max(1, config.velocity_samples))
+		.flat_map(|v| (0..usize::max(1, config.angular_samples)).map(move |a| (v, a)))
+		.map(|(v, a)| (interpolate(min_velocity, max_velocity, v, config.velocity_samples), interpolate(min_angular, max_angular, a, config.angular_samples)))
+		.chain(std::iter::once(braking));
+
+	let mut best: Option<Candidate> = None;
+	for (forward, angular) in samples {
+		if !robot.is_admissible(forward, angular) {
+			continue;
+		}
+		// Admissible only if the robot is able to stop on the free part of the arc after this cycle
+		let distance = get_free_distance(area, rows, cols, pose, forward, angular, config);
+		if (forward * config.time_step) + (forward * forward / (2.0 * acceleration)) > distance {
+			continue;
+		}
+		let trajectory = simulate(pose, forward, angular, config);
+
+		let last = trajectory.last().unwrap_or(pose);
+		let direction = f64::atan2(goal.1 - last.y, goal.0 - last.x);
+		let heading = 1.0 - (normalize_angle(direction - last.heading).abs() / PI);
+		let score = (config.heading_weight * heading)
+			+ (config.clearance_weight * distance / config.max_distance)
+			+ (config.velocity_weight * forward / config.max_velocity);
+
+		if best.as_ref().is_none_or(|best| score > best.score) {
+			best = Some(Candidate {
+				control: Control { velocity: forward, angular_velocity: angular, time: config.time_step },
+				trajectory,
+				score,
+			});
+		}
+	}
+	best
+}
+
+/// Follows a global path with the DWA around the obstacles of the local grid
+///
+/// # Arguments
+///
+/// * `area` - The local obstacle grid as a one-dimensional vector
+/// * `rows` - number of rows
+/// * `cols` - number of cols
+/// * `start` - start pose of the robot, standing still
+/// * `path` - The global path from the start to the end
+/// * `config` - The robot, the sampling and the weights of the score
+///
+/// # Result
+///
+/// The driven path, poses and controls
+pub fn follow(area: &[u64], rows: &usize, cols: &usize, start: Pose, path: &[(usize, usize)], config: &DwaConfig) -> DwaResult {
+	let benchmark = Instant::now();
+	let waypoints: Vec<(f64, f64)> = path.iter().map(|(row, col)| (*row as f64 + 0.5, *col as f64 + 0.5)).collect();
+	let end = waypoints.last().copied().unwrap_or((start.x, start.y));
+
+	let mut pose = start;
+	let mut velocity = (0.0, 0.0);
+	let mut poses = vec![start];
+	let mut controls = vec![];
+	let mut next = 0;
+	let mut reached = false;
+
+	for _ in 0..(config.max_time / config.time_step).ceil() as usize {
+		if f64::hypot(end.0 - pose.x, end.1 - pose.y) <= config.goal_radius {
+			reached = true;
+			break;
+		}
+		// The next waypoint is the first one beyond the lookahead distance which the robot is able to reach
+		let is_reachable = |point: (f64, f64)| get_clearance(area, rows, cols, point, config.radius) >= config.radius;
+		while next + 1 < waypoints.len() && (f64::hypot(waypoints[next].0 - pose.x, waypoints[next].1 - pose.y) < config.lookahead || !is_reachable(waypoints[next])) {
+			next += 1;
+		}
+
+		// Slow enough to stop on the end and to turn towards it with a turning radius of at most half the distance
+		let (robot, distance) = (&config.robot, f64::hypot(end.0 - pose.x, end.1 - pose.y));
+		let limit = f64::min(f64::sqrt(2.0 * robot.max_acceleration * distance), distance * robot.max_velocity / (robot.wheel_distance + distance));
+		let window = DwaConfig { max_velocity: f64::min(config.max_velocity, limit), ..*config };
+		let control = match plan(area, rows, cols, &pose, velocity, waypoints[next], &window) {
+			Some(candidate) => candidate.control,
+			// Nothing is admissible anymore, brake as hard as possible on the current arc
+			None if velocity.0 > 0.0 => {
+				let forward = f64::max(0.0, velocity.0 - (config.robot.max_acceleration * config.time_step));
+				Control { velocity: forward, angular_velocity: velocity.1 * forward / velocity.0, time: config.time_step }
+			},
+			None => break,
+		};
+		pose = pose.simulate(control.velocity, control.angular_velocity, control.time);
+		velocity = (control.velocity, control.angular_velocity);
+		poses.push(pose);
+		controls.push(control);
+	}
+
+	if reached {
+		println!("DWA Calc: {:.6?}", benchmark.elapsed());
+	} else {
+		println!("DWA Calc: No conneciton found");
+	}
+
+	let mut driven: Vec<(usize, usize)> = vec![];
+	poses.iter().rev()
+		.filter_map(|pose| pose.field(rows, cols))
+		.for_each(|field| if driven.last() != Some(&field) { driven.push(field) });
+	println!("DWA Path length: {}", driven.len());
+	println!("DWA Time: {:.2}s", controls.len() as f64 * config.time_step);
+
+	DwaResult {
+		path: driven,
+		poses,
+		controls,
+		reached,
+	}
+}
+
+/// Returns the distance from the point to the nearest obstacle or the border, at most the maximum distance
+///
+/// # Arguments
+///
+/// * `area` - The local obstacle grid as a one-dimensional vector
+/// * `rows` - number of rows
+/// * `cols` - number of cols
+/// * `point` - The point (x, y)
+/// * `max_distance` - Only obstacles within this distance are searched
+pub fn get_clearance(area: &[u64], rows: &usize, cols: &usize, point: (f64, f64), max_distance: f64) -> f64 {
+	let border = [point.0, point.1, *rows as f64 - point.0, *cols as f64 - point.1].into_iter().fold(max_distance, f64::min);
+	if border <= 0.0 {
+		return 0.0;
+	}
+
+	let first_row = f64::max(0.0, (point.0 - max_distance).floor()) as usize;
+	let last_row = usize::min(*rows - 1, (point.0 + max_distance).floor() as usize);
+	let first_col = f64::max(0.0, (point.1 - max_distance).floor()) as usize;
+	let last_col = usize::min(*cols - 1, (point.1 + max_distance).floor() as usize);
+
+	let mut clearance = border;
+	for col in first_col..=last_col {
+		for row in first_row..=last_row {
+			if area[(col * rows) + row] == u64::MAX {
+				// Distance to the nearest point of the field
+				let dx = f64::max(0.0, f64::max(row as f64 - point.0, point.0 - (row + 1) as f64));
+				let dy = f64::max(0.0, f64::max(col as f64 - point.1, point.1 - (col + 1) as f64));
+				clearance = f64::min(clearance, f64::hypot(dx, dy));
+			}
+		}
+	}
+	clearance
+}
+
+/// Returns the free distance along the arc of the velocities from the pose, at most the maximum distance
+///
+/// The arc depends only on the curvature `ω / v`, so a slower robot on the same arc has the same free distance.
+/// Turning on the spot has no arc, its distance is the one straight ahead after turning for the prediction time.
+fn get_free_distance(area: &[u64], rows: &usize, cols: &usize, pose: &Pose, velocity: f64, angular_velocity: f64, config: &DwaConfig) -> f64 {
+	let is_free = |pose: &Pose| get_clearance(area, rows, cols, (pose.x, pose.y), config.radius) >= config.radius;
+	if !is_free(pose) {
+		return 0.0;
+	}
+
+	let (from, curvature) = if velocity.abs() < 1e-9 {
+		(pose.simulate(0.0, angular_velocity, config.predict_time), 0.0)
+	} else {
+		(*pose, angular_velocity / velocity)
+	};
+	let steps = (config.max_distance / ARC_STEP).ceil() as usize;
+	for step in 1..=steps {
+		let distance = f64::min(config.max_distance, ARC_STEP * step as f64);
+		if !is_free(&from.simulate(1.0, curvature, distance)) {
+			// The obstacle is reached between the last free and this pose
+			let (mut free, mut blocked) = (ARC_STEP * (step - 1) as f64, distance);
+			for _ in 0..BISECTIONS {
+				let middle = (free + blocked) / 2.0;
+				if is_free(&from.simulate(1.0, curvature, middle)) { free = middle } else { blocked = middle }
+			}
+			return free;
+		}
+	}
+	config.max_distance
+}
+
+/// Returns the area with all fields as obstacles where the robot with the radius would touch an obstacle or the border
+///
+/// A global path planned on the inflated area for a point keeps the radius to all obstacles of the map.
+pub fn inflate(area: &[u64], rows: &usize, cols: &usize, radius: f64) -> Vec<u64> {
+	let mut inflated = area.to_vec();
+	for col in 0..*cols {
+		for row in 0..*rows {
+			if get_clearance(area, rows, cols, (row as f64 + 0.5, col as f64 + 0.5), radius) < radius {
+				inflated[(col * rows) + row] = u64::MAX;
+			}
+		}
+	}
+	inflated
+}
+
+/// Simulates the velocities from the pose in steps of the control cycle for the prediction time
+fn simulate(pose: &Pose, velocity: f64, angular_velocity: f64, config: &DwaConfig) -> Vec<Pose> {
+	let steps = usize::max(1, (config.predict_time / config.time_step).ceil() as usize);
+	(1..=steps).map(|step| pose.simulate(velocity, angular_velocity, config.time_step * step as f64)).collect()
+}
+
+/// Returns the value of the sample between the minimum and the maximum, the middle for a single sample
+fn interpolate(min: f64, max: f64, sample: usize, samples: usize) -> f64 {
+	if samples <= 1 {
+		(min + max) / 2.0
+	} else {
+		min + ((max - min) * sample as f64 / (samples - 1) as f64)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// An obstacle which is not on the map blocks the straight global path
+	#[test]
+	fn avoids_an_unmapped_obstacle() {
+		let (rows, cols) = (100, 200);
+		let mut area = vec![0u64; rows * cols];
+		for col in 90..110 {
+			for row in 40..60 {
+				area[(col * rows) + row] = u64::MAX;
+			}
+		}
+		let path: Vec<(usize, usize)> = (10..170).map(|col| (50, col)).collect();
+
+		let result = follow(&area, &rows, &cols, Pose::from_field((50, 10), PI / 2.0), &path, &DwaConfig::default());
+		assert!(result.reached);
+		let config = DwaConfig::default();
+		for pose in &result.poses {
+			assert!(get_clearance(&area, &rows, &cols, (pose.x, pose.y), config.radius) >= config.radius);
+		}
+		for control in &result.controls {
+			assert!(config.robot.is_admissible(control.velocity, control.angular_velocity));
+		}
+	}
+}
@@ -1,5 +1,9 @@
+use rayon::prelude::*;
+
 use crate::Instant;
 
+const PARALLEL_MIN_FIELDS: usize = 256; // Minimum number of frontier fields for each thread
+
 /// Use the Grassfire algorithm to calculate the optimal way fro the start to the end.
 ///
 /// Version 1: go through the whole matrix multiple times (rows x cols times)
@@ -265,6 +269,107 @@ pub fn v4(area: &mut [u64], rows: &usize, cols: &usize, start:(usize, usize), en
 	find_path("Grassfire-V4", start, area, rows, cols, true)
 }
 
+/// Use the Grassfire algorithm to calculate the optimal way fro the start to the end.
+///
+/// Version 2 parallel: Like version 2, but the neighbours of the last processed fields are searched on all threads
+///                     The calculated area and the path are identical to the ones of version 2
+///
+/// # Arguments
+///
+/// * `area` - The play field as a one-dimensional vector
+/// * `rows` - number of rows
+/// * `cols` - number of cols
+/// * `start` - start position (row, col)
+/// * `end` - end position (row, col)
+///
+/// # Result
+///
+/// A Vector of tuples where each tuple represents a waypoint
+pub fn v2_parallel(area: &mut [u64], rows: &usize, cols: &usize, start:(usize, usize), end:(usize, usize)) -> Vec<(usize, usize)> {
+	let benchmark = Instant::now();
+	expand_parallel("Grassfire-V2-Parallel", area, rows, cols, start, end, false);
+	println!("Grassfire-V2-Parallel Calc: {:.6?}", benchmark.elapsed());
+
+	find_path("Grassfire-V2-Parallel", start, area, rows, cols, false)
+}
+
+/// Use the Grassfire algorithm to calculate the optimal way fro the start to the end.
+///
+/// Version 3 parallel: Like version 3, but the neighbours of the last processed fields are searched on all threads
+///                     The calculated area and the path are identical to the ones of version 3
+///
+/// # Arguments
+///
+/// * `area` - The play field as a one-dimensional vector
+/// * `rows` - number of rows
+/// * `cols` - number of cols
+/// * `start` - start position (row, col)
+/// * `end` - end position (row, col)
+///
+/// # Result
+///
+/// A Vector of tuples where each tuple represents a waypoint
+pub fn v3_parallel(area: &mut [u64], rows: &usize, cols: &usize, start:(usize, usize), end:(usize, usize)) -> Vec<(usize, usize)> {
+	let benchmark = Instant::now();
+	expand_parallel("Grassfire-V3-Parallel", area, rows, cols, start, end, true);
+	println!("Grassfire-V3-Parallel Calc: {:.6?}", benchmark.elapsed());
+
+	find_path("Grassfire-V3-Parallel", start, area, rows, cols, false)
+}
+
+/// Expands the grassfire from the end, one frontier after the other, until the start is reached
+///
+/// All fields of a frontier have the same value, so the order in which the threads find the neighbours does not matter:
+/// The free neighbours are searched in parallel on the unchanged area, afterwards each one gets the next value once.
+///
+/// # Arguments:
+///
+/// * `algorithm` - Name of the algorithm for logging
+/// * `area` - The play field as a one-dimensional vector
+/// * `rows` - Number of rows
+/// * `cols` - Number of columns
+/// * `start` - start position (row, col)
+/// * `end` - end position (row, col)
+/// * `eight` - Use the 8-Neighborhood instead of the 4-Neighborhood
+fn expand_parallel(algorithm: &str, area: &mut [u64], rows: &usize, cols: &usize, start:(usize, usize), end:(usize, usize), eight: bool) {
+	// Fill the start position with 1
+	area[(end.1 * rows) + end.0] = 1;
+	let mut last_fields: Vec<(usize, usize)> = vec![end];
+
+	loop {
+		let value = area[(last_fields[0].1 * rows) + last_fields[0].0] + 1;
+		let current: &[u64] = area;
+		let mut next_fields: Vec<(usize, usize)> = last_fields.par_iter()
+			.with_min_len(PARALLEL_MIN_FIELDS)
+			.flat_map_iter(|field| get_neighbours(*field, rows, cols, eight))
+			.filter(|fld| current[(fld.1 * rows) + fld.0] == 0)
+			.collect();
+
+		// A field can be the neighbour of multiple fields of the frontier
+		next_fields.retain(|fld| {
+			let free = area[(fld.1 * rows) + fld.0] == 0;
+			if free {
+				area[(fld.1 * rows) + fld.0] = value;
+			}
+			free
+		});
+		last_fields = next_fields;
+
+		if last_fields.is_empty() { println!("{} Calc: No conneciton possible", algorithm); break; }
+		if area[(start.1 * rows) + start.0] != 0 { break; }
+	}
+}
+
+/// Returns the neighbours of the field inside of the area in the 4-Neighborhood or the 8-Neighborhood
+fn get_neighbours(field: (usize, usize), rows: &usize, cols: &usize, eight: bool) -> impl Iterator<Item = (usize, usize)> {
+	let (rows, cols) = (*rows as i64, *cols as i64);
+	[(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (-1, 1), (1, -1), (-1, -1)].into_iter()
+		.take(if eight { 8 } else { 4 })
+		.map(move |(dx, dy): (i64, i64)| (field.0 as i64 + dx, field.1 as i64 + dy))
+		.filter(move |(x, y)| (0..rows).contains(x) && (0..cols).contains(y))
+		.map(|(x, y)| (x as usize, y as usize))
+}
+
 /// This is a generic function to finally findthe path from the start to the end
 ///
 /// This funciton works with 4-Neighborhood and 8-Neighborhood because it checks always for a value -1 of the current
@@ -355,3 +460,27 @@ pub fn find_path(algorithm: &str, start: (usize, usize), area: &mut [u64], rows:
 	result
 }
 
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{thread_rng, Rng};
+
+	/// The parallel versions calculate the same area and path as the sequential ones
+	#[test]
+	fn parallel_versions_are_identical() {
+		let (rows, cols) = (80, 60);
+		let mut rng = thread_rng();
+		let mut area: Vec<u64> = (0..rows * cols).map(|_| if rng.gen_bool(0.3) { u64::MAX } else { 0 }).collect();
+		let (start, end) = ((0, 0), (rows - 1, cols - 1));
+		area[0] = 0;
+		area[(end.1 * rows) + end.0] = 0;
+
+		let (mut sequential, mut parallel) = (area.clone(), area.clone());
+		assert_eq!(v2(&mut sequential, &rows, &cols, start, end), v2_parallel(&mut parallel, &rows, &cols, start, end));
+		assert_eq!(sequential, parallel);
+
+		let (mut sequential, mut parallel) = (area.clone(), area.clone());
+		assert_eq!(v3(&mut sequential, &rows, &cols, start, end), v3_parallel(&mut parallel, &rows, &cols, start, end));
+		assert_eq!(sequential, parallel);
+	}
+}
@@ -0,0 +1,362 @@
+//! Hybrid A* for vehicles with a minimum turning radius
+//!
+//! The search runs over the state `(x, y, θ)`: Each node keeps its continuous pose,
+//! but only the best node per field and heading bin is expanded.
+//! A node is expanded by driving arcs with a fixed length and curvatures up to the minimum turning radius,
+//! so every path of the search can be driven by the robot.
+//!
+//! The heuristic is the maximum of the shortest path on the grid around all obstacles
+//! and the Dubins curve (Reeds-Shepp if driving backwards is allowed) to the goal without obstacles.
+//! From time to time, and on every node near the end, this curve to the goal pose is checked for collisions,
+//! if it is free the search is done (analytic expansion).
+//! The search is also done if a node lies in the end field with the heading bin of the end pose.
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::f64::consts::{PI, SQRT_2};
+
+use crate::Instant;
+use crate::curves::{is_trajectory_free, Steering};
+use crate::robot::Pose;
+use crate::tree::Edge;
+
+/// Configuration of the Hybrid A* algorithm
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HybridAStarConfig {
+	/// Minimum turning radius of the vehicle in fields
+	pub turning_radius: f64,
+	/// Length of each motion primitive in fields, should be longer than the diagonal of a field
+	pub step_distance: f64,
+	/// Number of discrete headings per field
+	pub heading_bins: usize,
+	/// Number of curvatures between the maximum left and right turn
+	pub steering_samples: usize,
+	/// If the vehicle may drive backwards
+	pub reverse: bool,
+	/// Factor for the costs of driving backwards
+	pub reverse_penalty: f64,
+	/// Additional costs to change between forward and backward
+	pub switch_penalty: f64,
+	/// Additional costs per driven field in a curve with the minimum turning radius
+	pub steering_penalty: f64,
+	/// Number of expanded nodes between two analytic expansions
+	pub analytic_interval: usize,
+	/// Maximum number of expanded nodes, `None` for the number of fields in the area
+	pub max_expansions: Option<usize>,
+}
+
+impl Default for HybridAStarConfig {
+	fn default() -> Self {
+		HybridAStarConfig {
+			turning_radius: 20.0,
+			step_distance: 3.0,
+			heading_bins: 72,
+			steering_samples: 5,
+			reverse: false,
+			reverse_penalty: 2.0,
+			switch_penalty: 10.0,
+			steering_penalty: 0.1,
+			analytic_interval: 10,
+			max_expansions: None,
+		}
+	}
+}
+
+/// The result of the Hybrid A* algorithm
+#[derive(Debug)]
+pub struct HybridAStarResult {
+	/// The driven path from the end to the start as fields
+	pub path: Vec<(usize, usize)>,
+	/// All expanded motion primitives as lines/tuples: ((x0, y0), (x1, y1))
+	pub tree: Vec<Edge>,
+	/// The poses along the path from the start to the end, at most one field apart
+	pub poses: Vec<Pose>,
+}
+
+/// Inner type representing a node
+#[derive(Debug, Clone)]
+struct Node {
+	/// Continuous state of the vehicle
+	pose: Pose,
+	/// Parent node index
+	parent: usize,
+	/// Costs from the start
+	cost: f64,
+	/// If the node was reached by driving backwards
+	backwards: bool,
+	/// All poses from the parent to this node
+	trajectory: Vec<Pose>,
+}
+
+/// Entry of the open list, ordered by the lowest estimation first
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Open {
+	estimate: f64,
+	node: usize,
+}
+
+impl Eq for Open {}
+
+impl Ord for Open {
+	fn cmp(&self, other: &Self) -> Ordering {
+		other.estimate.total_cmp(&self.estimate)
+	}
+}
+
+impl PartialOrd for Open {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+/// Use the Hybrid A* algorithm to find a drivable path from the start to the end pose
+///
+/// # Arguments
+///
+/// * `area` - The play field as a one-dimensional vector
+/// * `rows` - number of rows
+/// * `cols` - number of cols
+/// * `start` - start pose of the vehicle
+/// * `end` - end pose, the vehicle has to reach the position with the given heading
+/// * `config` - Turning radius, motion primitives and costs
+///
+/// # Result
+///
+/// The path, all expanded motion primitives and the poses along the path
+pub fn calculate(area: &mut [u64], rows: &usize, cols: &usize, start: Pose, end: Pose, config: &HybridAStarConfig) -> HybridAStarResult {
+	let benchmark = Instant::now();
+	let radius = config.turning_radius;
+	let bins = usize::max(1, config.heading_bins);
+	let primitives = get_primitives(config);
+	let steering = if config.reverse { Steering::ReedsShepp(radius) } else { Steering::Dubins(radius) };
+	let max_expansions = config.max_expansions.unwrap_or(area.len());
+
+	// Shortest distances on the grid from every field to the end
+	let holonomic = get_holonomic_distances(area, rows, cols, end.field(rows, cols));
+	let estimate = |pose: &Pose| -> Option<f64> {
+		let field = pose.field(rows, cols)?;
+		let grid = holonomic[(field.1 * rows) + field.0];
+		if grid == f64::MAX {
+			return None;
+		}
+		Some(f64::max(grid, steering.curve(pose, &end).length()))
+	};
+	let state = |pose: &Pose| -> Option<usize> {
+		let field = pose.field(rows, cols)?;
+		let bin = ((pose.heading.rem_euclid(2.0 * PI) / (2.0 * PI)) * bins as f64) as usize % bins;
+		Some((((field.1 * rows) + field.0) * bins) + bin)
+	};
+
+	let mut nodes: Vec<Node> = vec![Node { pose: start, parent: 0, cost: 0.0, backwards: false, trajectory: vec![start] }];
+	let mut open: BinaryHeap<Open> = BinaryHeap::new();
+	let mut best: HashMap<usize, usize> = HashMap::new();
+	let mut closed: HashSet<usize> = HashSet::new();
+	let mut finish: Option<(usize, Vec<Pose>)> = None;
+	let mut expansions = 0;
+	let end_key = state(&end);
+
+	if let (Some(key), Some(estimation)) = (state(&start), estimate(&start)) {
+		best.insert(key, 0);
+		open.push(Open { estimate: estimation, node: 0 });
+	}
+
+	while let Some(Open { node: current, .. }) = open.pop() {
+		let pose = nodes[current].pose;
+		let Some(key) = state(&pose) else { continue };
+		if best.get(&key) != Some(&current) || !closed.insert(key) {
+			continue;
+		}
+		if expansions >= max_expansions {
+			break;
+		}
+		expansions += 1;
+
+		// The end field is reached with the end heading
+		if Some(key) == end_key {
+			finish = Some((current, vec![pose]));
+			break;
+		}
+
+		// Analytic expansion: Try to reach the end pose directly
+		if expansions % usize::max(1, config.analytic_interval) == 0 || pose.distance(&end) <= 2.0 * radius {
+			let poses = steering.curve(&pose, &end).sample(1.0);
+			if is_trajectory_free(area, rows, cols, &poses) {
+				finish = Some((current, poses));
+				break;
+			}
+		}
+
+		// Drive all motion primitives from the current pose
+		for (curvature, backwards) in &primitives {
+			let Some(trajectory) = drive(area, rows, cols, &pose, *curvature, *backwards, config.step_distance) else { continue };
+			let next = *trajectory.last().unwrap_or(&pose);
+			let Some(next_key) = state(&next) else { continue };
+			if closed.contains(&next_key) {
+				continue;
+			}
+
+			let mut cost = config.step_distance * (1.0 + (config.steering_penalty * (curvature * radius).abs()));
+			if *backwards { cost *= config.reverse_penalty; }
+			if current != 0 && *backwards != nodes[current].backwards { cost += config.switch_penalty; }
+			let cost = nodes[current].cost + cost;
+
+			if best.get(&next_key).is_some_and(|other| nodes[*other].cost <= cost) {
+				continue;
+			}
+			let Some(estimation) = estimate(&next) else { continue };
+
+			let key = nodes.len();
+			nodes.push(Node { pose: next, parent: current, cost, backwards: *backwards, trajectory });
+			best.insert(next_key, key);
+			open.push(Open { estimate: cost + estimation, node: key });
+		}
+	}
+
+	if finish.is_none() { println!("Hybrid-A* Calc: No conneciton found"); }
+	println!("Hybrid-A* Calc: {:.6?}", benchmark.elapsed());
+
+	// Go backwards from the node with the analytic expansion to the start
+	let path_benchmark = Instant::now();
+	let mut poses: Vec<Pose> = vec![];
+	if let Some((last, curve)) = finish {
+		poses = curve.into_iter().rev().collect();
+		let mut current = last;
+		while current != 0 {
+			poses.extend(nodes[current].trajectory.iter().rev().skip(1));
+			current = nodes[current].parent;
+		}
+		poses.push(start);
+		poses.reverse();
+	}
+
+	let mut path: Vec<(usize, usize)> = vec![];
+	poses.iter().rev()
+		.filter_map(|pose| pose.field(rows, cols))
+		.for_each(|field| if path.last() != Some(&field) { path.push(field) });
+
+	println!("Hybrid-A* Path-Calculation: {:.6?}", path_benchmark.elapsed());
+	println!("Hybrid-A* Path length: {}", path.len());
+	println!("Hybrid-A* Expanded Nodes: {}", expansions);
+
+	// For the return value we need only the position as a tuple ((x0, y0), (x1, y1)) to draw the tree/network
+	let tree = nodes.iter()
+		.skip(1)
+		.filter_map(|node| Some((nodes[node.parent].pose.field(rows, cols)?, node.pose.field(rows, cols)?)))
+		.collect();
+
+	HybridAStarResult {
+		path,
+		tree,
+		poses,
+	}
+}
+
+/// Creates all motion primitives as curvature and direction
+fn get_primitives(config: &HybridAStarConfig) -> Vec<(f64, bool)> {
+	let samples = usize::max(1, config.steering_samples);
+	let max_curvature = 1.0 / config.turning_radius;
+	let curvatures: Vec<f64> = (0..samples)
+		.map(|i| if samples == 1 { 0.0 } else { -max_curvature + (2.0 * max_curvature * i as f64 / (samples - 1) as f64) })
+		.collect();
+
+	let mut primitives: Vec<(f64, bool)> = curvatures.iter().map(|curvature| (*curvature, false)).collect();
+	if config.reverse {
+		primitives.extend(curvatures.iter().map(|curvature| (*curvature, true)));
+	}
+	primitives
+}
+
+/// Drives one motion primitive in steps of at most one field and checks every step for collisions
+///
+/// # Result:
+///
+/// All poses after each step or `None` if the vehicle collides
+fn drive(area: &[u64], rows: &usize, cols: &usize, from: &Pose, curvature: f64, backwards: bool, distance: f64) -> Option<Vec<Pose>> {
+	let velocity = if backwards { -1.0 } else { 1.0 };
+	let steps = f64::max(1.0, distance.ceil()) as usize;
+
+	let mut trajectory = Vec::with_capacity(steps + 1);
+	trajectory.push(*from);
+	for step in 1..=steps {
+		let time = distance * step as f64 / steps as f64;
+		trajectory.push(from.simulate(velocity, velocity * curvature, time));
+	}
+
+	if is_trajectory_free(area, rows, cols, &trajectory) { Some(trajectory) } else { None }
+}
+
+/// Calculates the shortest distance on the grid (8-Neighborhood) from every field to the end
+///
+/// # Result:
+///
+/// The distances as a one-dimensional vector like the area, `f64::MAX` for unreachable fields
+fn get_holonomic_distances(area: &[u64], rows: &usize, cols: &usize, end: Option<(usize, usize)>) -> Vec<f64> {
+	let mut distances = vec![f64::MAX; area.len()];
+	let Some(end) = end else { return distances };
+
+	let mut queue: BinaryHeap<Open> = BinaryHeap::new();
+	let index = (end.1 * rows) + end.0;
+	distances[index] = 0.0;
+	queue.push(Open { estimate: 0.0, node: index });
+
+	while let Some(Open { estimate: distance, node: index }) = queue.pop() {
+		if distance > distances[index] {
+			continue;
+		}
+		let (row, col) = (index % rows, index / rows);
+		for (dr, dc) in [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (-1, 1), (1, -1), (1, 1)] {
+			let (r, c) = (row as isize + dr, col as isize + dc);
+			if r < 0 || c < 0 || r as usize >= *rows || c as usize >= *cols {
+				continue;
+			}
+			let next = (c as usize * rows) + r as usize;
+			if area[next] == u64::MAX {
+				continue;
+			}
+			let next_distance = distance + if dr != 0 && dc != 0 { SQRT_2 } else { 1.0 };
+			if next_distance < distances[next] {
+				distances[next] = next_distance;
+				queue.push(Open { estimate: next_distance, node: next });
+			}
+		}
+	}
+	distances
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::robot::normalize_angle;
+
+	/// The poses avoid the wall, turn at most with the minimum radius and end in the end field with the end heading
+	#[test]
+	fn path_is_drivable() {
+		let (rows, cols) = (60, 60);
+		// A wall across the middle with a gap at the bottom
+		let mut area = vec![0; rows * cols];
+		for col in 28..32 {
+			for row in 0..40 {
+				area[(col * rows) + row] = u64::MAX;
+			}
+		}
+		let config = HybridAStarConfig { turning_radius: 5.0, heading_bins: 36, ..HybridAStarConfig::default() };
+		let (start, end) = (Pose::from_field((10, 10), 0.0), Pose::from_field((10, 50), PI));
+		let result = calculate(&mut area.clone(), &rows, &cols, start, end, &config);
+		assert!(!result.poses.is_empty());
+
+		for pose in &result.poses {
+			let field = pose.field(&rows, &cols).expect("pose outside of the area");
+			assert_ne!(area[(field.1 * rows) + field.0], u64::MAX, "{:?} collides", pose);
+		}
+		for pair in result.poses.windows(2) {
+			let turn = normalize_angle(pair[1].heading - pair[0].heading).abs();
+			assert!(turn * config.turning_radius <= (pair[0].distance(&pair[1]) * 1.01) + 1e-6, "{:?} turns too sharp", pair);
+		}
+
+		let last = result.poses[result.poses.len() - 1];
+		assert_eq!(last.field(&rows, &cols), end.field(&rows, &cols));
+		let bin = |pose: &Pose| ((pose.heading.rem_euclid(2.0 * PI) / (2.0 * PI)) * config.heading_bins as f64) as usize % config.heading_bins;
+		assert_eq!(bin(&last), bin(&end));
+		assert_eq!(result.path.last(), start.field(&rows, &cols).as_ref());
+	}
+}
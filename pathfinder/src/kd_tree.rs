@@ -0,0 +1,224 @@
+//! A two dimensional KD-Tree as a spatial index for the nodes of the Tree-Pathfinder algorithms
+//!
+//! The points are inserted incrementally, the splitting axis alternates between x and y on each level.
+//! No rebalancing is done, the random order of the inserted points keeps the tree balanced well enough.
+
+/// Inner type representing a point in the KD-Tree
+#[derive(Debug, Clone)]
+struct KdNode {
+	/// Coordinates
+	pos: (f64, f64),
+	/// Index of the element this point belongs to
+	index: usize,
+	/// Index of the child with the smaller value on the splitting axis
+	left: Option<usize>,
+	/// Index of the child with the bigger or same value on the splitting axis
+	right: Option<usize>,
+}
+
+/// KD-Tree which supports nearest neighbour and radius queries
+#[derive(Debug, Clone, Default)]
+pub struct KdTree {
+	nodes: Vec<KdNode>,
+}
+
+impl KdTree {
+	/// Creates an empty KD-Tree
+	pub fn new() -> Self {
+		KdTree { nodes: vec![] }
+	}
+
+	/// Creates an empty KD-Tree with space for `capacity` points
+	pub fn with_capacity(capacity: usize) -> Self {
+		KdTree { nodes: Vec::with_capacity(capacity) }
+	}
+
+	/// Number of points in the tree
+	pub fn len(&self) -> usize {
+		self.nodes.len()
+	}
+
+	/// If there are no points in the tree
+	pub fn is_empty(&self) -> bool {
+		self.nodes.is_empty()
+	}
+
+	/// Inserts a new point into the tree
+	///
+	/// # Arguments:
+	///
+	/// * `pos` - The position of the point
+	/// * `index` - Index of the element the point belongs to, returned by the queries
+	pub fn insert(&mut self, pos: (f64, f64), index: usize) {
+		let new_node = self.nodes.len();
+		self.nodes.push(KdNode { pos, index, left: None, right: None });
+		if new_node == 0 {
+			return;
+		}
+
+		let mut current = 0;
+		let mut depth = 0;
+		loop {
+			let node = &mut self.nodes[current];
+			let child = if axis_value(pos, depth) < axis_value(node.pos, depth) { &mut node.left } else { &mut node.right };
+			match child {
+				Some(next) => current = *next,
+				None => {
+					*child = Some(new_node);
+					return;
+				},
+			}
+			depth += 1;
+		}
+	}
+
+	/// Finds the nearest point to the given position
+	///
+	/// # Arguments:
+	///
+	/// * `pos` - The position to search around
+	///
+	/// # Result:
+	///
+	/// The index of the nearest element and its distance or `None` if the tree is empty
+	pub fn nearest(&self, pos: (f64, f64)) -> Option<(usize, f32)> {
+		self.nearest_where(pos, |_| true)
+	}
+
+	/// Finds the nearest point to the given position where the filter returns true
+	///
+	/// # Arguments:
+	///
+	/// * `pos` - The position to search around
+	/// * `filter` - Gets the index of an element, only elements where it returns true are taken into account
+	///
+	/// # Result:
+	///
+	/// The index of the nearest element and its distance or `None` if no element was found
+	pub fn nearest_where<F: Fn(usize) -> bool>(&self, pos: (f64, f64), filter: F) -> Option<(usize, f32)> {
+		if self.nodes.is_empty() {
+			return None;
+		}
+
+		// (element index, squared distance)
+		let mut best: Option<(usize, f64)> = None;
+		let mut stack = vec![(0, 0)];
+		while let Some((current, depth)) = stack.pop() {
+			let node = &self.nodes[current];
+			let distance = squared_distance(pos, node.pos);
+			if best.is_none_or(|(_, best_distance)| distance < best_distance) && filter(node.index) {
+				best = Some((node.index, distance));
+			}
+
+			// Go first into the half where the position lies, the other one only if it may contain a nearer point
+			let diff = axis_value(pos, depth) - axis_value(node.pos, depth);
+			let (near, far) = if diff < 0.0 { (node.left, node.right) } else { (node.right, node.left) };
+			if let Some(far) = far {
+				if best.is_none_or(|(_, best_distance)| diff * diff < best_distance) {
+					stack.push((far, depth + 1));
+				}
+			}
+			if let Some(near) = near {
+				stack.push((near, depth + 1));
+			}
+		}
+
+		best.map(|(index, distance)| (index, distance.sqrt() as f32))
+	}
+
+	/// Finds all points inside the radius around the given position
+	///
+	/// # Arguments:
+	///
+	/// * `pos` - The position to search around
+	/// * `radius` - The radius around the position
+	///
+	/// # Result:
+	///
+	/// The indexes of all elements in the radius
+	pub fn within(&self, pos: (f64, f64), radius: f32) -> Vec<usize> {
+		let mut result = vec![];
+		if self.nodes.is_empty() {
+			return result;
+		}
+
+		let radius_squared = (radius as f64) * (radius as f64);
+		let mut stack = vec![(0, 0)];
+		while let Some((current, depth)) = stack.pop() {
+			let node = &self.nodes[current];
+			if squared_distance(pos, node.pos) <= radius_squared {
+				result.push(node.index);
+			}
+
+			let diff = axis_value(pos, depth) - axis_value(node.pos, depth);
+			if let Some(left) = node.left {
+				if diff < 0.0 || diff * diff <= radius_squared {
+					stack.push((left, depth + 1));
+				}
+			}
+			if let Some(right) = node.right {
+				if diff >= 0.0 || diff * diff <= radius_squared {
+					stack.push((right, depth + 1));
+				}
+			}
+		}
+		result
+	}
+}
+
+/// Returns the value of the splitting axis for the given depth
+fn axis_value(pos: (f64, f64), depth: usize) -> f64 {
+	if depth.is_multiple_of(2) { pos.0 } else { pos.1 }
+}
+
+/// Returns the squared distance between two points
+fn squared_distance(p1: (f64, f64), p2: (f64, f64)) -> f64 {
+	let dx = p1.0 - p2.0;
+	let dy = p1.1 - p2.1;
+	(dx * dx) + (dy * dy)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{thread_rng, Rng};
+
+	/// The queries have to find the same points as a scan over all points, on random and on sorted (degenerated) trees
+	#[test]
+	fn queries_match_a_brute_force_scan() {
+		let mut rng = thread_rng();
+		let random: Vec<(f64, f64)> = (0..1000).map(|_| (rng.gen_range(0.0..100.0), rng.gen_range(0.0..100.0))).collect();
+		// Sorted points make each level a chain, duplicates lie on the splitting axis
+		let sorted: Vec<(f64, f64)> = (0..1000).map(|i| ((i / 20) as f64 * 2.0, (i % 20) as f64 * 5.0)).chain([(10.0, 10.0); 5]).collect();
+
+		for points in [random, sorted] {
+			let mut tree = KdTree::new();
+			assert_eq!(tree.nearest((0.0, 0.0)), None);
+			for (index, pos) in points.iter().enumerate() {
+				tree.insert(*pos, index);
+			}
+			assert_eq!(tree.len(), points.len());
+
+			for _ in 0..200 {
+				let pos = (rng.gen_range(-10.0..110.0), rng.gen_range(-10.0..110.0));
+				let filter = |index: usize| !index.is_multiple_of(3);
+				let expected = (0..points.len()).filter(|index| filter(*index))
+					.map(|index| squared_distance(pos, points[index]).sqrt() as f32)
+					.fold(f32::MAX, f32::min);
+				let (index, distance) = tree.nearest_where(pos, filter).unwrap();
+				assert!(filter(index));
+				assert_eq!(distance, expected);
+				assert_eq!(distance, squared_distance(pos, points[index]).sqrt() as f32);
+
+				let radius = rng.gen_range(0.0..20.0);
+				let mut found = tree.within(pos, radius);
+				found.sort_unstable();
+				let expected: Vec<usize> = (0..points.len())
+					.filter(|index| squared_distance(pos, points[*index]) <= (radius as f64) * (radius as f64))
+					.collect();
+				assert_eq!(found, expected);
+			}
+			assert_eq!(tree.nearest_where((0.0, 0.0), |_| false), None);
+		}
+	}
+}
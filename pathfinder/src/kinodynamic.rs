@@ -0,0 +1,356 @@
+//! Kinodynamic RRT for a differential-drive robot
+//!
+//! Instead of straight lines, the tree is extended by forward simulating the robot with a set of
+//! admissible controls (forward and angular velocity) for a fixed time step.
+//! Each node stores the full state of the robot (x, y, heading), so every path in the tree can be driven.
+//!
+//! 1. A random point on the area is choosen.
+//! 2. The nearest node of the tree to the random point is searched in a KD-Tree.
+//! 3. From there, all controls are simulated and the collision free one ending nearest to the random point is added.
+
+use crate::{Instant, thread_rng};
+use crate::kd_tree::KdTree;
+use crate::robot::{DiffDrive, Pose};
+use crate::sampler::Sampler;
+use crate::tree::{is_line_free, Edge, RrtConfig};
+
+/// Configuration of the robot and the controls for the kinodynamic RRT
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KinodynamicConfig {
+	/// The robot to plan for
+	pub robot: DiffDrive,
+	/// Time to simulate each control in seconds
+	pub time_step: f64,
+	/// Number of forward velocities between the minimum and maximum velocity
+	pub velocity_samples: usize,
+	/// Number of angular velocities between the maximum left and right turn
+	pub angular_samples: usize,
+}
+
+impl Default for KinodynamicConfig {
+	fn default() -> Self {
+		KinodynamicConfig {
+			robot: DiffDrive::default(),
+			time_step: 1.0,
+			velocity_samples: 3,
+			angular_samples: 7,
+		}
+	}
+}
+
+/// A control of the robot: Forward velocity, angular velocity and the time to apply them
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Control {
+	/// Forward velocity
+	pub velocity: f64,
+	/// Angular velocity
+	pub angular_velocity: f64,
+	/// Time in seconds
+	pub time: f64,
+}
+
+/// The result of the kinodynamic RRT
+#[derive(Debug)]
+pub struct KinodynamicResult {
+	/// The driven path from the end to the start as fields
+	pub path: Vec<(usize, usize)>,
+	/// The tree as lines/tuples: ((x0, y0), (x1, y1))
+	pub tree: Vec<Edge>,
+	/// The states of the robot on each node from the start to the end
+	pub states: Vec<Pose>,
+	/// The controls to drive from one state to the next one
+	pub controls: Vec<Control>,
+}
+
+/// Inner type representing a node
+#[derive(Debug, Clone)]
+struct Node {
+	/// State of the robot
+	pose: Pose,
+	/// Parent node index
+	parent: usize,
+	/// Control to get from the parent to this node
+	control: Control,
+	/// All simulated poses from the parent to this node, for drawing and collision checking
+	trajectory: Vec<Pose>,
+	/// Driven distance from the start
+	distance: f64,
+}
+
+/// Use the kinodynamic Rapidly-Exploring RandomTree Algorithm for a differential-drive robot
+///
+/// # Arguments
+///
+/// * `area` - The play field as a one-dimensional vector
+/// * `rows` - number of rows
+/// * `cols` - number of cols
+/// * `start` - start pose of the robot
+/// * `end` - end position (row, col)
+/// * `config` - Configuration like the number of nodes, goal radius, sampling, ...
+/// * `kinodynamic` - The robot and its controls
+///
+/// # Result
+///
+/// The path, tree, states and controls
+///
+/// # Panics
+///
+/// If the configuration is invalid, see [`RrtConfig::validate`]
+pub fn rrt(area: &mut [u64], rows: &usize, cols: &usize, start: Pose, end: (usize, usize), config: &RrtConfig, kinodynamic: &KinodynamicConfig) -> KinodynamicResult {
+	let benchmark = Instant::now();
+	let mut rng = thread_rng();
+	let mut sampler = Sampler::new(config.sampling);
+
+	// Configuration
+	if let Err(err) = config.validate() { panic!("{}", err) }
+	let max_nodes = config.get_max_nodes(area.len());
+	let controls = get_controls(kinodynamic);
+	let goal = Pose::from_field(end, 0.0);
+
+	let mut nodes: Vec<Node> = Vec::with_capacity(max_nodes);
+	let mut index = KdTree::with_capacity(max_nodes);
+	let mut end_nodes: Vec<usize> = vec![];
+	let mut is_end: Vec<bool> = Vec::with_capacity(max_nodes);
+
+	// Initialize with the start node
+	nodes.push(Node {
+		pose: start,
+		parent: 0,
+		control: Control::default(),
+		trajectory: vec![start],
+		distance: 0.0,
+	});
+	index.insert((start.x, start.y), 0);
+	is_end.push(false);
+
+	while nodes.len() < max_nodes {
+		// 1. Get a random point on the area where to give the direction
+		let direction_node = sampler.sample(&mut rng, area, rows, cols, end);
+		let direction = Pose::from_field(direction_node, 0.0);
+
+		// 2. Find the nearest Node, do not grow the tree from the end
+		let Some((parent, _)) = index.nearest_where((direction.x, direction.y), |key| !is_end[key]) else { continue };
+
+		// 3. Simulate all controls and add the best collision free one
+		let Some((control, trajectory, reached)) = steer(area, rows, cols, &nodes[parent].pose, &direction, &goal, config.goal_radius as f64, &controls) else { continue };
+		let pose = *trajectory.last().unwrap_or(&nodes[parent].pose);
+		if pose.field(rows, cols).is_none() { continue }
+
+		let key = nodes.len();
+		nodes.push(Node {
+			pose,
+			parent,
+			control,
+			distance: nodes[parent].distance + (control.velocity * control.time),
+			trajectory,
+		});
+		index.insert((pose.x, pose.y), key);
+		is_end.push(reached);
+
+		if reached {
+			end_nodes.push(key);
+			println!("RRT-Kinodynamic End reached within {}: {:.6?}", nodes.len(), benchmark.elapsed());
+			if config.stop_on_first_solution { break; }
+		}
+	}
+
+	if end_nodes.is_empty() { println!("RRT-Kinodynamic Calc: No conneciton found"); }
+	println!("RRT-Kinodynamic Calc: {:.6?}", benchmark.elapsed());
+
+	// Find the end with the shortest distance, then go backwards to the start
+	let path_benchmark = Instant::now();
+	let finish = end_nodes.iter().min_by(|a, b| nodes[**a].distance.total_cmp(&nodes[**b].distance));
+	let mut branch = vec![];
+	if let Some(finish) = finish {
+		let mut current = *finish;
+		while current != 0 {
+			branch.push(current);
+			current = nodes[current].parent;
+		}
+		branch.push(0);
+	}
+
+	// The path from the end to the start over all simulated poses
+	let mut path: Vec<(usize, usize)> = vec![];
+	branch.iter()
+		.flat_map(|key| nodes[*key].trajectory.iter().rev())
+		.filter_map(|pose| pose.field(rows, cols))
+		.for_each(|field| if path.last() != Some(&field) { path.push(field) });
+
+	let states: Vec<Pose> = branch.iter().rev().map(|key| nodes[*key].pose).collect();
+	let controls: Vec<Control> = branch.iter().rev().skip(1).map(|key| nodes[*key].control).collect();
+
+	println!("RRT-Kinodynamic Path-Calculation: {:.6?}", path_benchmark.elapsed());
+	println!("RRT-Kinodynamic Path length: {}", path.len());
+	println!("RRT-Kinodynamic Tree Edges: {}", nodes.len());
+
+	// For the return value we need only the position as a tuple ((x0, y0), (x1, y1)) to draw the tree/network
+	let tree = nodes.iter()
+		.flat_map(|node| {
+			let parent = nodes[node.parent].pose;
+			std::iter::once(&parent).chain(node.trajectory.iter())
+				.zip(node.trajectory.iter())
+				.filter_map(|(from, to)| Some((from.field(rows, cols)?, to.field(rows, cols)?)))
+				.collect::<Vec<Edge>>()
+		})
+		.collect();
+
+	KinodynamicResult {
+		path,
+		tree,
+		states,
+		controls,
+	}
+}
+
+/// Creates all admissible controls of the robot
+///
+/// # Arguments:
+///
+/// * `kinodynamic` - The robot and the number of samples for the velocities
+///
+/// # Result:
+///
+/// All controls with the configured time step
+fn get_controls(kinodynamic: &KinodynamicConfig) -> Vec<Control> {
+	let robot = &kinodynamic.robot;
+	let velocities = usize::max(1, kinodynamic.velocity_samples);
+	let angulars = usize::max(1, kinodynamic.angular_samples);
+
+	let mut controls = vec![];
+	for v in 0..velocities {
+		let velocity = if velocities == 1 {
+			robot.max_velocity
+		} else {
+			robot.min_velocity + ((robot.max_velocity - robot.min_velocity) * v as f64 / (velocities - 1) as f64)
+		};
+		// Without an angular velocity left, all samples would be the same straight line
+		let max_angular = robot.max_angular_velocity(velocity);
+		let samples = if max_angular > 0.0 { angulars } else { 1 };
+		for a in 0..samples {
+			let angular_velocity = if samples == 1 {
+				0.0
+			} else {
+				-max_angular + (2.0 * max_angular * a as f64 / (samples - 1) as f64)
+			};
+			controls.push(Control { velocity, angular_velocity, time: kinodynamic.time_step });
+		}
+	}
+	controls
+}
+
+/// Simulates all controls from the given pose and returns the collision free one which ends nearest to the direction
+///
+/// # Arguments:
+///
+/// * `area` - The area as a vector of u64 where every obstacle is u64::MAX
+/// * `rows` - Number of rows, where rows x cols is the size of the area
+/// * `cols` - Number of columns, where rows x cols is the size of the area
+/// * `from` - Pose to start the simulation
+/// * `direction` - The point in which the tree should grow
+/// * `goal` - The End-Position
+/// * `goal_radius` - Radius around the end to catch the end position
+/// * `controls` - All controls to simulate
+///
+/// # Result:
+///
+/// The control, the simulated poses and if the goal was reached or `None` if all controls collide
+#[allow(clippy::too_many_arguments)]
+fn steer(area: &[u64], rows: &usize, cols: &usize, from: &Pose, direction: &Pose, goal: &Pose, goal_radius: f64, controls: &[Control]) -> Option<(Control, Vec<Pose>, bool)> {
+	let mut best: Option<(Control, Vec<Pose>, bool)> = None;
+	let mut best_distance = f64::MAX;
+
+	for control in controls {
+		if let Some((control, trajectory, reached)) = simulate(area, rows, cols, from, control, goal, goal_radius) {
+			// Reaching the goal is always the best choice
+			let distance = if reached { -1.0 } else { trajectory.last().map_or(f64::MAX, |pose| pose.distance(direction)) };
+			if distance < best_distance {
+				best_distance = distance;
+				best = Some((control, trajectory, reached));
+			}
+		}
+	}
+	best
+}
+
+/// Simulates one control in small steps and checks every step for collisions
+///
+/// The simulation stops early if the goal radius is reached.
+///
+/// # Result:
+///
+/// The applied control, all simulated poses and if the goal was reached or `None` if the robot collides
+fn simulate(area: &[u64], rows: &usize, cols: &usize, from: &Pose, control: &Control, goal: &Pose, goal_radius: f64) -> Option<(Control, Vec<Pose>, bool)> {
+	// One step per field the robot drives
+	let steps = f64::max(1.0, (control.velocity * control.time).abs().ceil()) as usize;
+	let step_time = control.time / steps as f64;
+
+	let mut trajectory = Vec::with_capacity(steps);
+	let mut last = *from;
+	for step in 1..=steps {
+		let pose = from.simulate(control.velocity, control.angular_velocity, step_time * step as f64);
+		if !is_line_free(area, rows, last.field(rows, cols)?, pose.field(rows, cols)?) {
+			return None;
+		}
+		trajectory.push(pose);
+		last = pose;
+
+		if pose.distance(goal) <= goal_radius {
+			let control = Control { time: step_time * step as f64, ..*control };
+			return Some((control, trajectory, true));
+		}
+	}
+	Some((*control, trajectory, false))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::sampler::Sampling;
+
+	/// The tree reaches the end around a block, the trajectories are free and the controls drive the robot from state to state
+	#[test]
+	fn path_is_drivable() {
+		let (rows, cols) = (80, 80);
+		// A block in the middle between the start and the end
+		let mut area = vec![0; rows * cols];
+		for col in 30..50 {
+			for row in 30..50 {
+				area[(col * rows) + row] = u64::MAX;
+			}
+		}
+		let config = RrtConfig { max_nodes: Some(5000), stop_on_first_solution: true, sampling: Sampling::GoalBias(0.1), ..RrtConfig::default() };
+		let kinodynamic = KinodynamicConfig::default();
+		let (start, end) = (Pose::from_field((10, 10), 0.0), (70, 70));
+		let result = rrt(&mut area.clone(), &rows, &cols, start, end, &config, &kinodynamic);
+
+		let last = result.states.last().expect("no path found");
+		assert!(last.distance(&Pose::from_field(end, 0.0)) <= config.goal_radius as f64);
+		assert_eq!(result.states[0], start);
+		assert_eq!(result.controls.len(), result.states.len() - 1);
+		for (control, pair) in result.controls.iter().zip(result.states.windows(2)) {
+			assert!(kinodynamic.robot.is_admissible(control.velocity, control.angular_velocity), "{:?} is not admissible", control);
+			let pose = pair[0].simulate(control.velocity, control.angular_velocity, control.time);
+			assert!(pose.distance(&pair[1]) < 1e-6, "{:?} does not lead from {:?} to {:?}", control, pair[0], pair[1]);
+		}
+
+		for (from, to) in &result.tree {
+			assert!(is_line_free(&area, &rows, *from, *to), "The trajectory from {:?} to {:?} collides", from, to);
+		}
+		for field in &result.path {
+			assert_ne!(area[(field.1 * rows) + field.0], u64::MAX, "{:?} collides", field);
+		}
+	}
+
+	/// Every control is admissible and the straight line at full speed is sampled only once
+	#[test]
+	fn controls_are_admissible_and_unique() {
+		let kinodynamic = KinodynamicConfig::default();
+		let controls = get_controls(&kinodynamic);
+		assert_eq!(controls.len(), (2 * kinodynamic.angular_samples) + 1);
+		for (index, control) in controls.iter().enumerate() {
+			assert!(kinodynamic.robot.is_admissible(control.velocity, control.angular_velocity), "{:?} is not admissible", control);
+			assert!(!controls[..index].contains(control), "{:?} is sampled twice", control);
+		}
+	}
+}
@@ -0,0 +1,33 @@
+//! Pathfinder-Algorithms in rust.
+//!
+//! All algorithms work on a one-dimensional area where a new column begins on each `rows` values.
+//! Obstacles are marked with `u64::MAX`, free fields with `0`.
+
+pub use std::time::Instant;
+pub use rand::distributions::{Distribution, Uniform};
+pub use rand::{thread_rng, Rng};
+
+pub mod grassfire;
+pub mod a_star;
+pub mod tree;
+pub mod sampler;
+pub mod kd_tree;
+pub mod robot;
+pub mod map;
+pub mod world;
+pub mod visibility_graph;
+pub mod potential_field;
+pub mod coverage;
+pub mod space_time;
+pub mod multi_agent;
+pub mod kinodynamic;
+pub mod dwa;
+pub mod curves;
+pub mod hybrid_a_star;
+pub mod smoothing;
+pub mod waypoints;
+pub mod trajectory;
+pub mod simulation;
+pub mod occupancy;
+pub mod batch;
+pub mod benchmark;
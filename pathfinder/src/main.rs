@@ -1,111 +1,527 @@
-pub use std::time::Instant;
-pub use rand::distributions::{Distribution, Uniform};
-pub use rand::{thread_rng, Rng};
-
 use std::env;
-use image::{ImageBuffer, ImageError, RgbImage};
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::process::ExitCode;
+use image::{GrayImage, ImageBuffer, ImageError, ImageFormat, Luma, RgbImage};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use pathfinder::{Instant, Distribution, Uniform, thread_rng, Rng};
+use pathfinder::tree::{self, Edge, RrtConfig};
+use pathfinder::robot::Pose;
+use pathfinder::kinodynamic::{self, KinodynamicConfig};
+use pathfinder::dwa::{self, DwaConfig};
+use pathfinder::simulation::{self, Controller, SimulationConfig};
+use pathfinder::hybrid_a_star::{self, HybridAStarConfig};
+use pathfinder::smoothing::{self, Spline};
+use pathfinder::waypoints::get_waypoints;
+use pathfinder::trajectory::{self, TrajectoryConfig};
+use pathfinder::visibility_graph;
+use pathfinder::potential_field::{self, PotentialFieldConfig};
+use pathfinder::coverage;
+use pathfinder::multi_agent::{self, Agent};
+use pathfinder::batch::{self, Algorithm, Query};
+use pathfinder::benchmark;
+use pathfinder::space_time::{self, DynamicObstacle, ReservationTable};
+
+mod cli;
+use cli::{Args, Command, MapSource, Planner, USAGE};
+
+/// The map all commands work on
+struct Scene {
+	/// The Play-Field is a one-dimensional vector where all columns are just in line
+	area: Vec<u64>,
+	rows: usize,
+	cols: usize,
+}
+
+fn main() -> ExitCode {
+	let args = match Args::parse(env::args().skip(1)) {
+		Ok(args) => args,
+		Err(err) => {
+			eprintln!("ERROR: {}\n\nRun `pathfinder help` for the usage.", err);
+			return ExitCode::FAILURE;
+		}
+	};
+
+	let result = match args.command {
+		Command::Plan => plan(&args),
+		Command::Generate => generate(&args),
+		Command::Bench => bench(&args),
+		Command::Render => render(&args),
+		Command::Help => {
+			println!("{}", USAGE);
+			Ok(())
+		}
+	};
+	match result {
+		Ok(()) => ExitCode::SUCCESS,
+		Err(err) => {
+			eprintln!("ERROR: {}", err);
+			ExitCode::FAILURE
+		}
+	}
+}
 
-mod grassfire;
-mod a_star;
-mod tree;
+/// Plans from the start to the goal with each selected algorithm and exports an image for each one
+fn plan(args: &Args) -> Result<(), String> {
+	let (start, end) = (args.start.unwrap_or_default(), args.goal.unwrap_or_default());
+	let mut rng = get_rng(args.seed);
+	let scene = get_scene(args, &mut rng, &[start, end])?;
+	println!("Tree:       {:?}\n", RrtConfig { world: None, ..args.tree.clone() });
 
-fn main() {
-	let args: Vec<String> = env::args().collect();
-	if args.len() < 7 {
-		panic!("Usage: {} ROWS COLS START_ROW START_COL END_ROW END_COL [OBSTACLES]\n   ROWS, COLS: Size of the playfield\n   START_*: Position of the Robot\n   END_*: The Position to reach\n   OBSTACLES: (100) Number of walls/obstacles", args[0]);
+	for planner in &args.planners {
+		run_planner(*planner, &scene.area, &scene.rows, &scene.cols, start, end, args, &mut rng);
 	}
+	Ok(())
+}
 
-	let rows  = &args[1].parse::<usize>().unwrap_or_default();
-	let cols  = &args[2].parse::<usize>().unwrap_or_default();
-	let start = ( &args[3].parse::<usize>().unwrap_or_default() - 1, &args[4].parse::<usize>().unwrap_or_default() - 1 );
-	let end   = ( &args[5].parse::<usize>().unwrap_or_default() - 1, &args[6].parse::<usize>().unwrap_or_default() - 1 );
-	let obstacles = if args.len() >= 8 { args[7].parse::<usize>().unwrap_or(100) } else { 100 };
-
-	assert!(start.0 < *rows, "Start-Position X {} is outside of the area {}", start.0, *rows);
-	assert!(start.1 < *cols, "Start-Position Y {} is outside of the area {}", start.1, *rows);
-	assert!(end.0   < *rows, "End-Position X {} is outside of the area {}", end.0, *rows);
-	assert!(end.1   < *cols, "End-Position Y {} is outside of the area {}", end.1, *rows);
-
-	// The Play-Field is a one-dimensional vector where all columns are just in line
-	let max_size = (rows / 10, cols / 10);
-	let mut area = create_area(rows, cols, &obstacles, &max_size);
-	let mut count = 0;
-	while area[(start.1 * rows) + start.0] == u64::MAX || area[(end.1 * rows) + end.0] == u64::MAX {
-		count += 1;
-		println!("Area {} invalid: Start or End is inside an obstacle.", count);
-		if count > 100 {
-			println!("ERROR: Unable to create a valid area. Change the values...");
-			return;
+/// Creates a random map and saves it as an image, obstacles are black and free fields white
+fn generate(args: &Args) -> Result<(), String> {
+	let mut rng = get_rng(args.seed);
+	let scene = get_scene(args, &mut rng, &[])?;
+	let format = args.format.unwrap_or(ImageFormat::Png);
+
+	let img = GrayImage::from_fn(scene.rows as u32, scene.cols as u32, |row, col| {
+		match scene.area[(col as usize * scene.rows) + row as usize] {
+			u64::MAX => Luma([0]),
+			_ => Luma([255]),
 		}
-		area = create_area(rows, cols, &obstacles, &max_size);
+	});
+	let file_name = format!("{}.{}", args.output, format.extensions_str()[0]);
+	img.save_with_format(&file_name, format).map_err(|err| format!("Unable to save {}: {}", file_name, err))?;
+	println!("Saved: {}", file_name);
+	Ok(())
+}
+
+/// Plans random queries with the selected algorithms on each map, writes each run as CSV and prints the statistics
+///
+/// With `--parallel` the queries of each map are planned in parallel by the batch mode and printed one by one instead.
+fn bench(args: &Args) -> Result<(), String> {
+	let mut rng = get_rng(args.seed);
+	let positions: Vec<(usize, usize)> = args.start.into_iter().chain(args.goal).collect();
+	let files = match &args.map {
+		MapSource::Directory(dir) => Some(get_map_files(dir)?),
+		_ => None,
+	};
+	let algorithms: Vec<Algorithm> = args.planners.iter()
+		.filter_map(|planner| match planner {
+			Planner::Grid(algorithm) => Some(*algorithm),
+			_ => None,
+		})
+		.collect();
+	println!("Tree:       {:?}", RrtConfig { world: None, ..args.tree.clone() });
+
+	let mut records = vec![];
+	for map in 0..files.as_ref().map_or(args.maps, Vec::len) {
+		println!("\nMap {}", map);
+		let scene = match &files {
+			Some(files) => load_map(&files[map], &positions)?,
+			None => get_scene(args, &mut rng, &positions)?,
+		};
+		let (area, rows, cols) = (&scene.area, &scene.rows, &scene.cols);
+		if positions.iter().any(|(row, col)| area[(col * rows) + row] == u64::MAX) {
+			return Err(format!("Start or End is inside an obstacle of the map {}.", map));
+		}
+
+		// The start and goal first, if they are connected on this map
+		let mut tasks: Vec<benchmark::Task> = vec![];
+		if let (Some(start), Some(end)) = (args.start, args.goal) {
+			match benchmark::get_optimum(area, rows, cols, start, end) {
+				Some(optimum) => tasks.push(((start, end), optimum)),
+				None => println!("Start and End are not connected, only random queries"),
+			}
+		}
+		tasks.extend(benchmark::get_tasks(&mut rng, area, rows, cols, args.queries - tasks.len()));
+		println!("Queries:    {}", tasks.len());
+		if args.parallel {
+			let queries: Vec<Query> = tasks.iter().map(|(query, _)| *query).collect();
+			for result in batch::run(area, rows, cols, &queries, &algorithms, &args.tree) {
+				let ((start, end), algorithm) = (result.query, result.algorithm.name());
+				println!("Batch {} {:?} -> {:?}: {} fields, Length {:.2}, {:.6?}", algorithm, start, end, result.path.len(), result.length, result.duration);
+			}
+			continue;
+		}
+		records.extend(benchmark::run(map, area, rows, cols, &tasks, &algorithms, &args.tree));
+	}
+	if args.parallel {
+		return Ok(());
 	}
 
-	println!("Field Size: {}x{}", rows, cols);
-	println!("Obstacles:  {} max {}x{}\n", obstacles, max_size.0, max_size.1);
-/*
-	// Use Grassfire for the path: 4-Neighborhood, Not optimized
-	{
-		#[allow(clippy::redundant_clone)]
-		let mut field = area.clone();
-		let path = grassfire::v1(&mut field, rows, cols, start, end);
-		let _ = export_image("grassfire_v1", &field, (rows, cols), start, end, &path, None);
+	let file = File::create(&args.csv).map_err(|err| format!("Unable to create {}: {}", args.csv, err))?;
+	benchmark::write_csv(&records, BufWriter::new(file)).map_err(|err| format!("Unable to write {}: {}", args.csv, err))?;
+	println!("\nSaved: {}\n", args.csv);
+	benchmark::write_table(&benchmark::summarize(&records, &algorithms), io::stdout())
+		.map_err(|err| format!("Unable to print the summary: {}", err))
+}
+
+/// Exports an image of the map with the start and goal
+fn render(args: &Args) -> Result<(), String> {
+	let mut rng = get_rng(args.seed);
+	let positions: Vec<(usize, usize)> = args.start.into_iter().chain(args.goal).collect();
+	let scene = get_scene(args, &mut rng, &positions)?;
+
+	export_image(&args.output, args.format, &scene.area, (&scene.rows, &scene.cols), args.start, args.goal, &[], None)
+		.map_err(|err| format!("Unable to save {}: {}", args.output, err))
+}
+
+/// Creates the random number generator from the seed or a random seed and prints the seed to repeat the run
+fn get_rng(seed: Option<u64>) -> StdRng {
+	let seed = seed.unwrap_or_else(|| thread_rng().gen());
+	println!("Seed:       {}", seed);
+	StdRng::seed_from_u64(seed)
+}
+
+/// Creates, loads or rasterizes the map where the positions are free
+///
+/// # Arguments:
+///
+/// * `args` - The arguments with the map source
+/// * `rng` - Random number generator for the random obstacles
+/// * `positions` - Fields which have to be on the map and free, e.g. the start and goal
+///
+/// # Result
+///
+/// The map or an error if a position is outside or inside an obstacle
+fn get_scene(args: &Args, rng: &mut StdRng, positions: &[(usize, usize)]) -> Result<Scene, String> {
+	let scene = match &args.map {
+		MapSource::Random { rows, cols, obstacles } => {
+			check_positions(rows, cols, positions)?;
+			let max_size = (rows / 10, cols / 10);
+			let is_blocked = |area: &[u64]| positions.iter().any(|(row, col)| area[(col * rows) + row] == u64::MAX);
+			let mut area = create_area(rng, rows, cols, obstacles, &max_size);
+			let mut count = 0;
+			while is_blocked(&area) {
+				count += 1;
+				println!("Area {} invalid: Start or End is inside an obstacle.", count);
+				if count > 100 {
+					return Err("Unable to create a valid area. Change the values...".to_string());
+				}
+				area = create_area(rng, rows, cols, obstacles, &max_size);
+			}
+			println!("Field Size: {}x{}", rows, cols);
+			println!("Obstacles:  {} max {}x{}", obstacles, max_size.0, max_size.1);
+			Scene { area, rows: *rows, cols: *cols }
+		}
+		MapSource::Image(file) => load_map(file, positions)?,
+		MapSource::Directory(dir) => return Err(format!("The map is a directory: {}", dir)),
+		MapSource::World { world, rows, cols } => {
+			check_positions(rows, cols, positions)?;
+			// Rasterize the world for the grid algorithms
+			let mut area = vec![0; rows * cols];
+			world.rasterize(&mut area, rows, cols);
+			println!("Field Size: {}x{}", rows, cols);
+			println!("Obstacles:  {} shapes of the world", world.shapes.len());
+			Scene { area, rows: *rows, cols: *cols }
+		}
+	};
+
+	if positions.iter().any(|(row, col)| scene.area[(col * scene.rows) + row] == u64::MAX) {
+		return Err("Start or End is inside an obstacle of the map.".to_string());
 	}
+	Ok(scene)
+}
 
-	// Use Grassfire for the path: 4-Neighborhood, Optimized
-	{
-		#[allow(clippy::redundant_clone)]
-		let mut field = area.clone();
-		let path = grassfire::v2(&mut field, rows, cols, start, end);
-		let _ = export_image("grassfire_v2", &field, (rows, cols), start, end, &path, None);
+/// Loads the map from an image, dark pixels are obstacles
+fn load_map(file: &str, positions: &[(usize, usize)]) -> Result<Scene, String> {
+	let img = image::open(file).map_err(|err| format!("Unable to load the map {}: {}", file, err))?.to_luma8();
+	let (rows, cols) = (img.width() as usize, img.height() as usize);
+	check_positions(&rows, &cols, positions)?;
+	let mut area = vec![0; rows * cols];
+	for (row, col, pixel) in img.enumerate_pixels() {
+		if pixel.0[0] < 128 {
+			area[(col as usize * rows) + row as usize] = u64::MAX;
+		}
 	}
+	println!("Field Size: {}x{}", rows, cols);
+	println!("Obstacles:  {} fields of the map {}", area.iter().filter(|val| **val == u64::MAX).count(), file);
+	Ok(Scene { area, rows, cols })
+}
 
-	// Use Grassfire for the path: 8-Neighborhood, Based on v2
-	{
-		#[allow(clippy::redundant_clone)]
-		let mut field = area.clone();
-		let path = grassfire::v3(&mut field, rows, cols, start, end);
-		let _ = export_image("grassfire_v3", &field, (rows, cols), start, end, &path, None);
+/// Returns the image files of a directory sorted by name
+fn get_map_files(dir: &str) -> Result<Vec<String>, String> {
+	let entries = fs::read_dir(dir).map_err(|err| format!("Unable to read the directory {}: {}", dir, err))?;
+	let mut files: Vec<String> = entries
+		.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+		.filter(|path| path.is_file() && ImageFormat::from_path(path).is_ok())
+		.map(|path| path.to_string_lossy().into_owned())
+		.collect();
+	if files.is_empty() {
+		return Err(format!("No images in the directory {}", dir));
 	}
+	files.sort();
+	Ok(files)
+}
 
-	// Use Grassfire for the path: 4-Neighborhood for calculation fut 8-Neighborhood for the path
-	{
-		#[allow(clippy::redundant_clone)]
-		let mut field = area.clone();
-		let path = grassfire::v4(&mut field, rows, cols, start, end);
-		let _ = export_image("grassfire_v4", &field, (rows, cols), start, end, &path, None);
+/// Checks if all positions are on an area of the given size
+fn check_positions(rows: &usize, cols: &usize, positions: &[(usize, usize)]) -> Result<(), String> {
+	match positions.iter().find(|(row, col)| row >= rows || col >= cols) {
+		Some((row, col)) => Err(format!("Position {},{} is outside of the area {}x{}", row + 1, col + 1, rows, cols)),
+		None => Ok(()),
 	}
+}
+
+/// Runs one planner from the start to the end and exports its images
+///
+/// # Arguments:
+///
+/// * `planner` - The planner to run
+/// * `area` - The map, each planner works on its own copy
+/// * `rows` - Number of rows of the area
+/// * `cols` - Number of columns of the area
+/// * `start` - Start position (row, col)
+/// * `end` - End position (row, col)
+/// * `args` - The arguments with the configuration of the trees and the output
+/// * `rng` - Random number generator for the slip of the simulation, seeded with `--seed`
+#[allow(clippy::too_many_arguments)]
+fn run_planner(planner: Planner, area: &[u64], rows: &usize, cols: &usize, start: (usize, usize), end: (usize, usize), args: &Args, rng: &mut StdRng) {
+	let config = &args.tree;
+	let export = |name: &str, area: &[u64], path: &[(usize, usize)], lines: Option<&[Edge]>| {
+		let _ = export_image(name, args.format, area, (rows, cols), Some(start), Some(end), path, lines);
+	};
+
+	match planner {
+		// Use RRT - Rapidly-Exploring Random Tree
+		Planner::Grid(Algorithm::RrtV1) => {
+			let mut field = area.to_vec();
+			let path = tree::rrt_v1(&mut field, rows, cols, start, end, config);
+			export("rrt_v1", &field, &path.path, Some(&path.tree));
+			println!("RRT-V1 Length: {:.2}", get_length(&path.waypoints));
+			post_process("rrt_v1", area, rows, cols, start, end, &path.path, args);
+		}
+		// Use RRT* - Rapidly-Exploring Random Tree with rewiring
+		Planner::Grid(Algorithm::RrtV2) => {
+			let mut field = area.to_vec();
+			let path = tree::rrt_v2(&mut field, rows, cols, start, end, config);
+			export("rrt_v2", &field, &path.path, Some(&path.tree));
+			println!("RRT-V2 Length: {:.2}", get_length(&path.waypoints));
+			post_process("rrt_v2", area, rows, cols, start, end, &path.path, args);
+		}
+		// Use RRT* - Rapidly-Exploring Random Tree with optimized area after the end is reached
+		Planner::Grid(Algorithm::RrtV3) => {
+			let mut field = area.to_vec();
+			let path = tree::rrt_v3(&mut field, rows, cols, start, end, config);
+			export("rrt_v3", &field, &path.path, Some(&path.tree));
+			println!("RRT-V3 Length: {:.2}", get_length(&path.waypoints));
+			post_process("rrt_v3", area, rows, cols, start, end, &path.path, args);
+		}
+		// Use RRT-Connect - Two Rapidly-Exploring Random Trees from the start and the end
+		Planner::Grid(Algorithm::RrtConnect) => {
+			let mut field = area.to_vec();
+			let path = tree::rrt_connect(&mut field, rows, cols, start, end, config);
+			export("rrt_connect", &field, &path.path, Some(&path.tree));
+			println!("RRT-Connect Length: {:.2}", get_length(&path.waypoints));
+			post_process("rrt_connect", area, rows, cols, start, end, &path.path, args);
+		}
+		// Use Grassfire or A* for the path, the image shows the costs of each field
+		Planner::Grid(algorithm) => {
+			let mut field = area.to_vec();
+			let path = algorithm.plan(&mut field, rows, cols, start, end, config);
+			let name = algorithm.name().replace('-', "_");
+			export(&name, &field, &path, None);
+			post_process(&name, area, rows, cols, start, end, &path, args);
+		}
+		// Use the Potential-Field: Attracted by the end, repelled by obstacles
+		Planner::PotentialField => {
+			let mut field = area.to_vec();
+			let path = potential_field::calculate(&mut field, rows, cols, start, end, &PotentialFieldConfig::default());
+			export("potential_field", &field, &path, None);
+		}
+		// Use the Navigation-Function: Potential-Field on the grassfire distance, free of local minima
+		Planner::NavigationFunction => {
+			let mut field = area.to_vec();
+			let path = potential_field::navigation_function(&mut field, rows, cols, start, end, &PotentialFieldConfig::default());
+			export("navigation_function", &field, &path, None);
+		}
+		// Use the Coverage-Planner: Lawnmower path over the boustrophedon cells with a tool of 5 fields
+		Planner::Coverage => {
+			let mut field = area.to_vec();
+			let path = coverage::calculate(&mut field, rows, cols, start, 5);
+			export("coverage", &field, &path.path, None);
+		}
+		// Use Multi-Agent planning: Four robots between the corners of start and end cross each other
+		Planner::MultiAgent => {
+			let corners = [start, end, (start.0, end.1), (end.0, start.1)];
+			let mut agents: Vec<Agent> = vec![];
+			for (a, b) in [(corners[0], corners[1]), (corners[1], corners[0]), (corners[2], corners[3]), (corners[3], corners[2])] {
+				let used = agents.iter().any(|(start, end)| *start == a || *end == b);
+				if a != b && !used && area[(a.1 * rows) + a.0] != u64::MAX && area[(b.1 * rows) + b.0] != u64::MAX {
+					agents.push((a, b));
+				}
+			}
+			let paths = multi_agent::prioritized(area, rows, cols, &agents);
+			export("multi_agent_prioritized", area, &paths.concat(), None);
+			let paths = multi_agent::cbs(area, rows, cols, &agents, 1000);
+			export("multi_agent_cbs", area, &paths.concat(), None);
+		}
+		// Use Space-Time-A*: Two robots with known schedules cross between the other corners of start and end
+		Planner::SpaceTime => {
+			let corners = [(start.0, end.1), (end.0, start.1)];
+			let obstacles: Vec<DynamicObstacle> = [(corners[0], corners[1]), (corners[1], corners[0])].into_iter()
+				.filter(|(a, b)| ![start, end].contains(a) && ![start, end].contains(b))
+				.filter(|(a, b)| area[(a.1 * rows) + a.0] != u64::MAX && area[(b.1 * rows) + b.0] != u64::MAX)
+				.map(|(a, b)| {
+					let distances = space_time::get_distances(area, rows, cols, b);
+					DynamicObstacle { trajectory: space_time::a_star(area, rows, cols, a, b, &ReservationTable::new(), &distances), radius: 1 }
+				})
+				.collect();
+			let path = space_time::calculate(area, rows, cols, start, end, &obstacles);
+			let fields: Vec<(usize, usize)> = path.iter().map(|(field, _)| *field).collect();
+			export("space_time", area, &fields, None);
+
+			// One image per time step with the obstacles and their remaining trajectories
+			if args.frames {
+				let duration = obstacles.iter().map(|obstacle| obstacle.trajectory.len()).chain([path.len()]).max().unwrap_or(0);
+				for time in 0..duration {
+					let mut field = area.to_vec();
+					for obstacle in &obstacles {
+						for (row, col) in obstacle.get_fields(time, rows, cols) {
+							field[(col * rows) + row] = 1;
+						}
+					}
+					let lines: Vec<Edge> = obstacles.iter()
+						.flat_map(|obstacle| obstacle.trajectory.iter().skip(time).zip(obstacle.trajectory.iter().skip(time + 1)))
+						.map(|(a, b)| (*a, *b))
+						.collect();
+					export(&format!("space_time_{:04}", time), &field, &fields[..usize::min(time + 1, fields.len())], Some(&lines));
+				}
+			}
+		}
+		// Use the Dynamic-Window-Approach: Follow the A* path on the inflated map around obstacles which are not on the map
+		Planner::Dwa => {
+			let dwa_config = DwaConfig { radius: 2.0, max_velocity: 10.0, lookahead: 15.0, max_time: 300.0, ..DwaConfig::default() };
+			let mut field = dwa::inflate(area, rows, cols, dwa_config.radius);
+			let global: Vec<(usize, usize)> = pathfinder::a_star::calculate(&mut field, rows, cols, start, end).into_iter().rev().collect();
+
+			// Unmapped obstacles on the global path from a third and two thirds on, where the map around them is free
+			let mut local = area.to_vec();
+			let size = (usize::max(1, rows / 50), usize::max(1, cols / 50));
+			let square = |(row, col): (usize, usize), scale: usize| {
+				let rows = row.saturating_sub(size.0 * scale)..usize::min(*rows, row + (size.0 * scale));
+				(col.saturating_sub(size.1 * scale)..usize::min(*cols, col + (size.1 * scale))).flat_map(move |col| rows.clone().map(move |row| (row, col)))
+			};
+			for from in [global.len() / 3, 2 * global.len() / 3] {
+				let free = global.iter().skip(from).find(|field| square(**field, 3).all(|(row, col)| area[(col * rows) + row] != u64::MAX));
+				for (row, col) in free.map(|field| square(*field, 1)).into_iter().flatten() {
+					local[(col * rows) + row] = u64::MAX;
+				}
+			}
 
-	// Use A* for the path
-	{
-		#[allow(clippy::redundant_clone)]
-		let mut field = area.clone();
-		let path = a_star::calculate(&mut field, rows, cols, start, end);
-		let _ = export_image("a_star_calculate", &field, (rows, cols), start, end, &path, None);
+			let ahead = global.get(10).or(global.last()).copied().unwrap_or(end);
+			let heading = f64::atan2(ahead.1 as f64 - start.1 as f64, ahead.0 as f64 - start.0 as f64);
+			let path = dwa::follow(&local, rows, cols, Pose::from_field(start, heading), &global, &dwa_config);
+			let lines: Vec<Edge> = global.windows(2).map(|pair| (pair[0], pair[1])).collect();
+			export("dwa", &local, &path.path, Some(&lines));
+		}
+		// Use the Simulation: Drive the smoothed A* path on the inflated map with noisy wheels and encoders
+		Planner::Simulation => {
+			let simulation_config = SimulationConfig::default();
+			// Keep a margin of three times the radius, the controllers cut the corners or swing out
+			let margin = 3.0 * simulation_config.radius;
+			let inflated = dwa::inflate(area, rows, cols, margin);
+			let path = pathfinder::a_star::calculate(&mut inflated.clone(), rows, cols, start, end);
+			// A* returns the path from the end back to the start, only the end if there is no connection
+			if path.last() != Some(&start) {
+				println!("Simulation: No connection");
+				return;
+			}
+			let waypoints = smoothing::shortcut(&inflated, rows, &path);
+			let planned: Vec<(f64, f64)> = smoothing::smooth(area, rows, cols, &waypoints, Spline::default(), margin as usize).into_iter().rev().collect();
+			let lines: Vec<Edge> = smoothing::to_fields(&planned).windows(2).map(|pair| (pair[0], pair[1])).collect();
+
+			let ahead = planned.get(1).copied().unwrap_or((end.0 as f64, end.1 as f64));
+			let heading = f64::atan2(ahead.1 - start.1 as f64, ahead.0 - start.0 as f64);
+			for (name, controller) in [("simulation_pure_pursuit", Controller::PurePursuit { lookahead: 20.0 }), ("simulation_stanley", Controller::Stanley { gain: 2.0 })] {
+				let config = SimulationConfig { controller, ..simulation_config };
+				let result = simulation::simulate(rng, area, rows, cols, Pose::from_field(start, heading), &planned, &config);
+				export(name, area, &result.path, Some(&lines));
+				let file_name = format!("{}.csv", name);
+				if let Err(err) = File::create(&file_name).and_then(|file| result.write_csv(BufWriter::new(file))) {
+					eprintln!("ERROR: Unable to write {}: {}", file_name, err);
+				}
+			}
+		}
+		// Use a kinodynamic RRT - Forward simulated differential-drive robot
+		Planner::RrtKinodynamic => {
+			let mut field = area.to_vec();
+			let path = kinodynamic::rrt(&mut field, rows, cols, Pose::from_field(start, 0.0), end, config, &KinodynamicConfig::default());
+			export("rrt_kinodynamic", &field, &path.path, Some(&path.tree));
+		}
+		// Use Hybrid A* - Drivable path with a minimum turning radius
+		Planner::HybridAStar => {
+			let mut field = area.to_vec();
+			let path = hybrid_a_star::calculate(&mut field, rows, cols, Pose::from_field(start, 0.0), Pose::from_field(end, 0.0), &HybridAStarConfig::default());
+			export("hybrid_a_star", &field, &path.path, Some(&path.tree));
+		}
+		// Use the Visibility-Graph - The optimal path through the corners of a vector world
+		Planner::VisibilityGraph => {
+			if let Some(world) = &config.world {
+				let path = visibility_graph::calculate(world, rows, cols, start, end, 1.0);
+				println!("Visibility-Graph Length: {:.2}", path.length);
+				export("visibility_graph", area, &path.path, Some(&path.graph));
+			}
+		}
 	}
-*/
-	// Use RRT - Rapidly-Exploring Random Tree
-	{
-		#[allow(clippy::redundant_clone)]
-		let mut field = area.clone();
-		let path = tree::rrt_v1(&mut field, rows, cols, start, end);
-		let _ = export_image("rrt_v1", &field, (rows, cols), start, end, &path.path, Some(&path.tree));
+}
+
+/// Reduces the path of a grid or tree algorithm to waypoints with `--waypoints`
+/// and smoothes it with `--smooth`, exports it and its trajectory for the robot
+///
+/// # Arguments:
+///
+/// * `name` - Name of the algorithm for the files
+/// * `area` - The map without the values of the algorithm
+/// * `rows` - Number of rows of the area
+/// * `cols` - Number of columns of the area
+/// * `start` - Start position (row, col)
+/// * `end` - End position (row, col)
+/// * `path` - The path of the algorithm, in any direction
+/// * `args` - The arguments with the spline and the output
+#[allow(clippy::too_many_arguments)]
+fn post_process(name: &str, area: &[u64], rows: &usize, cols: &usize, start: (usize, usize), end: (usize, usize), path: &[(usize, usize)], args: &Args) {
+	if args.smooth.is_none() && !args.waypoints {
+		return;
 	}
-	// Use RRT* - Rapidly-Exploring Random Tree with rewiring
-	{
-		#[allow(clippy::redundant_clone)]
-		let mut field = area.clone();
-		let path = tree::rrt_v2(&mut field, rows, cols, start, end);
-		let _ = export_image("rrt_v2", &field, (rows, cols), start, end, &path.path, Some(&path.tree));
+	// From the start to the end, failed algorithms return only a part of it
+	let forward: Vec<(usize, usize)> = match (path.first(), path.last()) {
+		(Some(first), Some(last)) if *first == start && *last == end => path.to_vec(),
+		(Some(first), Some(last)) if *first == end && *last == start => path.iter().rev().copied().collect(),
+		_ => {
+			println!("{} Post-processing: No connection", name);
+			return;
+		}
+	};
+
+	// Waypoints with turn radii for the path follower of the robot
+	if args.waypoints {
+		let waypoints = get_waypoints(area, rows, cols, &forward, 2.0, 20.0);
+		println!("{} Waypoints: {}", name, waypoints.iter()
+			.map(|waypoint| format!("({:.1}, {:.1}, {:.1})", waypoint.x, waypoint.y, waypoint.turn_radius))
+			.collect::<Vec<String>>()
+			.join(" "));
 	}
-	// Use RRT* - Rapidly-Exploring Random Tree with optimized area after the end is reached
-	{
-		#[allow(clippy::redundant_clone)]
-		let mut field = area.clone();
-		let path = tree::rrt_v3(&mut field, rows, cols, start, end);
-		let _ = export_image("rrt_v3", &field, (rows, cols), start, end, &path.path, Some(&path.tree));
+	let Some(spline) = args.smooth else { return };
+
+	// Shortcut the jagged path and fit a spline through the remaining waypoints
+	let waypoints = smoothing::shortcut(area, rows, &forward);
+	let smoothed = smoothing::smooth(area, rows, cols, &waypoints, spline, 2);
+	println!("{} Smooth: {} waypoints, Length {:.2}", name, waypoints.len(), get_length(&smoothed));
+	let _ = export_image(&format!("{}_smooth", name), args.format, area, (rows, cols), Some(start), Some(end), &smoothing::to_fields(&smoothed), None);
+
+	// Time-parameterize the smoothed path for the robot
+	let trajectory = trajectory::get_trajectory(&smoothed, &TrajectoryConfig::default());
+	println!("{} Trajectory: {:.2}s", name, trajectory.duration());
+	let file_name = format!("{}_trajectory.csv", name);
+	if let Err(err) = File::create(&file_name).and_then(|file| trajectory.write_csv(BufWriter::new(file))) {
+		eprintln!("ERROR: Unable to write {}: {}", file_name, err);
 	}
 }
 
+/// Returns the length of the polyline through the given points
+fn get_length(points: &[(f64, f64)]) -> f64 {
+	points.windows(2).map(|pair| f64::hypot(pair[1].0 - pair[0].0, pair[1].1 - pair[0].1)).sum()
+}
+
 /// Creates the area and adds random created obstacles
 ///
 /// The Area is a 1-Dimensonal vector with rows*cols fields
@@ -118,6 +534,7 @@ fn main() {
 ///
 /// # Arguments:
 ///
+/// * `rng` - Random number generator for the obstacles
 /// * `rows` - Number of rows for the area
 /// * `cols` - Number of columns for the area
 /// * `obstacles` - Number of obstacles
@@ -126,7 +543,7 @@ fn main() {
 /// # Result
 ///
 /// A vector which represents the whole area including obstacles
-fn create_area(rows: &usize, cols: &usize, obstacles: &usize, max_size: &(usize, usize)) -> Vec<u64> {
+fn create_area(rng: &mut impl Rng, rows: &usize, cols: &usize, obstacles: &usize, max_size: &(usize, usize)) -> Vec<u64> {
 	let benchmark = Instant::now();
 	let max = *rows * *cols;
 	let mut area:Vec<u64> = vec![0; max];
@@ -140,12 +557,11 @@ fn create_area(rows: &usize, cols: &usize, obstacles: &usize, max_size: &(usize,
 		let range_y = Uniform::from(0..*cols);
 		let size_x = Uniform::from(0..max_size.0);
 		let size_y = Uniform::from(0..max_size.1);
-		let mut rng = rand::thread_rng();
 		for _ in 0..*obstacles {
-			let x = range_x.sample(&mut rng);
-			let y = range_y.sample(&mut rng);
-			let sx = size_x.sample(&mut rng);
-			let sy = size_y.sample(&mut rng);
+			let x = range_x.sample(rng);
+			let y = range_y.sample(rng);
+			let sx = size_x.sample(rng);
+			let sy = size_y.sample(rng);
 
 			for row in x..(x + sx) {
 				if row+1 > *rows {
@@ -172,10 +588,11 @@ fn create_area(rows: &usize, cols: &usize, obstacles: &usize, max_size: &(usize,
 /// # Arguments:
 ///
 /// * `algorith` - Name of the algorithm used for the area and path - used for the filename
+/// * `format` - Format of the image, `None` to skip the export
 /// * `area` - The area processed by the algorithm - A one dimensional list where on each rows entries a new column begins
 /// * `area_size` - Tuple represents the rows and columns of the area
-/// * `start` - Tuple with the start point (row, col)
-/// * `end` - Tuple with the end point (row, col)
+/// * `start` - Optional tuple with the start point (row, col)
+/// * `end` - Optional tuple with the end point (row, col)
 /// * `path` - List of tuples where the robot should drive on
 /// * `line` - Optional list of tuples to draw a line between x and y ((x0, y0), (x1, y1))
 ///
@@ -183,7 +600,8 @@ fn create_area(rows: &usize, cols: &usize, obstacles: &usize, max_size: &(usize,
 ///
 /// Error from the image creation
 #[allow(clippy::too_many_arguments)]
-fn export_image(algorithm: &str, area: &[u64], area_size: (&usize, &usize), start: (usize, usize), end: (usize, usize), path: &[(usize, usize)], line: Option<&[((usize, usize), (usize, usize))]>) -> Result<(), ImageError> {
+fn export_image(algorithm: &str, format: Option<ImageFormat>, area: &[u64], area_size: (&usize, &usize), start: Option<(usize, usize)>, end: Option<(usize, usize)>, path: &[(usize, usize)], line: Option<&[Edge]>) -> Result<(), ImageError> {
+	let Some(format) = format else { return Ok(()) };
 	let field_size = if area_size.0 > &200 || area_size.1 > &200 { 1 } else { 5 };
 	let fild_size_offset = field_size / 2;
 	let mut img: RgbImage = ImageBuffer::new((area_size.0 * field_size) as u32, (area_size.1 * field_size) as u32);
@@ -232,7 +650,7 @@ fn export_image(algorithm: &str, area: &[u64], area_size: (&usize, &usize), star
 	}
 
 	// Draw the path
-	if path.len() > 0 {
+	if !path.is_empty() {
 		let mut last = &path[0];
 		for p in path {
 			// Draw a Point/Node
@@ -256,29 +674,33 @@ fn export_image(algorithm: &str, area: &[u64], area_size: (&usize, &usize), star
 	}
 
 	// Draw start
-	let color = if line.is_some() { [164, 8, 160] } else { [243, 219, 5] };
-	let c_row = start.0 * field_size;
-	let c_col = start.1 * field_size;
-	for x in c_row..(c_row + field_size) {
-		for y in c_col..(c_col + field_size) {
-			let px = img.get_pixel_mut(x as u32, y as u32);
-			*px = image::Rgb(color);
+	if let Some(start) = start {
+		let color = if line.is_some() { [164, 8, 160] } else { [243, 219, 5] };
+		let c_row = start.0 * field_size;
+		let c_col = start.1 * field_size;
+		for x in c_row..(c_row + field_size) {
+			for y in c_col..(c_col + field_size) {
+				let px = img.get_pixel_mut(x as u32, y as u32);
+				*px = image::Rgb(color);
+			}
 		}
 	}
 
 	// Draw the end
-	let color = if line.is_some() { [130, 12, 30] } else { [36, 178, 156] };
-	let c_row = end.0 * field_size;
-	let c_col = end.1 * field_size;
-	for x in c_row..(c_row + field_size) {
-		for y in c_col..(c_col + field_size) {
-			let px = img.get_pixel_mut(x as u32, y as u32);
-			*px = image::Rgb(color);
+	if let Some(end) = end {
+		let color = if line.is_some() { [130, 12, 30] } else { [36, 178, 156] };
+		let c_row = end.0 * field_size;
+		let c_col = end.1 * field_size;
+		for x in c_row..(c_row + field_size) {
+			for y in c_col..(c_col + field_size) {
+				let px = img.get_pixel_mut(x as u32, y as u32);
+				*px = image::Rgb(color);
+			}
 		}
 	}
 
-	let file_name = format!("{}.png", algorithm);
-	img.save(file_name.clone())?;
+	let file_name = format!("{}.{}", algorithm, format.extensions_str()[0]);
+	img.save_with_format(&file_name, format)?;
 	println!("Saved: {}\n", file_name);
 	Ok(())
 }
@@ -292,8 +714,8 @@ fn export_image(algorithm: &str, area: &[u64], area_size: (&usize, &usize), star
 /// * `p1` - Start point
 /// * `p2` - End point
 fn draw_line(img: &mut RgbImage, color: [u8; 3], p1: (usize, usize), p2: (usize, usize)) {
-	let dx = if p1.0 > p2.0 { p1.0 - p2.0 } else { p2.0 - p1.0 } as i64;
-	let dy = if p1.1 > p2.1 { p1.1 - p2.1 } else { p2.1 - p1.1 } as i64;
+	let dx = p1.0.abs_diff(p2.0) as i64;
+	let dy = p1.1.abs_diff(p2.1) as i64;
 
 	let sx = if p1.0 < p2.0 { 1 } else { -1 } as i64;
 	let sy = if p1.1 < p2.1 { 1 } else { -1 } as i64;
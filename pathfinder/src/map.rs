@@ -0,0 +1,113 @@
+//! Map frame between the world coordinates of the robot and the fields of the area
+//!
+//! The area is a grid of `rows x cols` fields, each `resolution` metres wide.
+//! The origin is the pose of the corner of the first field `(0, 0)` in the world: the rows grow into the direction of
+//! its heading and the columns 90° to the left of it.
+//!
+//! Grid coordinates are continuous, the field `(row, col)` covers `row..row + 1` and `col..col + 1`,
+//! its middle is `(row + 0.5, col + 0.5)` like `robot::Pose::from_field`.
+
+use crate::robot::{normalize_angle, Pose};
+
+/// The map frame of an area
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Map {
+	/// Number of rows of the area
+	pub rows: usize,
+	/// Number of columns of the area
+	pub cols: usize,
+	/// Size of one field in metres
+	pub resolution: f64,
+	/// Pose of the corner of the first field in the world
+	pub origin: Pose,
+}
+
+impl Map {
+	/// Creates a new map frame
+	///
+	/// # Arguments:
+	///
+	/// * `rows` - Number of rows of the area
+	/// * `cols` - Number of columns of the area
+	/// * `resolution` - Size of one field in metres
+	/// * `origin` - Pose of the corner of the first field in the world
+	pub fn new(rows: usize, cols: usize, resolution: f64, origin: Pose) -> Self {
+		Map { rows, cols, resolution, origin }
+	}
+
+	/// Converts a world position in metres to continuous grid coordinates
+	pub fn to_grid(&self, world: (f64, f64)) -> (f64, f64) {
+		let (sin, cos) = self.origin.heading.sin_cos();
+		let (dx, dy) = (world.0 - self.origin.x, world.1 - self.origin.y);
+		(((dx * cos) + (dy * sin)) / self.resolution, ((dy * cos) - (dx * sin)) / self.resolution)
+	}
+
+	/// Converts continuous grid coordinates to a world position in metres
+	pub fn to_world(&self, grid: (f64, f64)) -> (f64, f64) {
+		let (sin, cos) = self.origin.heading.sin_cos();
+		let (x, y) = (grid.0 * self.resolution, grid.1 * self.resolution);
+		(self.origin.x + (x * cos) - (y * sin), self.origin.y + (x * sin) + (y * cos))
+	}
+
+	/// Returns the field of a world position or `None` if it is outside of the area
+	pub fn to_field(&self, world: (f64, f64)) -> Option<(usize, usize)> {
+		let (x, y) = self.to_grid(world);
+		Pose::new(x, y, 0.0).field(&self.rows, &self.cols)
+	}
+
+	/// Returns the world position of the middle of a field
+	pub fn field_to_world(&self, field: (usize, usize)) -> (f64, f64) {
+		self.to_world((field.0 as f64 + 0.5, field.1 as f64 + 0.5))
+	}
+
+	/// Converts a pose in the world to a pose in grid coordinates
+	pub fn pose_to_grid(&self, pose: &Pose) -> Pose {
+		let (x, y) = self.to_grid((pose.x, pose.y));
+		Pose::new(x, y, normalize_angle(pose.heading - self.origin.heading))
+	}
+
+	/// Converts a pose in grid coordinates to a pose in the world
+	pub fn pose_to_world(&self, pose: &Pose) -> Pose {
+		let (x, y) = self.to_world((pose.x, pose.y));
+		Pose::new(x, y, normalize_angle(pose.heading + self.origin.heading))
+	}
+
+	/// Converts a distance in metres to fields
+	pub fn to_grid_distance(&self, distance: f64) -> f64 {
+		distance / self.resolution
+	}
+
+	/// Converts a distance in fields to metres
+	pub fn to_world_distance(&self, distance: f64) -> f64 {
+		distance * self.resolution
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{thread_rng, Rng};
+	use std::f64::consts::PI;
+
+	/// Converting to the grid and back has to return the same position and heading
+	#[test]
+	fn conversion_is_reversible() {
+		let mut rng = thread_rng();
+		for _ in 0..1000 {
+			let origin = Pose::new(rng.gen_range(-5.0..5.0), rng.gen_range(-5.0..5.0), rng.gen_range(-PI..PI));
+			let map = Map::new(100, 200, rng.gen_range(0.001..0.1), origin);
+			let pose = Pose::new(rng.gen_range(-5.0..5.0), rng.gen_range(-5.0..5.0), rng.gen_range(-PI..PI));
+
+			let back = map.pose_to_world(&map.pose_to_grid(&pose));
+			assert!(back.distance(&pose) < 1e-9, "{:?} is {:?} after the conversion", pose, back);
+			assert!(normalize_angle(back.heading - pose.heading).abs() < 1e-9);
+		}
+
+		// The columns are 90° to the left of the rows
+		let map = Map::new(10, 10, 0.5, Pose::new(1.0, 2.0, PI / 2.0));
+		let (x, y) = map.field_to_world((1, 0));
+		assert!((x - 0.75).abs() < 1e-9 && (y - 2.75).abs() < 1e-9);
+		assert_eq!(map.to_field((0.5, 2.25)), Some((0, 1)));
+		assert_eq!(map.to_field((1.5, 2.0)), None);
+	}
+}
@@ -0,0 +1,220 @@
+//! Multi-agent path planning on the grid
+//!
+//! Several robots move at the same time in the same area. In each time step, every agent moves to one of its
+//! 4 neighbours or waits on its field. After reaching its end, an agent stays there. Two agents collide in a
+//!
+//! * **Vertex conflict:** Both are on the same field at the same time.
+//! * **Edge conflict:** Both swap their fields in the same time step.
+//!
+//! A single agent is planned with the space-time A* of [`space_time`], which avoids the fields and moves of a
+//! reservation table. The cost of a path is its arrival time.
+//!
+//! * **Prioritized planning:** The agents are planned one after the other, the path of each one is reserved for the following ones.
+//!   It is fast but neither optimal nor complete: an agent with a lower priority may find no path at all.
+//! * **Conflict-Based Search (CBS):** All agents are planned alone. On the first conflict of the cheapest solution, two
+//!   new solutions are created, each one forbids the conflict for one of both agents and plans it again.
+//!   The first solution without a conflict has the lowest sum of costs.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::Instant;
+use crate::space_time::{self, ReservationTable};
+
+/// Start and end position (row, col) of an agent
+pub type Agent = ((usize, usize), (usize, usize));
+
+/// A collision of two agents
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conflict {
+	/// Both agents are on the same field at the same time
+	Vertex { agents: (usize, usize), field: (usize, usize), time: usize },
+	/// Both agents swap their fields, `fields` is the move of the first agent arriving at `time`
+	Edge { agents: (usize, usize), fields: ((usize, usize), (usize, usize)), time: usize },
+}
+
+/// A solution in the constraint tree of CBS
+#[derive(Debug, Clone)]
+struct Solution {
+	constraints: Vec<ReservationTable>,
+	paths: Vec<Vec<(usize, usize)>>,
+}
+
+/// Plans the agents one after the other, each one avoids the paths of all agents before
+///
+/// # Arguments
+///
+/// * `area` - The play field as a one-dimensional vector
+/// * `rows` - number of rows
+/// * `cols` - number of cols
+/// * `agents` - The start and end positions of all agents, ordered by their priority
+///
+/// # Result
+///
+/// The path of each agent from its start to its end, one field per time step, or no path if any agent has none
+pub fn prioritized(area: &[u64], rows: &usize, cols: &usize, agents: &[Agent]) -> Vec<Vec<(usize, usize)>> {
+	let benchmark = Instant::now();
+	let mut reservations = ReservationTable::new();
+	let mut paths = vec![];
+
+	for (start, end) in agents {
+		let distances = space_time::get_distances(area, rows, cols, *end);
+		let path = space_time::a_star(area, rows, cols, *start, *end, &reservations, &distances);
+		if path.is_empty() {
+			println!("Prioritized Calc: No conneciton found");
+			return vec![];
+		}
+		reservations.reserve_path(&path);
+		paths.push(path);
+	}
+
+	println!("Prioritized Calc: {:.6?}", benchmark.elapsed());
+	println!("Prioritized Sum of costs: {}", get_cost(&paths));
+	paths
+}
+
+/// Plans all agents optimal with the Conflict-Based Search
+///
+/// # Arguments
+///
+/// * `area` - The play field as a one-dimensional vector
+/// * `rows` - number of rows
+/// * `cols` - number of cols
+/// * `agents` - The start and end positions of all agents
+/// * `max_nodes` - Maximum number of solutions to expand in the constraint tree
+///
+/// # Result
+///
+/// The path of each agent from its start to its end with the lowest sum of costs, or no path if there is no solution
+pub fn cbs(area: &[u64], rows: &usize, cols: &usize, agents: &[Agent], max_nodes: usize) -> Vec<Vec<(usize, usize)>> {
+	let benchmark = Instant::now();
+	let distances: Vec<Vec<usize>> = agents.iter().map(|(_, end)| space_time::get_distances(area, rows, cols, *end)).collect();
+
+	// The root plans each agent alone
+	let root = Solution {
+		constraints: vec![ReservationTable::new(); agents.len()],
+		paths: agents.iter().zip(&distances)
+			.map(|((start, end), distances)| space_time::a_star(area, rows, cols, *start, *end, &ReservationTable::new(), distances))
+			.collect(),
+	};
+	if root.paths.iter().any(|path| path.is_empty()) {
+		println!("CBS Calc: No conneciton found");
+		return vec![];
+	}
+
+	// Open list of the solutions, ordered by the lowest sum of costs first
+	let mut open: BinaryHeap<Reverse<(usize, usize)>> = BinaryHeap::new();
+	open.push(Reverse((get_cost(&root.paths), 0)));
+	let mut solutions = vec![root];
+	let mut expanded = 0;
+
+	while let Some(Reverse((_, key))) = open.pop() {
+		let conflict = match find_conflict(&solutions[key].paths) {
+			Some(conflict) => conflict,
+			None => {
+				println!("CBS Calc: {:.6?}", benchmark.elapsed());
+				println!("CBS Nodes: {}", expanded);
+				println!("CBS Sum of costs: {}", get_cost(&solutions[key].paths));
+				return solutions.swap_remove(key).paths;
+			},
+		};
+		expanded += 1;
+		if expanded > max_nodes {
+			break;
+		}
+
+		// Forbid the conflict for each of both agents
+		let (Conflict::Vertex { agents: (a, b), .. } | Conflict::Edge { agents: (a, b), .. }) = conflict;
+		for agent in [a, b] {
+			let mut solution = solutions[key].clone();
+			match conflict {
+				Conflict::Vertex { field, time, .. } => solution.constraints[agent].reserve_field(field, time),
+				Conflict::Edge { fields: (from, to), time, .. } if agent == a => solution.constraints[agent].reserve_move(from, to, time),
+				Conflict::Edge { fields: (from, to), time, .. } => solution.constraints[agent].reserve_move(to, from, time),
+			}
+			let (start, end) = agents[agent];
+			solution.paths[agent] = space_time::a_star(area, rows, cols, start, end, &solution.constraints[agent], &distances[agent]);
+			if !solution.paths[agent].is_empty() {
+				open.push(Reverse((get_cost(&solution.paths), solutions.len())));
+				solutions.push(solution);
+			}
+		}
+	}
+
+	println!("CBS Calc: No conneciton found");
+	println!("CBS Calc: {:.6?}", benchmark.elapsed());
+	println!("CBS Nodes: {}", expanded);
+	vec![]
+}
+
+/// Searches the first conflict of the paths, each agent stays on its end after its path
+///
+/// # Arguments
+///
+/// * `paths` - The path of each agent, one field per time step
+///
+/// # Result
+///
+/// The earliest vertex or edge conflict between two agents
+pub fn find_conflict(paths: &[Vec<(usize, usize)>]) -> Option<Conflict> {
+	let position = |path: &Vec<(usize, usize)>, time: usize| path[usize::min(time, path.len() - 1)];
+	let horizon = paths.iter().map(|path| path.len()).max().unwrap_or(0);
+
+	for time in 0..horizon {
+		for a in 0..paths.len() {
+			for b in a + 1..paths.len() {
+				let (field_a, field_b) = (position(&paths[a], time), position(&paths[b], time));
+				if field_a == field_b {
+					return Some(Conflict::Vertex { agents: (a, b), field: field_a, time });
+				}
+				if time > 0 {
+					let (from_a, from_b) = (position(&paths[a], time - 1), position(&paths[b], time - 1));
+					if from_a == field_b && from_b == field_a {
+						return Some(Conflict::Edge { agents: (a, b), fields: (from_a, field_a), time });
+					}
+				}
+			}
+		}
+	}
+	None
+}
+
+/// Returns the sum of the arrival times of all agents
+pub fn get_cost(paths: &[Vec<(usize, usize)>]) -> usize {
+	paths.iter().map(|path| path.len().saturating_sub(1)).sum()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Two agents swap their positions in a corridor with a single niche
+	#[test]
+	fn agents_pass_each_other_in_a_corridor() {
+		let (rows, cols) = (2, 5);
+		let mut area = vec![u64::MAX; rows * cols];
+		for col in 0..cols {
+			area[(col * rows) + 1] = 0;
+		}
+		area[2 * rows] = 0;
+		let agents = [((1, 0), (1, 4)), ((1, 4), (1, 0))];
+
+		// Alone, both agents would swap in the corridor
+		let alone: Vec<Vec<(usize, usize)>> = agents.iter()
+			.map(|agent| cbs(&area, &rows, &cols, std::slice::from_ref(agent), 10).remove(0))
+			.collect();
+		assert!(find_conflict(&alone).is_some());
+
+		let paths = cbs(&area, &rows, &cols, &agents, 100);
+		assert_eq!(paths.len(), 2);
+		assert_eq!(find_conflict(&paths), None);
+		for ((start, end), path) in agents.iter().zip(&paths) {
+			assert_eq!((path.first(), path.last()), (Some(start), Some(end)));
+		}
+		// One agent dodges into the niche with 2 more steps, the other one waits a step in front of it
+		assert_eq!(get_cost(&paths), 11);
+
+		// The agent with the higher priority goes straight through the corridor, the other one can not dodge in time
+		assert!(prioritized(&area, &rows, &cols, &agents).is_empty());
+	}
+}
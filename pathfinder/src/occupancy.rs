@@ -0,0 +1,416 @@
+//! Bit-packed occupancy grid for large areas
+//!
+//! The area needs 8 bytes per field only to mark the obstacles. The occupancy grid stores one bit per field with the
+//! same layout as the area: all rows of a column follow each other, each column starts on a new word.
+//! A run of rows in a column is checked with a few word operations instead of field by field,
+//! the line-of-sight test checks the runs of the Bresenham line.
+//!
+//! Search data like costs and parents is kept out of the grid in a [`Scratch`], which allocates its square tiles only
+//! when a field in them is written, so a search only pays for the regions it visits.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::f64::consts::SQRT_2;
+use std::mem::size_of;
+use std::ops::{Range, RangeInclusive};
+
+use crate::Instant;
+
+const WORD_BITS: usize = 64;
+const TILE_SIZE: usize = 64; // Rows and columns of a tile of the scratch data
+
+/// One bit per field, set on obstacles
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OccupancyGrid {
+	rows: usize,
+	cols: usize,
+	/// Number of words of each column
+	stride: usize,
+	words: Vec<u64>,
+}
+
+impl OccupancyGrid {
+	/// Creates a grid without obstacles
+	pub fn new(rows: usize, cols: usize) -> Self {
+		let stride = rows.div_ceil(WORD_BITS);
+		OccupancyGrid {
+			rows,
+			cols,
+			stride,
+			words: vec![0; stride * cols],
+		}
+	}
+
+	/// Creates the grid from an area, the fields with `u64::MAX` are the obstacles
+	pub fn from_area(area: &[u64], rows: &usize, cols: &usize) -> Self {
+		let mut grid = OccupancyGrid::new(*rows, *cols);
+		for col in 0..*cols {
+			for (row, value) in area[col * rows..(col + 1) * rows].iter().enumerate() {
+				if *value == u64::MAX {
+					grid.set(row, col, true);
+				}
+			}
+		}
+		grid
+	}
+
+	/// Number of rows
+	pub fn rows(&self) -> usize {
+		self.rows
+	}
+
+	/// Number of columns
+	pub fn cols(&self) -> usize {
+		self.cols
+	}
+
+	/// Memory of the bits in bytes
+	pub fn size_in_bytes(&self) -> usize {
+		self.words.len() * size_of::<u64>()
+	}
+
+	/// Marks the field as an obstacle or as free
+	pub fn set(&mut self, row: usize, col: usize, occupied: bool) {
+		let (word, bit) = self.get_position(row, col);
+		if occupied {
+			self.words[word] |= 1 << bit;
+		} else {
+			self.words[word] &= !(1 << bit);
+		}
+	}
+
+	/// Marks all fields of the rectangle as obstacles, the rows of each column word by word
+	pub fn fill(&mut self, rows: Range<usize>, cols: Range<usize>) {
+		let rows = rows.start..usize::min(rows.end, self.rows);
+		for col in cols.start..usize::min(cols.end, self.cols) {
+			for (word, mask) in self.get_masks(col, rows.clone()) {
+				self.words[word] |= mask;
+			}
+		}
+	}
+
+	/// Checks if the field is free, fields outside of the grid are not
+	pub fn is_free(&self, row: usize, col: usize) -> bool {
+		if row >= self.rows || col >= self.cols {
+			return false;
+		}
+		let (word, bit) = self.get_position(row, col);
+		self.words[word] & (1 << bit) == 0
+	}
+
+	/// Checks if all rows of the range in the column are free
+	pub fn is_run_free(&self, col: usize, rows: RangeInclusive<usize>) -> bool {
+		if col >= self.cols || *rows.end() >= self.rows {
+			return false;
+		}
+		self.get_masks(col, *rows.start()..*rows.end() + 1).all(|(word, mask)| self.words[word] & mask == 0)
+	}
+
+	/// Returns the first obstacle in the column from the row on downwards
+	pub fn next_obstacle(&self, col: usize, row: usize) -> Option<usize> {
+		if col >= self.cols {
+			return None;
+		}
+		self.get_masks(col, row..self.rows)
+			.find(|(word, mask)| self.words[*word] & mask != 0)
+			.map(|(word, mask)| ((word - (col * self.stride)) * WORD_BITS) + (self.words[word] & mask).trailing_zeros() as usize)
+	}
+
+	/// Returns the number of obstacles
+	pub fn count_obstacles(&self) -> usize {
+		self.words.iter().map(|word| word.count_ones() as usize).sum()
+	}
+
+	/// Checks if the direct line between both fields is free, with the same fields as `tree::is_line_free`
+	///
+	/// The fields of the Bresenham line in one column follow each other, each run is checked word by word.
+	pub fn is_line_free(&self, p1: (usize, usize), p2: (usize, usize)) -> bool {
+		let dx = p1.0.abs_diff(p2.0) as i64;
+		let dy = p1.1.abs_diff(p2.1) as i64;
+		let sx = if p1.0 < p2.0 { 1 } else { -1 };
+		let sy = if p1.1 < p2.1 { 1 } else { -1 };
+		let mut err = if dx > dy { dx } else { -dy } / 2;
+
+		let (mut x, mut y) = (p1.0 as i64, p1.1 as i64);
+		let mut run_start = x;
+		loop {
+			if x == p2.0 as i64 && y == p2.1 as i64 {
+				return self.is_run_free(y as usize, i64::min(run_start, x) as usize..=i64::max(run_start, x) as usize);
+			}
+
+			let err2 = 2 * err;
+			let (mut next_x, mut next_y) = (x, y);
+			if err2 > -dx {
+				err -= dy;
+				next_x += sx;
+			}
+			if err2 < dy {
+				err += dx;
+				next_y += sy;
+			}
+
+			// The run in this column ends with a step to the next column
+			if next_y != y {
+				if !self.is_run_free(y as usize, i64::min(run_start, x) as usize..=i64::max(run_start, x) as usize) {
+					return false;
+				}
+				run_start = next_x;
+			}
+			(x, y) = (next_x, next_y);
+		}
+	}
+
+	/// Returns the word and the bit of the field
+	fn get_position(&self, row: usize, col: usize) -> (usize, usize) {
+		((col * self.stride) + (row / WORD_BITS), row % WORD_BITS)
+	}
+
+	/// Returns the words with the masks of the bits of the rows in the column
+	fn get_masks(&self, col: usize, rows: Range<usize>) -> impl Iterator<Item = (usize, u64)> {
+		let first = col * self.stride;
+		let words = if rows.is_empty() { 0..0 } else { (rows.start / WORD_BITS)..(rows.end - 1) / WORD_BITS + 1 };
+		words.map(move |word| {
+			let low = usize::max(rows.start, word * WORD_BITS) - (word * WORD_BITS);
+			let high = usize::min(rows.end, (word + 1) * WORD_BITS) - (word * WORD_BITS);
+			let mask = if high - low == WORD_BITS { u64::MAX } else { ((1u64 << (high - low)) - 1) << low };
+			(first + word, mask)
+		})
+	}
+}
+
+/// Values for all fields of a grid in square tiles, a tile is allocated on the first write into it
+#[derive(Debug, Clone)]
+pub struct Scratch<T: Copy> {
+	rows: usize,
+	/// Number of tiles in each column of tiles
+	stride: usize,
+	default: T,
+	tiles: Vec<Option<Box<[T]>>>,
+}
+
+impl<T: Copy> Scratch<T> {
+	/// Creates the scratch data for a grid, all fields with the default value
+	pub fn new(rows: usize, cols: usize, default: T) -> Self {
+		let stride = rows.div_ceil(TILE_SIZE);
+		Scratch {
+			rows,
+			stride,
+			default,
+			tiles: vec![None; stride * cols.div_ceil(TILE_SIZE)],
+		}
+	}
+
+	/// Returns the value of the field with the index in the area
+	pub fn get(&self, index: usize) -> T {
+		let (tile, offset) = self.get_position(index);
+		match &self.tiles[tile] {
+			Some(tile) => tile[offset],
+			None => self.default,
+		}
+	}
+
+	/// Sets the value of the field with the index in the area and allocates its tile if needed
+	pub fn set(&mut self, index: usize, value: T) {
+		let (tile, offset) = self.get_position(index);
+		let default = self.default;
+		let tile = self.tiles[tile].get_or_insert_with(|| vec![default; TILE_SIZE * TILE_SIZE].into_boxed_slice());
+		tile[offset] = value;
+	}
+
+	/// Memory of the allocated tiles and the tile table in bytes
+	pub fn size_in_bytes(&self) -> usize {
+		let tiles = self.tiles.iter().filter(|tile| tile.is_some()).count();
+		(tiles * TILE_SIZE * TILE_SIZE * size_of::<T>()) + (self.tiles.len() * size_of::<Option<Box<[T]>>>())
+	}
+
+	/// Returns the tile and the offset in it of the field with the index in the area
+	fn get_position(&self, index: usize) -> (usize, usize) {
+		let (row, col) = (index % self.rows, index / self.rows);
+		(((col / TILE_SIZE) * self.stride) + (row / TILE_SIZE), ((col % TILE_SIZE) * TILE_SIZE) + (row % TILE_SIZE))
+	}
+}
+
+/// Entry of the open list, ordered by the lowest estimation first
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Open {
+	estimate: f64,
+	index: usize,
+}
+
+impl Eq for Open {}
+
+impl Ord for Open {
+	fn cmp(&self, other: &Self) -> Ordering {
+		other.estimate.total_cmp(&self.estimate)
+	}
+}
+
+impl PartialOrd for Open {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+/// Use the A* algorithm on the occupancy grid with the 8-Neighborhood
+///
+/// The costs and parents are kept in lazily allocated scratch data, the grid itself is only read.
+///
+/// # Arguments
+///
+/// * `grid` - The occupancy grid
+/// * `start` - start position (row, col)
+/// * `end` - end position (row, col)
+///
+/// # Result
+///
+/// A Vector of tuples where each tuple represents a waypoint, from the end to the start
+pub fn a_star(grid: &OccupancyGrid, start: (usize, usize), end: (usize, usize)) -> Vec<(usize, usize)> {
+	let benchmark = Instant::now();
+	let rows = grid.rows;
+	// Octile distance, the exact distance without obstacles
+	let heuristic = |index: usize| {
+		let (dx, dy) = ((index % rows).abs_diff(end.0) as f64, (index / rows).abs_diff(end.1) as f64);
+		f64::max(dx, dy) + ((SQRT_2 - 1.0) * f64::min(dx, dy))
+	};
+
+	let mut costs: Scratch<f64> = Scratch::new(rows, grid.cols, f64::INFINITY);
+	let mut parents: Scratch<usize> = Scratch::new(rows, grid.cols, usize::MAX);
+	let mut open: BinaryHeap<Open> = BinaryHeap::new();
+	let (start_index, end_index) = ((start.1 * rows) + start.0, (end.1 * rows) + end.0);
+	if grid.is_free(start.0, start.1) && grid.is_free(end.0, end.1) {
+		costs.set(start_index, 0.0);
+		open.push(Open { estimate: heuristic(start_index), index: start_index });
+	}
+
+	while let Some(Open { estimate, index }) = open.pop() {
+		if index == end_index {
+			break;
+		}
+		let cost = costs.get(index);
+		if estimate > cost + heuristic(index) {
+			continue;
+		}
+		let (row, col) = (index % rows, index / rows);
+		for (dx, dy) in [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)] {
+			let (x, y) = (row as i64 + dx, col as i64 + dy);
+			if x < 0 || y < 0 || !grid.is_free(x as usize, y as usize) {
+				continue;
+			}
+			let next = (y as usize * rows) + x as usize;
+			let next_cost = cost + if dx != 0 && dy != 0 { SQRT_2 } else { 1.0 };
+			if next_cost < costs.get(next) {
+				costs.set(next, next_cost);
+				parents.set(next, index);
+				open.push(Open { estimate: next_cost + heuristic(next), index: next });
+			}
+		}
+	}
+
+	if costs.get(end_index).is_infinite() { println!("Occupancy-A* Calc: No conneciton found"); }
+	println!("Occupancy-A* Calc: {:.6?}", benchmark.elapsed());
+
+	let path_benchmark = Instant::now();
+	let mut path = vec![];
+	if costs.get(end_index).is_finite() {
+		let mut current = end_index;
+		path.push(end);
+		while current != start_index {
+			current = parents.get(current);
+			path.push((current % rows, current / rows));
+		}
+	}
+	println!("Occupancy-A* Path-Calculation: {:.6?}", path_benchmark.elapsed());
+	println!("Occupancy-A* Path length: {}", path.len());
+	println!("Occupancy-A* Scratch: {} bytes", costs.size_in_bytes() + parents.size_in_bytes());
+	path
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tree::is_line_free;
+	use crate::{thread_rng, Rng};
+
+	/// The word-wise checks find the same obstacles as the checks on the area field by field
+	#[test]
+	fn grid_matches_the_area() {
+		let (rows, cols) = (150, 70);
+		let mut rng = thread_rng();
+		let area: Vec<u64> = (0..rows * cols).map(|_| if rng.gen_bool(0.02) { u64::MAX } else { 0 }).collect();
+		let grid = OccupancyGrid::from_area(&area, &rows, &cols);
+		assert_eq!(grid.count_obstacles(), area.iter().filter(|value| **value == u64::MAX).count());
+
+		for _ in 0..2000 {
+			let p1 = (rng.gen_range(0..rows), rng.gen_range(0..cols));
+			let p2 = (rng.gen_range(0..rows), rng.gen_range(0..cols));
+			assert_eq!(grid.is_line_free(p1, p2), is_line_free(&area, &rows, p1, p2), "Line from {:?} to {:?}", p1, p2);
+
+			let (col, first) = (p1.1, usize::min(p1.0, p2.0));
+			let last = usize::max(p1.0, p2.0);
+			let expected = (first..=last).all(|row| area[(col * rows) + row] != u64::MAX);
+			assert_eq!(grid.is_run_free(col, first..=last), expected);
+			assert_eq!(grid.next_obstacle(col, first), (first..rows).find(|row| area[(col * rows) + row] == u64::MAX));
+		}
+	}
+
+	/// The A* on the grid is optimal, the A* on the area with the Manhattan heuristic can only be longer
+	#[test]
+	fn a_star_is_optimal() {
+		let (rows, cols) = (120, 90);
+		let mut rng = thread_rng();
+		let length = |path: &[(usize, usize)]| -> f64 {
+			path.windows(2).map(|pair| f64::hypot(pair[1].0 as f64 - pair[0].0 as f64, pair[1].1 as f64 - pair[0].1 as f64)).sum()
+		};
+
+		for _ in 0..20 {
+			let mut area = vec![0; rows * cols];
+			for _ in 0..30 {
+				let (row, col) = (rng.gen_range(0..rows - 10), rng.gen_range(0..cols - 10));
+				for field in (col..col + rng.gen_range(1..10)).flat_map(|col| (row..row + rng.gen_range(1..10)).map(move |row| (col * rows) + row)) {
+					area[field] = u64::MAX;
+				}
+			}
+			let (start, end) = ((rng.gen_range(0..rows), rng.gen_range(0..cols)), (rng.gen_range(0..rows), rng.gen_range(0..cols)));
+			if area[(start.1 * rows) + start.0] == u64::MAX || area[(end.1 * rows) + end.0] == u64::MAX || start == end {
+				continue;
+			}
+
+			let path = a_star(&OccupancyGrid::from_area(&area, &rows, &cols), start, end);
+			let reference = crate::a_star::calculate(&mut area.clone(), &rows, &cols, start, end);
+			match crate::benchmark::get_optimum(&area, &rows, &cols, start, end) {
+				Some(optimum) => {
+					assert_eq!((path.first(), path.last()), (Some(&end), Some(&start)));
+					assert!(path.iter().all(|(row, col)| area[(col * rows) + row] != u64::MAX));
+					assert!((length(&path) - optimum).abs() < 1e-6, "{} != {}", length(&path), optimum);
+					assert_eq!((reference.first(), reference.last()), (Some(&end), Some(&start)));
+					assert!(length(&path) <= length(&reference) + 1e-6, "{} > {}", length(&path), length(&reference));
+				},
+				None => {
+					assert!(path.is_empty());
+					assert_ne!(reference.last(), Some(&start));
+				},
+			}
+		}
+	}
+
+	/// Only the written tiles are allocated, the other fields return the default
+	#[test]
+	fn scratch_allocates_the_touched_tiles() {
+		let (rows, cols) = (1000, 1000);
+		let mut scratch: Scratch<f64> = Scratch::new(rows, cols, f64::INFINITY);
+		let table = scratch.size_in_bytes();
+		assert_eq!(table, rows.div_ceil(TILE_SIZE) * cols.div_ceil(TILE_SIZE) * size_of::<Option<Box<[f64]>>>());
+
+		// Two fields in the first tile and one in the last one
+		for (row, col, value) in [(0, 0, 1.0), (TILE_SIZE - 1, TILE_SIZE - 1, 2.0), (rows - 1, cols - 1, 3.0)] {
+			scratch.set((col * rows) + row, value);
+		}
+		assert_eq!(scratch.size_in_bytes(), table + (2 * TILE_SIZE * TILE_SIZE * size_of::<f64>()));
+		assert!(scratch.size_in_bytes() * 50 < rows * cols * size_of::<f64>());
+		assert_eq!(scratch.get(0), 1.0);
+		assert_eq!(scratch.get(((TILE_SIZE - 1) * rows) + TILE_SIZE - 1), 2.0);
+		assert_eq!(scratch.get((rows * cols) - 1), 3.0);
+		assert_eq!(scratch.get(1), f64::INFINITY);
+		assert_eq!(scratch.get(500 * rows), f64::INFINITY);
+	}
+}
@@ -0,0 +1,287 @@
+//! Artificial potential field as a reactive planner on the grid
+//!
+//! Every field gets a potential: the end attracts and the obstacles repel. The path follows the steepest descent
+//! over the 8 neighbours from the start until the end is reached.
+//!
+//! * **Potential field:** The attractive potential is the direct distance to the end, the repulsive potential
+//!   `0.5 * gain * (1 / d - 1 / influence)²` for obstacles nearer than the influence.
+//!   Between obstacles the descent may get stuck in a local minimum, it is left with a random walk which gets longer on each escape.
+//! * **Navigation function:** The attractive potential is the grassfire distance to the end around the obstacles,
+//!   where each step costs more the nearer it is to an obstacle. Every field except the end has a neighbour with a lower potential,
+//!   so there are no local minima.
+//!
+//! The potential is written into the area like the grassfire distances, so `export_image` shows the field.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::f64::consts::SQRT_2;
+
+use crate::{Instant, thread_rng, Rng};
+
+/// Configuration of the potential field
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PotentialFieldConfig {
+	/// Gain of the attractive potential
+	pub attractive_gain: f64,
+	/// Gain of the repulsive potential
+	pub repulsive_gain: f64,
+	/// Distance in fields up to which the obstacles repel
+	pub influence: f64,
+	/// Number of random steps to escape a local minimum, doubled on each further escape
+	pub escape_steps: usize,
+	/// Maximum number of escapes before the calculation is stopped
+	pub max_escapes: usize,
+}
+
+impl Default for PotentialFieldConfig {
+	fn default() -> Self {
+		PotentialFieldConfig {
+			attractive_gain: 1.0,
+			repulsive_gain: 100.0,
+			influence: 5.0,
+			escape_steps: 10,
+			max_escapes: 10,
+		}
+	}
+}
+
+/// Entry of the open list, ordered by the lowest distance first
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Open {
+	distance: f64,
+	index: usize,
+}
+
+impl Eq for Open {}
+
+impl Ord for Open {
+	fn cmp(&self, other: &Self) -> Ordering {
+		other.distance.total_cmp(&self.distance)
+	}
+}
+
+impl PartialOrd for Open {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+/// Use the artificial potential field to calculate a path from the start to the end
+///
+/// # Arguments
+///
+/// * `area` - The play field as a one-dimensional vector, the potential is written into the free fields
+/// * `rows` - number of rows
+/// * `cols` - number of cols
+/// * `start` - start position (row, col)
+/// * `end` - end position (row, col)
+/// * `config` - Gains of the potentials and the escape from local minima
+///
+/// # Result
+///
+/// A Vector of tuples where each tuple represents a waypoint, from the start to the end
+pub fn calculate(area: &mut [u64], rows: &usize, cols: &usize, start: (usize, usize), end: (usize, usize), config: &PotentialFieldConfig) -> Vec<(usize, usize)> {
+	let benchmark = Instant::now();
+
+	let obstacle_distances = get_obstacle_distances(area, rows, cols);
+	let potential: Vec<f64> = (0..area.len()).map(|index| {
+		let (row, col) = (index % rows, index / rows);
+		let attractive = config.attractive_gain * f64::hypot(row as f64 - end.0 as f64, col as f64 - end.1 as f64);
+		attractive + get_repulsive(obstacle_distances[index], config)
+	}).collect();
+	write_potential(area, &potential);
+	println!("Potential-Field Calc: {:.6?}", benchmark.elapsed());
+
+	descend("Potential-Field", &potential, rows, cols, start, end, config)
+}
+
+/// Use the navigation function, the potential field without local minima, to calculate a path from the start to the end
+///
+/// # Arguments
+///
+/// * `area` - The play field as a one-dimensional vector, the potential is written into the free fields
+/// * `rows` - number of rows
+/// * `cols` - number of cols
+/// * `start` - start position (row, col)
+/// * `end` - end position (row, col)
+/// * `config` - Gains of the potentials, the escape is never needed
+///
+/// # Result
+///
+/// A Vector of tuples where each tuple represents a waypoint, from the start to the end
+pub fn navigation_function(area: &mut [u64], rows: &usize, cols: &usize, start: (usize, usize), end: (usize, usize), config: &PotentialFieldConfig) -> Vec<(usize, usize)> {
+	let benchmark = Instant::now();
+
+	// Grassfire from the end with the 8-Neighborhood, each step costs more near obstacles
+	let obstacle_distances = get_obstacle_distances(area, rows, cols);
+	let mut potential = vec![f64::INFINITY; area.len()];
+	let mut queue: BinaryHeap<Open> = BinaryHeap::new();
+	let end_index = (end.1 * rows) + end.0;
+	potential[end_index] = 0.0;
+	queue.push(Open { distance: 0.0, index: end_index });
+
+	while let Some(Open { distance, index }) = queue.pop() {
+		if distance > potential[index] {
+			continue;
+		}
+		for (neighbour, length) in get_neighbours((index % rows, index / rows), rows, cols) {
+			let next = (neighbour.1 * rows) + neighbour.0;
+			if area[next] == u64::MAX {
+				continue;
+			}
+			let cost = length * (config.attractive_gain + get_repulsive(obstacle_distances[next], config));
+			if distance + cost < potential[next] {
+				potential[next] = distance + cost;
+				queue.push(Open { distance: distance + cost, index: next });
+			}
+		}
+	}
+	write_potential(area, &potential);
+	println!("Navigation-Function Calc: {:.6?}", benchmark.elapsed());
+
+	descend("Navigation-Function", &potential, rows, cols, start, end, config)
+}
+
+/// Follows the steepest descent of the potential from the start to the end
+///
+/// # Arguments
+///
+/// * `algorithm` - The name of the Algorithm (for benchmark logging)
+/// * `potential` - The potential of each field, obstacles and unreachable fields are infinite
+/// * `rows` - number of rows
+/// * `cols` - number of cols
+/// * `start` - start position (row, col)
+/// * `end` - end position (row, col)
+/// * `config` - The escape from local minima
+///
+/// # Result
+///
+/// The path from the start to the end or an empty one if the end was not reached
+fn descend(algorithm: &str, potential: &[f64], rows: &usize, cols: &usize, start: (usize, usize), end: (usize, usize), config: &PotentialFieldConfig) -> Vec<(usize, usize)> {
+	let benchmark = Instant::now();
+	let mut rng = thread_rng();
+	let mut path = vec![start];
+	let mut current = start;
+	let mut escapes = 0;
+
+	while current != end && path.len() < potential.len() && potential[(current.1 * rows) + current.0].is_finite() {
+		let value = potential[(current.1 * rows) + current.0];
+		let lowest = get_neighbours(current, rows, cols)
+			.map(|(field, _)| (field, potential[(field.1 * rows) + field.0]))
+			.min_by(|a, b| a.1.total_cmp(&b.1));
+		match lowest {
+			Some((field, lowest)) if lowest < value => {
+				current = field;
+				path.push(current);
+			},
+			_ => {
+				// Local minimum: Walk randomly over free fields to leave it
+				if escapes >= config.max_escapes {
+					break;
+				}
+				for _ in 0..(config.escape_steps << escapes) {
+					let free: Vec<(usize, usize)> = get_neighbours(current, rows, cols)
+						.map(|(field, _)| field)
+						.filter(|field| potential[(field.1 * rows) + field.0].is_finite())
+						.collect();
+					if free.is_empty() {
+						break;
+					}
+					current = free[rng.gen_range(0..free.len())];
+					path.push(current);
+				}
+				escapes += 1;
+			},
+		}
+	}
+
+	if current != end {
+		println!("{} Calc: No conneciton found", algorithm);
+		path.clear();
+	}
+	println!("{} Path-Calculation: {:.6?}", algorithm, benchmark.elapsed());
+	println!("{} Path length: {}", algorithm, path.len());
+	println!("{} Local minima: {}", algorithm, escapes);
+	path
+}
+
+/// Returns the repulsive potential of a field with the given distance to the nearest obstacle
+fn get_repulsive(distance: f64, config: &PotentialFieldConfig) -> f64 {
+	if distance <= 0.0 {
+		f64::INFINITY
+	} else if distance < config.influence {
+		0.5 * config.repulsive_gain * ((1.0 / distance) - (1.0 / config.influence)).powi(2)
+	} else {
+		0.0
+	}
+}
+
+/// Calculates the distance of every field to the nearest obstacle with the 8-Neighborhood, `0` on the obstacles
+fn get_obstacle_distances(area: &[u64], rows: &usize, cols: &usize) -> Vec<f64> {
+	let mut distances = vec![f64::INFINITY; area.len()];
+	let mut queue: BinaryHeap<Open> = BinaryHeap::new();
+	for (index, value) in area.iter().enumerate() {
+		if *value == u64::MAX {
+			distances[index] = 0.0;
+			queue.push(Open { distance: 0.0, index });
+		}
+	}
+
+	while let Some(Open { distance, index }) = queue.pop() {
+		if distance > distances[index] {
+			continue;
+		}
+		for (neighbour, length) in get_neighbours((index % rows, index / rows), rows, cols) {
+			let next = (neighbour.1 * rows) + neighbour.0;
+			if distance + length < distances[next] {
+				distances[next] = distance + length;
+				queue.push(Open { distance: distance + length, index: next });
+			}
+		}
+	}
+	distances
+}
+
+/// Returns all neighbours of the field in the 8-Neighborhood with the distance to them
+fn get_neighbours(field: (usize, usize), rows: &usize, cols: &usize) -> impl Iterator<Item = ((usize, usize), f64)> {
+	let (rows, cols) = (*rows as i64, *cols as i64);
+	[(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)].into_iter()
+		.map(move |(dx, dy): (i64, i64)| (field.0 as i64 + dx, field.1 as i64 + dy, if dx != 0 && dy != 0 { SQRT_2 } else { 1.0 }))
+		.filter(move |(x, y, _)| (0..rows).contains(x) && (0..cols).contains(y))
+		.map(|(x, y, length)| ((x as usize, y as usize), length))
+}
+
+/// Writes the potential into the free fields of the area, scaled to one tenth, unreachable fields stay `0`
+fn write_potential(area: &mut [u64], potential: &[f64]) {
+	for (value, potential) in area.iter_mut().zip(potential) {
+		if *value != u64::MAX && potential.is_finite() {
+			*value = (potential * 10.0) as u64 + 1;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// In front of a U-shaped obstacle the navigation function reaches the end without any local minimum
+	#[test]
+	fn navigation_function_has_no_local_minima() {
+		let (rows, cols) = (60, 60);
+		let mut area = vec![0; rows * cols];
+		for i in 15..45 {
+			area[(15 * rows) + i] = u64::MAX;
+			area[(45 * rows) + i] = u64::MAX;
+			area[(i * rows) + 45] = u64::MAX;
+		}
+		let config = PotentialFieldConfig { max_escapes: 0, ..PotentialFieldConfig::default() };
+
+		let path = navigation_function(&mut area.clone(), &rows, &cols, (30, 30), (55, 30), &config);
+		assert_eq!(path.first(), Some(&(30, 30)));
+		assert_eq!(path.last(), Some(&(55, 30)));
+		assert!(path.iter().all(|field| area[(field.1 * rows) + field.0] != u64::MAX));
+
+		// The classic potential field gets stuck inside of the U without an escape
+		assert!(calculate(&mut area.clone(), &rows, &cols, (30, 30), (55, 30), &config).is_empty());
+	}
+}
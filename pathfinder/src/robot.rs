@@ -0,0 +1,186 @@
+//! Model of a differential-drive robot like the EEROS robot
+//!
+//! The robot is controlled by a forward velocity `v` and an angular velocity `ω`.
+//! Both wheels are limited by the same maximum velocity, so the faster the robot drives,
+//! the slower it can turn: `|v| + |ω| * wheel_distance / 2 <= max_velocity`.
+//!
+//! All distances are in fields of the area and all times in seconds.
+
+use std::f64::consts::PI;
+
+/// Position and orientation of the robot
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Pose {
+	/// Position on the rows
+	pub x: f64,
+	/// Position on the columns
+	pub y: f64,
+	/// Orientation in radians, `0` points to increasing rows
+	pub heading: f64,
+}
+
+impl Pose {
+	/// Creates a new pose
+	pub fn new(x: f64, y: f64, heading: f64) -> Self {
+		Pose { x, y, heading }
+	}
+
+	/// Creates a pose in the middle of the given field
+	pub fn from_field(pos: (usize, usize), heading: f64) -> Self {
+		Pose { x: pos.0 as f64 + 0.5, y: pos.1 as f64 + 0.5, heading }
+	}
+
+	/// Returns the field the pose lies on or `None` if it is outside of the area
+	pub fn field(&self, rows: &usize, cols: &usize) -> Option<(usize, usize)> {
+		if self.x >= 0.0 && self.y >= 0.0 && (self.x as usize) < *rows && (self.y as usize) < *cols {
+			Some((self.x as usize, self.y as usize))
+		} else {
+			None
+		}
+	}
+
+	/// Returns the direct distance to an other pose
+	pub fn distance(&self, other: &Pose) -> f64 {
+		f64::hypot(other.x - self.x, other.y - self.y)
+	}
+
+	/// Moves the pose with the given velocities for the given time on an exact arc (unicycle model)
+	///
+	/// # Arguments:
+	///
+	/// * `velocity` - Forward velocity
+	/// * `angular_velocity` - Angular velocity, positive turns from the rows to the columns
+	/// * `time` - Time to move
+	///
+	/// # Result:
+	///
+	/// The pose after the movement
+	pub fn simulate(&self, velocity: f64, angular_velocity: f64, time: f64) -> Pose {
+		let heading = normalize_angle(self.heading + (angular_velocity * time));
+		if angular_velocity.abs() < 1e-9 {
+			Pose {
+				x: self.x + (velocity * time * self.heading.cos()),
+				y: self.y + (velocity * time * self.heading.sin()),
+				heading,
+			}
+		} else {
+			let radius = velocity / angular_velocity;
+			Pose {
+				x: self.x + (radius * (heading.sin() - self.heading.sin())),
+				y: self.y - (radius * (heading.cos() - self.heading.cos())),
+				heading,
+			}
+		}
+	}
+}
+
+/// Dimensions and limits of a differential-drive robot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiffDrive {
+	/// Distance between both wheels
+	pub wheel_distance: f64,
+	/// Radius of the wheels
+	pub wheel_radius: f64,
+	/// Maximum velocity of each wheel
+	pub max_velocity: f64,
+	/// Minimum forward velocity while driving
+	pub min_velocity: f64,
+	/// Maximum acceleration of each wheel
+	pub max_acceleration: f64,
+}
+
+impl Default for DiffDrive {
+	/// The EEROS robot with 1cm per field: Wheels 40mm and max 0.3m/s
+	fn default() -> Self {
+		DiffDrive {
+			wheel_distance: 15.0,
+			wheel_radius: 4.0,
+			max_velocity: 30.0,
+			min_velocity: 5.0,
+			max_acceleration: 30.0,
+		}
+	}
+}
+
+impl DiffDrive {
+	/// Returns the velocities of the left and right wheel for the given robot velocities
+	pub fn wheel_velocities(&self, velocity: f64, angular_velocity: f64) -> (f64, f64) {
+		let delta = angular_velocity * self.wheel_distance / 2.0;
+		(velocity - delta, velocity + delta)
+	}
+
+	/// Returns the forward and angular velocity of the robot for the given wheel velocities
+	pub fn robot_velocities(&self, left: f64, right: f64) -> (f64, f64) {
+		((left + right) / 2.0, (right - left) / self.wheel_distance)
+	}
+
+	/// Returns the maximum angular velocity possible with the given forward velocity
+	pub fn max_angular_velocity(&self, velocity: f64) -> f64 {
+		f64::max(0.0, self.max_velocity - velocity.abs()) * 2.0 / self.wheel_distance
+	}
+
+	/// If the robot is able to drive with the given velocities
+	pub fn is_admissible(&self, velocity: f64, angular_velocity: f64) -> bool {
+		let (left, right) = self.wheel_velocities(velocity, angular_velocity);
+		left.abs() <= self.max_velocity + 1e-9 && right.abs() <= self.max_velocity + 1e-9
+	}
+}
+
+/// Normalizes an angle to the range -π..π
+pub fn normalize_angle(angle: f64) -> f64 {
+	let mut angle = angle % (2.0 * PI);
+	if angle > PI {
+		angle -= 2.0 * PI;
+	} else if angle < -PI {
+		angle += 2.0 * PI;
+	}
+	angle
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// The wheel and robot velocities convert into each other, turning is only possible with the velocity left on the wheels
+	#[test]
+	fn velocities_respect_the_wheel_limits() {
+		let robot = DiffDrive::default();
+		for (velocity, angular_velocity) in [(10.0, 0.5), (-5.0, 1.2), (0.0, -2.0), (30.0, 0.0)] {
+			let (left, right) = robot.wheel_velocities(velocity, angular_velocity);
+			let (v, omega) = robot.robot_velocities(left, right);
+			assert!((v - velocity).abs() < 1e-9 && (omega - angular_velocity).abs() < 1e-9, "({}, {}) became ({}, {})", velocity, angular_velocity, v, omega);
+		}
+
+		assert_eq!(robot.max_angular_velocity(robot.max_velocity), 0.0);
+		assert_eq!(robot.max_angular_velocity(robot.max_velocity + 10.0), 0.0);
+		assert_eq!(robot.max_angular_velocity(-10.0), robot.max_angular_velocity(10.0));
+		for velocity in [0.0, 5.0, 10.0, -20.0, 30.0] {
+			let max_angular = robot.max_angular_velocity(velocity);
+			assert!(robot.is_admissible(velocity, max_angular));
+			assert!(robot.is_admissible(velocity, -max_angular));
+			assert!(!robot.is_admissible(velocity, max_angular + 0.01));
+		}
+		assert!(!robot.is_admissible(robot.max_velocity + 0.01, 0.0));
+	}
+
+	/// Straight lines and arcs end where the closed form of the unicycle model puts them
+	#[test]
+	fn simulate_matches_the_closed_form() {
+		let start = Pose::new(10.0, 20.0, 0.3);
+		let straight = start.simulate(10.0, 0.0, 2.0);
+		assert!((straight.x - (10.0 + (20.0 * f64::cos(0.3)))).abs() < 1e-9);
+		assert!((straight.y - (20.0 + (20.0 * f64::sin(0.3)))).abs() < 1e-9);
+		assert_eq!(straight.heading, 0.3);
+
+		// A quarter circle with radius 10 to the columns, every pose keeps the radius to the center
+		let start = Pose::new(0.0, 0.0, 0.0);
+		let arc = start.simulate(10.0, 1.0, PI / 2.0);
+		assert!((arc.x - 10.0).abs() < 1e-9 && (arc.y - 10.0).abs() < 1e-9, "{:?}", arc);
+		assert!((arc.heading - (PI / 2.0)).abs() < 1e-9);
+		for step in 1..10 {
+			let pose = start.simulate(10.0, 1.0, step as f64 * 0.3);
+			assert!((pose.distance(&Pose::new(0.0, 10.0, 0.0)) - 10.0).abs() < 1e-9, "{:?} leaves the circle", pose);
+			assert!((pose.heading - normalize_angle(step as f64 * 0.3)).abs() < 1e-9);
+		}
+	}
+}
@@ -0,0 +1,255 @@
+//! Sampling strategies for the random points of the Tree-Pathfinder algorithms
+//!
+//! * **Uniform:** Every field of the area has the same probability
+//! * **Goal-Bias:** With the given probability the goal itself is taken, otherwise uniform
+//! * **Gaussian:** Points near obstacles are preferred: A second point is taken normal distributed around a uniform one,
+//!   if only one of them is inside an obstacle, the free one is used.
+//! * **Bridge:** Points in narrow passages are preferred: Two normal distributed points inside obstacles,
+//!   where the point in the middle is free, form a bridge over a narrow passage.
+//! * **Halton/Sobol:** Low-discrepancy sequences which cover the area more evenly than random points.
+
+use std::str::FromStr;
+use crate::Rng;
+
+/// Maximum number of tries for the obstacle based strategies before a uniform point is taken
+const MAX_TRIES: usize = 100;
+
+/// The strategy to choose random points on the area
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Sampling {
+	/// Uniform over the whole area
+	#[default]
+	Uniform,
+	/// Take the goal with the given probability (0.0 - 1.0)
+	GoalBias(f32),
+	/// Prefer points near obstacles, the value is the standard deviation
+	Gaussian(f32),
+	/// Prefer points in narrow passages, the value is the standard deviation
+	Bridge(f32),
+	/// Halton sequence with the bases 2 and 3
+	Halton,
+	/// Sobol sequence
+	Sobol,
+}
+
+impl FromStr for Sampling {
+	type Err = String;
+
+	/// Parses a strategy like `uniform`, `goal:0.1`, `gaussian:10`, `bridge:20`, `halton` or `sobol`
+	fn from_str(value: &str) -> Result<Self, Self::Err> {
+		let (name, param) = match value.split_once(':') {
+			Some((name, param)) => (name, Some(param)),
+			None => (value, None),
+		};
+		let param = |default: f32| -> Result<f32, String> {
+			match param {
+				Some(val) => val.parse::<f32>().map_err(|_| format!("Invalid sampling parameter: {}", val)),
+				None => Ok(default),
+			}
+		};
+
+		match name.to_lowercase().as_str() {
+			"uniform" => Ok(Sampling::Uniform),
+			"goal" => Ok(Sampling::GoalBias(param(0.05)?)),
+			"gaussian" => Ok(Sampling::Gaussian(param(10.0)?)),
+			"bridge" => Ok(Sampling::Bridge(param(20.0)?)),
+			"halton" => Ok(Sampling::Halton),
+			"sobol" => Ok(Sampling::Sobol),
+			_ => Err(format!("Unknown sampling strategy: {}", value)),
+		}
+	}
+}
+
+/// Creates the random points for a tree algorithm based on a sampling strategy
+#[derive(Debug, Clone)]
+pub struct Sampler {
+	strategy: Sampling,
+	/// Index of the next point in the low-discrepancy sequences
+	index: u32,
+	/// Last point of the Sobol sequence (x, y)
+	sobol: (u32, u32),
+}
+
+impl Sampler {
+	/// Creates a new sampler for the given strategy
+	pub fn new(strategy: Sampling) -> Self {
+		Sampler {
+			strategy,
+			index: 0,
+			sobol: (0, 0),
+		}
+	}
+
+	/// Returns the next random point on the area
+	///
+	/// # Arguments:
+	///
+	/// * `rng` - The random generator to use
+	/// * `area` - The area as a vector of u64 where every obstacle is u64::MAX
+	/// * `rows` - Number of rows, where rows x cols is the size of the area
+	/// * `cols` - Number of columns, where rows x cols is the size of the area
+	/// * `goal` - The position the tree should grow to
+	///
+	/// # Result:
+	///
+	/// A point on the area as a tuple (row, col)
+	pub fn sample<R: Rng>(&mut self, rng: &mut R, area: &[u64], rows: &usize, cols: &usize, goal: (usize, usize)) -> (usize, usize) {
+		match self.strategy {
+			Sampling::Uniform => uniform(rng, rows, cols),
+			Sampling::GoalBias(probability) => {
+				if rng.gen::<f32>() < probability { goal } else { uniform(rng, rows, cols) }
+			},
+			Sampling::Gaussian(sigma) => gaussian_obstacle(rng, area, rows, cols, sigma),
+			Sampling::Bridge(sigma) => bridge(rng, area, rows, cols, sigma),
+			Sampling::Halton => {
+				self.index += 1;
+				let x = halton(self.index, 2);
+				let y = halton(self.index, 3);
+				((x * *rows as f64) as usize, (y * *cols as f64) as usize)
+			},
+			Sampling::Sobol => {
+				let (x, y) = self.next_sobol();
+				((x * *rows as f64) as usize, (y * *cols as f64) as usize)
+			},
+		}
+	}
+
+	/// Calculates the next point of the two dimensional Sobol sequence in gray code order
+	///
+	/// The first dimension uses the direction numbers `v_k = 2^(32-k)` (van der Corput),
+	/// the second one is based on the primitive polynomial `x + 1`.
+	fn next_sobol(&mut self) -> (f64, f64) {
+		// The first point of the sequence is (0, 0)
+		if self.index > 0 {
+			// Index of the rightmost zero bit of the previous index
+			let bit = (!(self.index - 1)).trailing_zeros();
+			let direction_x = 1u32 << (31 - bit);
+			let mut direction_y = 1u32 << 31;
+			for _ in 0..bit {
+				direction_y ^= direction_y >> 1;
+			}
+			self.sobol = (self.sobol.0 ^ direction_x, self.sobol.1 ^ direction_y);
+		}
+		self.index = self.index.wrapping_add(1);
+
+		let scale = (1u64 << 32) as f64;
+		(self.sobol.0 as f64 / scale, self.sobol.1 as f64 / scale)
+	}
+}
+
+/// Returns a uniform distributed point on the area
+fn uniform<R: Rng>(rng: &mut R, rows: &usize, cols: &usize) -> (usize, usize) {
+	(rng.gen_range(0..*rows), rng.gen_range(0..*cols))
+}
+
+/// Returns a uniform point which lies near an obstacle (gaussian sampling)
+fn gaussian_obstacle<R: Rng>(rng: &mut R, area: &[u64], rows: &usize, cols: &usize, sigma: f32) -> (usize, usize) {
+	for _ in 0..MAX_TRIES {
+		let first = uniform(rng, rows, cols);
+		if let Some(second) = get_normal_position(rng, rows, cols, first, sigma) {
+			let first_free = area[(first.1 * rows) + first.0] != u64::MAX;
+			let second_free = area[(second.1 * rows) + second.0] != u64::MAX;
+			match (first_free, second_free) {
+				(true, false) => return first,
+				(false, true) => return second,
+				_ => {},
+			}
+		}
+	}
+	uniform(rng, rows, cols)
+}
+
+/// Returns a point in the middle of two points inside of obstacles (bridge test)
+fn bridge<R: Rng>(rng: &mut R, area: &[u64], rows: &usize, cols: &usize, sigma: f32) -> (usize, usize) {
+	for _ in 0..MAX_TRIES {
+		let first = uniform(rng, rows, cols);
+		if area[(first.1 * rows) + first.0] != u64::MAX {
+			continue;
+		}
+		if let Some(second) = get_normal_position(rng, rows, cols, first, sigma) {
+			let middle = ((first.0 + second.0) / 2, (first.1 + second.1) / 2);
+			if area[(second.1 * rows) + second.0] == u64::MAX && area[(middle.1 * rows) + middle.0] != u64::MAX {
+				return middle;
+			}
+		}
+	}
+	uniform(rng, rows, cols)
+}
+
+/// Returns a normal distributed point around the given one or `None` if it lies outside of the area
+fn get_normal_position<R: Rng>(rng: &mut R, rows: &usize, cols: &usize, pos: (usize, usize), sigma: f32) -> Option<(usize, usize)> {
+	let x = pos.0 as f32 + gaussian(rng, sigma);
+	let y = pos.1 as f32 + gaussian(rng, sigma);
+	if x >= 0.0 && y >= 0.0 && (x as usize) < *rows && (y as usize) < *cols {
+		Some((x as usize, y as usize))
+	} else {
+		None
+	}
+}
+
+/// Returns a normal distributed value with mean 0 and the given standard deviation (Box-Muller transform)
+pub(crate) fn gaussian<R: Rng>(rng: &mut R, sigma: f32) -> f32 {
+	// `gen` is in [0, 1), the logarithm needs (0, 1]
+	let u1 = 1.0 - rng.gen::<f32>();
+	let u2 = rng.gen::<f32>();
+	sigma * f32::sqrt(-2.0 * u1.ln()) * (std::f32::consts::TAU * u2).cos()
+}
+
+/// Returns the value with the given index of the Halton sequence (radical inverse) in the given base
+fn halton(index: u32, base: u32) -> f64 {
+	let mut result = 0.0;
+	let mut fraction = 1.0;
+	let mut index = index;
+	while index > 0 {
+		fraction /= base as f64;
+		result += fraction * (index % base) as f64;
+		index /= base;
+	}
+	result
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rand::rngs::StdRng;
+	use rand::SeedableRng;
+
+	/// All strategies stay on the area, the sequences repeat and the goal is taken at the configured rate
+	#[test]
+	fn samples_are_on_the_area() {
+		let mut rng = StdRng::seed_from_u64(1);
+		let (rows, cols) = (37, 53);
+		// A block in the middle for the obstacle based strategies
+		let mut area = vec![0; rows * cols];
+		for col in 20..30 {
+			for row in 10..25 {
+				area[(col * rows) + row] = u64::MAX;
+			}
+		}
+		let goal = (5, 50);
+
+		for strategy in [Sampling::Uniform, Sampling::GoalBias(0.5), Sampling::Gaussian(5.0), Sampling::Bridge(10.0), Sampling::Halton, Sampling::Sobol] {
+			let mut sampler = Sampler::new(strategy);
+			for _ in 0..1000 {
+				let (row, col) = sampler.sample(&mut rng, &area, &rows, &cols, goal);
+				assert!(row < rows && col < cols, "{:?}: ({}, {})", strategy, row, col);
+			}
+		}
+
+		// The low-discrepancy sequences do not depend on the random generator
+		for strategy in [Sampling::Halton, Sampling::Sobol] {
+			let (mut first, mut second) = (Sampler::new(strategy), Sampler::new(strategy));
+			let points: Vec<(usize, usize)> = (0..256).map(|_| first.sample(&mut rng, &area, &rows, &cols, goal)).collect();
+			let repeated: Vec<(usize, usize)> = (0..256).map(|_| second.sample(&mut StdRng::seed_from_u64(2), &area, &rows, &cols, goal)).collect();
+			assert_eq!(points, repeated, "{:?}", strategy);
+		}
+		assert_eq!((halton(1, 2), halton(2, 2), halton(3, 2), halton(1, 3), halton(2, 3)), (0.5, 0.25, 0.75, 1.0 / 3.0, 2.0 / 3.0));
+		let mut sobol = Sampler::new(Sampling::Sobol);
+		assert_eq!([sobol.next_sobol(), sobol.next_sobol(), sobol.next_sobol()], [(0.0, 0.0), (0.5, 0.5), (0.75, 0.25)]);
+
+		// Uniform points hit the goal field only 1 / (rows * cols) of the time
+		let mut sampler = Sampler::new(Sampling::GoalBias(0.2));
+		let hits = (0..10000).filter(|_| sampler.sample(&mut rng, &area, &rows, &cols, goal) == goal).count();
+		assert!((1800..2200).contains(&hits), "{}", hits);
+	}
+}
@@ -0,0 +1,360 @@
+//! Closed-loop simulation of a differential-drive robot following a planned path
+//!
+//! The planned paths are checked on a simulated robot instead of the physical one. In each control cycle:
+//!
+//! 1. **Controller:** *Pure pursuit* or *Stanley* calculates the forward and angular velocity from the estimated pose
+//!    and the path. The velocity is limited by the curvature, so no wheel exceeds its maximum velocity.
+//! 2. **Motors:** The wheel velocities follow the commands within the maximum velocity and acceleration of the wheels.
+//! 3. **Motion:** Each wheel slips by a normal distributed part of its travel, the robot moves on the arc of the real travel.
+//! 4. **Encoders:** The rotation of each wheel is counted in whole ticks. The odometry integrates the ticks to the
+//!    estimated pose, which drifts away from the real one.
+//! 5. **Collision:** The real pose has to keep the radius of the robot to all obstacles of the area.
+//!
+//! All distances are in fields of the area and all times in seconds, like in `robot`.
+
+use std::f64::consts::TAU;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::{Instant, Rng};
+use crate::dwa::get_clearance;
+use crate::robot::{normalize_angle, DiffDrive, Pose};
+use crate::sampler::gaussian;
+
+const SEARCH_WINDOW: usize = 50; // Number of path segments ahead of the last closest point to search the next one
+const HEADING_TIME: f64 = 0.25; // Time in seconds for the Stanley controller to correct the steering angle
+
+/// The path tracking controller
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Controller {
+	/// Drives on the arc through the point on the path in the lookahead distance
+	PurePursuit { lookahead: f64 },
+	/// Steers by the heading error and the cross-track error weighted by the gain
+	Stanley { gain: f64 },
+}
+
+impl FromStr for Controller {
+	type Err = String;
+
+	/// Parses a controller like `pure-pursuit`, `pure-pursuit:20`, `stanley` or `stanley:2`
+	fn from_str(value: &str) -> Result<Self, Self::Err> {
+		let (name, param) = match value.split_once(':') {
+			Some((name, param)) => (name, Some(param)),
+			None => (value, None),
+		};
+		let param = |default: f64| -> Result<f64, String> {
+			match param {
+				Some(val) => val.parse::<f64>().map_err(|_| format!("Invalid controller parameter: {}", val)),
+				None => Ok(default),
+			}
+		};
+
+		match name.to_lowercase().as_str() {
+			"pure-pursuit" => Ok(Controller::PurePursuit { lookahead: param(20.0)? }),
+			"stanley" => Ok(Controller::Stanley { gain: param(2.0)? }),
+			_ => Err(format!("Unknown controller: {}", value)),
+		}
+	}
+}
+
+/// Configuration of the robot, its sensors and the controller
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimulationConfig {
+	/// The robot with its wheels and motor limits
+	pub robot: DiffDrive,
+	/// Radius of the robot, the minimum distance to all obstacles
+	pub radius: f64,
+	/// Encoder ticks per revolution of a wheel
+	pub ticks_per_revolution: u32,
+	/// Standard deviation of the wheel slip as part of the travel
+	pub slip: f64,
+	/// The path tracking controller
+	pub controller: Controller,
+	/// Forward velocity on straight parts of the path
+	pub velocity: f64,
+	/// Time of a control cycle in seconds
+	pub time_step: f64,
+	/// Radius around the end of the path to stop
+	pub goal_radius: f64,
+	/// Maximum time to reach the end in seconds
+	pub max_time: f64,
+}
+
+impl Default for SimulationConfig {
+	fn default() -> Self {
+		SimulationConfig {
+			robot: DiffDrive::default(),
+			radius: 2.0,
+			ticks_per_revolution: 512,
+			slip: 0.02,
+			controller: Controller::PurePursuit { lookahead: 20.0 },
+			velocity: 15.0,
+			time_step: 0.01,
+			goal_radius: 2.0,
+			max_time: 300.0,
+		}
+	}
+}
+
+/// The state of the robot after a control cycle
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct State {
+	/// Time since the start in seconds
+	pub time: f64,
+	/// The real pose
+	pub pose: Pose,
+	/// The pose estimated by the odometry
+	pub estimate: Pose,
+	/// Velocity of the left and the right wheel
+	pub wheels: (f64, f64),
+}
+
+/// The result of a simulation
+#[derive(Debug)]
+pub struct SimulationResult {
+	/// The driven path from the end to the start as fields
+	pub path: Vec<(usize, usize)>,
+	/// The states from the start on, one per control cycle
+	pub states: Vec<State>,
+	/// The real pose where the robot hit an obstacle
+	pub collision: Option<Pose>,
+	/// If the robot stopped at the end of the path
+	pub reached: bool,
+	/// Largest distance of the real pose to the path
+	pub max_error: f64,
+	/// Distance between the real and the estimated pose at the end
+	pub drift: f64,
+}
+
+impl SimulationResult {
+	/// Writes the states as CSV with a header line
+	///
+	/// # Arguments:
+	///
+	/// * `writer` - Where to write to, e.g. a `File`
+	///
+	/// # Result:
+	///
+	/// Error from writing
+	pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+		writeln!(writer, "time,x,y,heading,estimate_x,estimate_y,estimate_heading,left,right")?;
+		for state in &self.states {
+			writeln!(writer, "{:.6},{:.6},{:.6},{:.6},{:.6},{:.6},{:.6},{:.6},{:.6}",
+				state.time, state.pose.x, state.pose.y, state.pose.heading,
+				state.estimate.x, state.estimate.y, state.estimate.heading, state.wheels.0, state.wheels.1)?;
+		}
+		Ok(())
+	}
+}
+
+/// Simulates the robot following the path in closed loop
+///
+/// # Arguments
+///
+/// * `rng` - The random generator for the slip of the wheels
+/// * `area` - The play field as a one-dimensional vector
+/// * `rows` - number of rows
+/// * `cols` - number of cols
+/// * `start` - start pose of the robot, standing still
+/// * `path` - The points from the start to the end, e.g. from `smoothing::smooth`
+/// * `config` - The robot, its sensors and the controller
+///
+/// # Result
+///
+/// The states of the robot, where it collided and how well it followed the path
+pub fn simulate<R: Rng>(rng: &mut R, area: &[u64], rows: &usize, cols: &usize, start: Pose, path: &[(f64, f64)], config: &SimulationConfig) -> SimulationResult {
+	let benchmark = Instant::now();
+	let robot = &config.robot;
+	let end = path.last().copied().unwrap_or((start.x, start.y));
+	let tick = TAU * robot.wheel_radius / config.ticks_per_revolution as f64;
+
+	let mut state = State { time: 0.0, pose: start, estimate: start, wheels: (0.0, 0.0) };
+	let mut states = vec![state];
+	// Travel of each wheel and the counted ticks
+	let mut travel = (0.0, 0.0);
+	let mut ticks = (0i64, 0i64);
+	let mut progress = 0;
+	let mut collision = None;
+	let mut reached = false;
+	let mut max_error: f64 = 0.0;
+
+	for _ in 0..(config.max_time / config.time_step).ceil() as usize {
+		// Without a path there is nothing to follow
+		if path.len() < 2 {
+			break;
+		}
+		let remaining = f64::hypot(end.0 - state.estimate.x, end.1 - state.estimate.y);
+		if remaining <= config.goal_radius {
+			reached = true;
+			break;
+		}
+
+		// 1. The controller only knows the estimated pose
+		let (index, closest, tangent) = get_closest(path, progress, &state.estimate);
+		progress = index;
+		let velocity = f64::min(config.velocity, f64::sqrt(2.0 * robot.max_acceleration * remaining));
+		let (velocity, angular_velocity) = match config.controller {
+			Controller::PurePursuit { lookahead } => {
+				let target = get_lookahead(path, index, closest, lookahead);
+				let (dx, dy) = (target.0 - state.estimate.x, target.1 - state.estimate.y);
+				let lateral = (dy * state.estimate.heading.cos()) - (dx * state.estimate.heading.sin());
+				let curvature = 2.0 * lateral / f64::max(1e-9, (dx * dx) + (dy * dy));
+				let velocity = f64::min(velocity, robot.max_velocity / (1.0 + (curvature.abs() * robot.wheel_distance / 2.0)));
+				(velocity, velocity * curvature)
+			},
+			Controller::Stanley { gain } => {
+				let heading_error = normalize_angle(tangent - state.estimate.heading);
+				let cross_track = ((closest.1 - state.estimate.y) * tangent.cos()) - ((closest.0 - state.estimate.x) * tangent.sin());
+				let steering = heading_error + f64::atan2(gain * cross_track, velocity + 1.0);
+				let max_angular = robot.max_angular_velocity(velocity);
+				(velocity, (steering / HEADING_TIME).clamp(-max_angular, max_angular))
+			},
+		};
+
+		// 2. The motors follow the commands within their limits
+		let (left, right) = robot.wheel_velocities(velocity, angular_velocity);
+		let motor = |current: f64, command: f64| {
+			let change = robot.max_acceleration * config.time_step;
+			command.clamp(-robot.max_velocity, robot.max_velocity).clamp(current - change, current + change)
+		};
+		let wheels = (motor(state.wheels.0, left), motor(state.wheels.1, right));
+
+		// 3. Each wheel slips, the robot moves by the real travel
+		let slip = |wheel: f64, rng: &mut _| wheel * config.time_step * (1.0 + gaussian(rng, config.slip as f32) as f64);
+		let moved = (slip(wheels.0, rng), slip(wheels.1, rng));
+		let (v, w) = robot.robot_velocities(moved.0 / config.time_step, moved.1 / config.time_step);
+		let pose = state.pose.simulate(v, w, config.time_step);
+
+		// 4. The encoders count the whole ticks of the rotation, the odometry integrates them
+		travel = (travel.0 + moved.0, travel.1 + moved.1);
+		let counted = ((travel.0 / tick).floor() as i64, (travel.1 / tick).floor() as i64);
+		let measured = ((counted.0 - ticks.0) as f64 * tick, (counted.1 - ticks.1) as f64 * tick);
+		ticks = counted;
+		let (v, w) = robot.robot_velocities(measured.0 / config.time_step, measured.1 / config.time_step);
+		let estimate = state.estimate.simulate(v, w, config.time_step);
+
+		state = State { time: state.time + config.time_step, pose, estimate, wheels };
+		states.push(state);
+
+		// 5. The real robot must not touch an obstacle
+		if get_clearance(area, rows, cols, (pose.x, pose.y), config.radius) < config.radius {
+			collision = Some(pose);
+			break;
+		}
+		let (_, closest, _) = get_closest(path, progress, &pose);
+		max_error = f64::max(max_error, f64::hypot(closest.0 - pose.x, closest.1 - pose.y));
+	}
+
+	let name = match config.controller {
+		Controller::PurePursuit { .. } => "Pure-Pursuit",
+		Controller::Stanley { .. } => "Stanley",
+	};
+	match collision {
+		Some(pose) => println!("Simulation {} Collision at ({:.2}, {:.2}) after {:.2}s", name, pose.x, pose.y, state.time),
+		None if !reached => println!("Simulation {} Calc: No conneciton found", name),
+		None => println!("Simulation {} Calc: {:.6?}", name, benchmark.elapsed()),
+	}
+
+	let mut driven: Vec<(usize, usize)> = vec![];
+	states.iter().rev()
+		.filter_map(|state| state.pose.field(rows, cols))
+		.for_each(|field| if driven.last() != Some(&field) { driven.push(field) });
+	let drift = state.pose.distance(&state.estimate);
+	println!("Simulation {} Time: {:.2}s", name, state.time);
+	println!("Simulation {} Max error: {:.2}", name, max_error);
+	println!("Simulation {} Odometry drift: {:.2}", name, drift);
+
+	SimulationResult {
+		path: driven,
+		states,
+		collision,
+		reached,
+		max_error,
+		drift,
+	}
+}
+
+/// Returns the nearest point of the path to the pose within the search window from the given segment on
+///
+/// # Result
+///
+/// The index of the segment, the nearest point and the direction of the segment
+fn get_closest(path: &[(f64, f64)], from: usize, pose: &Pose) -> (usize, (f64, f64), f64) {
+	let mut best = (from, path[usize::min(from, path.len() - 1)], 0.0, f64::MAX);
+	for index in from..usize::min(from + SEARCH_WINDOW, path.len().saturating_sub(1)) {
+		let (a, b) = (path[index], path[index + 1]);
+		let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+		let length = (dx * dx) + (dy * dy);
+		let t = if length > 0.0 { (((pose.x - a.0) * dx) + ((pose.y - a.1) * dy)) / length } else { 0.0 };
+		let point = (a.0 + (t.clamp(0.0, 1.0) * dx), a.1 + (t.clamp(0.0, 1.0) * dy));
+		let distance = f64::hypot(point.0 - pose.x, point.1 - pose.y);
+		if distance < best.3 {
+			best = (index, point, f64::atan2(dy, dx), distance);
+		}
+	}
+	(best.0, best.1, best.2)
+}
+
+/// Returns the point the distance along the path ahead of the point on the given segment, the end at most
+fn get_lookahead(path: &[(f64, f64)], index: usize, point: (f64, f64), distance: f64) -> (f64, f64) {
+	let mut remaining = distance;
+	let mut from = point;
+	for next in path.iter().skip(index + 1) {
+		let length = f64::hypot(next.0 - from.0, next.1 - from.1);
+		if length >= remaining {
+			let t = remaining / length;
+			return (from.0 + ((next.0 - from.0) * t), from.1 + ((next.1 - from.1) * t));
+		}
+		remaining -= length;
+		from = *next;
+	}
+	path.last().copied().unwrap_or(point)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::f64::consts::PI;
+	use rand::rngs::StdRng;
+	use rand::SeedableRng;
+
+	/// Both controllers drive around an obstacle with slipping wheels, straight through it the robot collides
+	#[test]
+	fn controllers_follow_a_curve() {
+		let mut rng = StdRng::seed_from_u64(1);
+		let (rows, cols) = (100, 200);
+		let mut area = vec![0u64; rows * cols];
+		for col in 90..110 {
+			for row in 0..30 {
+				area[(col * rows) + row] = u64::MAX;
+			}
+		}
+		// Around the obstacle on a smooth detour
+		let path: Vec<(f64, f64)> = (10..190)
+			.map(|col| match col {
+				60..140 => (20.0 + (15.0 * (1.0 - f64::cos(2.0 * PI * (col - 60) as f64 / 80.0))), col as f64),
+				_ => (20.0, col as f64),
+			})
+			.collect();
+
+		for controller in [Controller::PurePursuit { lookahead: 10.0 }, Controller::Stanley { gain: 2.0 }] {
+			let config = SimulationConfig { controller, ..SimulationConfig::default() };
+			let result = simulate(&mut rng, &area, &rows, &cols, Pose::new(20.0, 10.0, PI / 2.0), &path, &config);
+			assert!(result.reached, "{:?}", controller);
+			assert_eq!(result.collision, None);
+			assert!(result.max_error < 5.0, "{:?}: {}", controller, result.max_error);
+			assert!(result.states.iter().all(|state| state.wheels.0.abs() <= config.robot.max_velocity && state.wheels.1.abs() <= config.robot.max_velocity));
+		}
+
+		// Straight through the obstacle
+		let straight: Vec<(f64, f64)> = (10..190).map(|col| (20.0, col as f64)).collect();
+		let result = simulate(&mut rng, &area, &rows, &cols, Pose::new(20.0, 10.0, PI / 2.0), &straight, &SimulationConfig::default());
+		assert!(result.collision.is_some());
+		assert!(!result.reached);
+
+		// No path to follow
+		for path in [vec![], vec![(20.0, 10.0)]] {
+			assert!(!simulate(&mut rng, &area, &rows, &cols, Pose::new(20.0, 10.0, 0.0), &path, &SimulationConfig::default()).reached);
+		}
+	}
+}
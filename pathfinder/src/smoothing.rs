@@ -0,0 +1,291 @@
+//! Post-processing to smooth the paths of all algorithms
+//!
+//! # Shortcutting
+//!
+//! Grid algorithms return one waypoint per field and the tree algorithms jagged polylines.
+//! Waypoints are removed as long as the direct line between the remaining ones is free of obstacles,
+//! either greedily from the start or between random pairs of waypoints.
+//!
+//! # Splines
+//!
+//! A cubic spline is fitted through the remaining waypoints and checked against the area.
+//! Where the spline hits an obstacle or comes closer to it than the clearance, it falls back to the straight lines locally.
+//! These straight lines are only free of obstacles like the lines between the waypoints, they do not keep the clearance.
+
+use std::str::FromStr;
+use crate::{thread_rng, Rng};
+use crate::tree::is_line_free;
+
+/// The spline to fit through the waypoints
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Spline {
+	/// Catmull-Rom spline, goes through all waypoints
+	#[default]
+	CatmullRom,
+	/// Uniform cubic B-spline, the waypoints are the control points, only the start and end are met
+	BSpline,
+}
+
+impl FromStr for Spline {
+	type Err = String;
+
+	/// Parses a spline like `catmull-rom` or `b-spline`
+	fn from_str(value: &str) -> Result<Self, Self::Err> {
+		match value {
+			"catmull-rom" => Ok(Spline::CatmullRom),
+			"b-spline" => Ok(Spline::BSpline),
+			_ => Err(format!("Unknown spline: {}", value)),
+		}
+	}
+}
+
+/// Removes waypoints greedily: From each waypoint the farthest one with a free direct line is taken as the next one
+///
+/// # Arguments:
+///
+/// * `area` - The area as a vector of u64 where every obstacle is u64::MAX
+/// * `rows` - Number of rows, where rows x cols is the size of the area
+/// * `path` - The path to shorten, in any direction
+///
+/// # Result:
+///
+/// The remaining waypoints, the first and last one are kept
+pub fn shortcut(area: &[u64], rows: &usize, path: &[(usize, usize)]) -> Vec<(usize, usize)> {
+	let mut result: Vec<(usize, usize)> = path.iter().take(1).copied().collect();
+	let mut current = 0;
+	while current + 1 < path.len() {
+		let next = (current + 1..path.len()).rev()
+			.find(|next| is_line_free(area, rows, path[current], path[*next]))
+			.unwrap_or(current + 1);
+		result.push(path[next]);
+		current = next;
+	}
+	result
+}
+
+/// Removes waypoints between random pairs of waypoints if the direct line between them is free
+///
+/// # Arguments:
+///
+/// * `area` - The area as a vector of u64 where every obstacle is u64::MAX
+/// * `rows` - Number of rows, where rows x cols is the size of the area
+/// * `path` - The path to shorten, in any direction
+/// * `iterations` - Number of random pairs to check
+///
+/// # Result:
+///
+/// The remaining waypoints, the first and last one are kept
+pub fn shortcut_random(area: &[u64], rows: &usize, path: &[(usize, usize)], iterations: usize) -> Vec<(usize, usize)> {
+	let mut rng = thread_rng();
+	let mut result = path.to_vec();
+	for _ in 0..iterations {
+		if result.len() < 3 {
+			break;
+		}
+		let first = rng.gen_range(0..result.len() - 2);
+		let second = rng.gen_range(first + 2..result.len());
+		if is_line_free(area, rows, result[first], result[second]) {
+			result.drain(first + 1..second);
+		}
+	}
+	result
+}
+
+/// Fits a spline through the waypoints and falls back to straight lines where it is not free
+///
+/// # Arguments:
+///
+/// * `area` - The area as a vector of u64 where every obstacle is u64::MAX
+/// * `rows` - Number of rows, where rows x cols is the size of the area
+/// * `cols` - Number of columns, where rows x cols is the size of the area
+/// * `path` - The waypoints, e.g. after shortcutting. The straight lines between them have to be free
+/// * `spline` - The spline to fit
+/// * `clearance` - Minimum number of free fields around each point of the spline, the straight lines of the fallback
+///   and a path of only two waypoints are not checked against it
+///
+/// # Result:
+///
+/// Points along the spline, at most about one field apart, in the middle of the fields like `robot::Pose::from_field`.
+/// Only the points of the spline keep the clearance, the straight lines only have a free direct line.
+pub fn smooth(area: &[u64], rows: &usize, cols: &usize, path: &[(usize, usize)], spline: Spline, clearance: usize) -> Vec<(f64, f64)> {
+	let waypoints: Vec<(f64, f64)> = path.iter().map(|pos| (pos.0 as f64 + 0.5, pos.1 as f64 + 0.5)).collect();
+	match waypoints.len() {
+		0 | 1 => return waypoints,
+		2 => return line_segment(waypoints[0], waypoints[1]),
+		_ => {},
+	}
+	let is_free = |points: &[(f64, f64)]| {
+		points.iter().all(|point| is_clear(area, rows, cols, *point, clearance))
+			&& points.windows(2).all(|pair| is_line_free(area, rows, to_field(pair[0]), to_field(pair[1])))
+	};
+
+	match spline {
+		Spline::CatmullRom => {
+			// Each segment between two waypoints is replaced by the straight line if it is not free
+			let mut result = vec![waypoints[0]];
+			for segment in 0..waypoints.len() - 1 {
+				let points = catmull_rom_segment(&waypoints, segment);
+				if is_free(&points) {
+					result.extend(points.into_iter().skip(1));
+				} else {
+					result.extend(line_segment(waypoints[segment], waypoints[segment + 1]).into_iter().skip(1));
+				}
+			}
+			result
+		},
+		Spline::BSpline => {
+			// A control point which is used three times is met by the spline with straight lines to its neighbours.
+			// All control points of a segment which is not free are tripled until the whole spline is free.
+			let mut multiplicity = vec![1; waypoints.len()];
+			multiplicity[0] = 3;
+			multiplicity[waypoints.len() - 1] = 3;
+			loop {
+				let mut owners = vec![];
+				let mut controls = vec![];
+				for (key, point) in waypoints.iter().enumerate() {
+					for _ in 0..multiplicity[key] {
+						owners.push(key);
+						controls.push(*point);
+					}
+				}
+
+				let mut result = vec![];
+				let mut changed = false;
+				for segment in 0..controls.len() - 3 {
+					let points = b_spline_segment(&controls, segment);
+					if !is_free(&points) {
+						for owner in &owners[segment..segment + 4] {
+							changed |= multiplicity[*owner] < 3;
+							multiplicity[*owner] = 3;
+						}
+					}
+					result.extend(points.into_iter().skip(if segment == 0 { 0 } else { 1 }));
+				}
+
+				// If nothing can be changed anymore, the spline is the polyline which is free
+				if !changed {
+					return result;
+				}
+			}
+		},
+	}
+}
+
+/// Converts points to the fields they lie on, without repeating a field
+pub fn to_fields(points: &[(f64, f64)]) -> Vec<(usize, usize)> {
+	let mut fields: Vec<(usize, usize)> = vec![];
+	points.iter()
+		.map(|point| to_field(*point))
+		.for_each(|field| if fields.last() != Some(&field) { fields.push(field) });
+	fields
+}
+
+/// Returns the field a point lies on
+fn to_field(point: (f64, f64)) -> (usize, usize) {
+	(f64::max(0.0, point.0) as usize, f64::max(0.0, point.1) as usize)
+}
+
+/// Checks if all fields in the given distance around the point are free and inside the area
+fn is_clear(area: &[u64], rows: &usize, cols: &usize, point: (f64, f64), clearance: usize) -> bool {
+	if point.0 < 0.0 || point.1 < 0.0 || point.0 as usize >= *rows || point.1 as usize >= *cols {
+		return false;
+	}
+	let (row, col) = to_field(point);
+	let rows_range = row.saturating_sub(clearance)..=usize::min(rows - 1, row + clearance);
+	let cols_range = col.saturating_sub(clearance)..=usize::min(cols - 1, col + clearance);
+	cols_range.into_iter().all(|c| rows_range.clone().all(|r| area[(c * rows) + r] != u64::MAX))
+}
+
+/// Returns the number of samples for a segment from `p1` to `p2`: one per field
+fn get_samples(p1: (f64, f64), p2: (f64, f64)) -> usize {
+	usize::max(2, f64::hypot(p2.0 - p1.0, p2.1 - p1.1).ceil() as usize)
+}
+
+/// Samples the straight line between two points, both included
+fn line_segment(p1: (f64, f64), p2: (f64, f64)) -> Vec<(f64, f64)> {
+	let samples = get_samples(p1, p2);
+	(0..=samples).map(|i| {
+		let t = i as f64 / samples as f64;
+		(p1.0 + ((p2.0 - p1.0) * t), p1.1 + ((p2.1 - p1.1) * t))
+	}).collect()
+}
+
+/// Samples the Catmull-Rom segment between the waypoints `segment` and `segment + 1`, both included
+///
+/// The first and last waypoints are mirrored to get the tangents on the ends.
+fn catmull_rom_segment(waypoints: &[(f64, f64)], segment: usize) -> Vec<(f64, f64)> {
+	let p1 = waypoints[segment];
+	let p2 = waypoints[segment + 1];
+	let p0 = if segment == 0 { (2.0 * p1.0 - p2.0, 2.0 * p1.1 - p2.1) } else { waypoints[segment - 1] };
+	let p3 = if segment + 2 < waypoints.len() { waypoints[segment + 2] } else { (2.0 * p2.0 - p1.0, 2.0 * p2.1 - p1.1) };
+
+	let samples = get_samples(p1, p2);
+	(0..=samples).map(|i| {
+		let t = i as f64 / samples as f64;
+		let (t2, t3) = (t * t, t * t * t);
+		let value = |v0: f64, v1: f64, v2: f64, v3: f64| {
+			0.5 * ((2.0 * v1) + ((v2 - v0) * t) + (((2.0 * v0) - (5.0 * v1) + (4.0 * v2) - v3) * t2) + (((3.0 * v1) - v0 - (3.0 * v2) + v3) * t3))
+		};
+		(value(p0.0, p1.0, p2.0, p3.0), value(p0.1, p1.1, p2.1, p3.1))
+	}).collect()
+}
+
+/// Samples the uniform cubic B-spline segment defined by the control points `segment` to `segment + 3`, both ends included
+fn b_spline_segment(controls: &[(f64, f64)], segment: usize) -> Vec<(f64, f64)> {
+	let [p0, p1, p2, p3] = [controls[segment], controls[segment + 1], controls[segment + 2], controls[segment + 3]];
+
+	// The segment goes from (p0 + 4 * p1 + p2) / 6 to (p1 + 4 * p2 + p3) / 6
+	let start = ((p0.0 + (4.0 * p1.0) + p2.0) / 6.0, (p0.1 + (4.0 * p1.1) + p2.1) / 6.0);
+	let end = ((p1.0 + (4.0 * p2.0) + p3.0) / 6.0, (p1.1 + (4.0 * p2.1) + p3.1) / 6.0);
+	let samples = get_samples(start, end);
+	(0..=samples).map(|i| {
+		let t = i as f64 / samples as f64;
+		let (t2, t3) = (t * t, t * t * t);
+		let b0 = (1.0 - t).powi(3) / 6.0;
+		let b1 = ((3.0 * t3) - (6.0 * t2) + 4.0) / 6.0;
+		let b2 = ((-3.0 * t3) + (3.0 * t2) + (3.0 * t) + 1.0) / 6.0;
+		let b3 = t3 / 6.0;
+		(
+			(b0 * p0.0) + (b1 * p1.0) + (b2 * p2.0) + (b3 * p3.0),
+			(b0 * p0.1) + (b1 * p1.1) + (b2 * p2.1) + (b3 * p3.1),
+		)
+	}).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{a_star, dwa};
+
+	/// The shortcuts keep both ends and free lines, the spline keeps the clearance around a block
+	#[test]
+	fn smoothed_path_is_free() {
+		let (rows, cols) = (60, 60);
+		let mut area = vec![0; rows * cols];
+		for col in 20..40 {
+			for row in 15..45 {
+				area[(col * rows) + row] = u64::MAX;
+			}
+		}
+		// The straight lines between the waypoints keep more than the clearance, the splines cut the corners
+		let clearance = 2;
+		let inflated = dwa::inflate(&area, &rows, &cols, 4.0);
+		let path = a_star::calculate(&mut inflated.clone(), &rows, &cols, (30, 5), (30, 55));
+		assert_eq!((path.first(), path.last()), (Some(&(30, 55)), Some(&(30, 5))));
+
+		for waypoints in [shortcut(&inflated, &rows, &path), shortcut_random(&inflated, &rows, &path, 1000)] {
+			assert!(waypoints.len() >= 3 && waypoints.len() < path.len(), "{:?}", waypoints);
+			assert_eq!((waypoints.first(), waypoints.last()), (path.first(), path.last()));
+			assert!(waypoints.windows(2).all(|pair| is_line_free(&inflated, &rows, pair[0], pair[1])), "{:?}", waypoints);
+
+			for spline in [Spline::CatmullRom, Spline::BSpline] {
+				let points = smooth(&area, &rows, &cols, &waypoints, spline, clearance);
+				assert_eq!(to_field(points[0]), path[0]);
+				assert_eq!(to_field(points[points.len() - 1]), path[path.len() - 1]);
+				for point in &points {
+					assert!(is_clear(&area, &rows, &cols, *point, clearance), "{:?}: {:?}", spline, point);
+				}
+			}
+		}
+	}
+}
@@ -0,0 +1,288 @@
+//! Space-time planning on the grid around moving obstacles
+//!
+//! Other robots move through the area with a known schedule: a dynamic obstacle is on one field of its trajectory
+//! at each time step and stays on the last one afterwards. In each time step, the robot moves to one of its
+//! 4 neighbours or waits on its field.
+//!
+//! All fields and moves of the obstacles are reserved in a reservation table. The space-time A* searches over
+//! (field, time) with the grassfire distance to the end as the heuristic and never enters a reserved field or swaps
+//! its field with an obstacle. After the last reservation the area is static, so all later times are the same state.
+
+use std::cmp::Ordering;
+use std::collections::hash_map::Entry;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+
+use crate::Instant;
+
+/// A field (row, col) at a time step
+pub type TimedField = ((usize, usize), usize);
+
+/// A moving obstacle with a known schedule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DynamicObstacle {
+	/// The field of the obstacle at each time step, it stays on the last field afterwards
+	pub trajectory: Vec<(usize, usize)>,
+	/// The obstacle covers all fields up to this distance around its field
+	pub radius: usize,
+}
+
+impl DynamicObstacle {
+	/// Returns all fields inside of the area covered by the obstacle at the time
+	pub fn get_fields(&self, time: usize, rows: &usize, cols: &usize) -> Vec<(usize, usize)> {
+		let Some(center) = self.trajectory.get(usize::min(time, self.trajectory.len().saturating_sub(1))) else {
+			return vec![];
+		};
+		let rows_range = center.0.saturating_sub(self.radius)..=usize::min(rows - 1, center.0 + self.radius);
+		let cols_range = center.1.saturating_sub(self.radius)..=usize::min(cols - 1, center.1 + self.radius);
+		cols_range.flat_map(|col| rows_range.clone().map(move |row| (row, col))).collect()
+	}
+}
+
+/// Inner type representing a move from a field to an other one, arriving at a time
+type Move = ((usize, usize), (usize, usize), usize);
+
+/// Fields and moves which are not allowed for a robot at a given time
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReservationTable {
+	/// Reserved fields at a time
+	fields: HashSet<TimedField>,
+	/// Reserved moves from a field to an other one, arriving at a time
+	moves: HashSet<Move>,
+	/// Fields reserved forever from a time on
+	goals: HashMap<(usize, usize), usize>,
+}
+
+impl ReservationTable {
+	/// Creates an empty reservation table
+	pub fn new() -> Self {
+		ReservationTable::default()
+	}
+
+	/// Reserves the field at the time
+	pub fn reserve_field(&mut self, field: (usize, usize), time: usize) {
+		self.fields.insert((field, time));
+	}
+
+	/// Reserves the move from a field to an other one, arriving at the time
+	pub fn reserve_move(&mut self, from: (usize, usize), to: (usize, usize), time: usize) {
+		self.moves.insert((from, to, time));
+	}
+
+	/// Reserves all fields of the path, the moves in the opposite direction and the end forever
+	pub fn reserve_path(&mut self, path: &[(usize, usize)]) {
+		for (time, field) in path.iter().enumerate() {
+			self.reserve_field(*field, time);
+			if time > 0 {
+				self.reserve_move(*field, path[time - 1], time);
+			}
+		}
+		if let Some(end) = path.last() {
+			self.goals.insert(*end, path.len() - 1);
+		}
+	}
+
+	/// Reserves the fields of the obstacle at each time step and its last fields forever
+	pub fn reserve_obstacle(&mut self, obstacle: &DynamicObstacle, rows: &usize, cols: &usize) {
+		for time in 0..obstacle.trajectory.len() {
+			for field in obstacle.get_fields(time, rows, cols) {
+				self.reserve_field(field, time);
+			}
+			if time > 0 {
+				self.reserve_move(obstacle.trajectory[time], obstacle.trajectory[time - 1], time);
+			}
+		}
+		for field in obstacle.get_fields(obstacle.trajectory.len().saturating_sub(1), rows, cols) {
+			let since = self.goals.entry(field).or_insert(usize::MAX);
+			*since = usize::min(*since, obstacle.trajectory.len().saturating_sub(1));
+		}
+	}
+
+	/// Checks if the move from a field to an other one, arriving at the time, is reserved
+	pub fn is_reserved(&self, from: (usize, usize), to: (usize, usize), time: usize) -> bool {
+		self.fields.contains(&(to, time))
+			|| self.moves.contains(&(from, to, time))
+			|| self.goals.get(&to).is_some_and(|since| time >= *since)
+	}
+
+	/// Returns the last time the field is reserved, `usize::MAX` if it is reserved forever
+	fn get_last_reservation(&self, field: (usize, usize)) -> Option<usize> {
+		if self.goals.contains_key(&field) {
+			return Some(usize::MAX);
+		}
+		self.fields.iter().filter(|(f, _)| *f == field).map(|(_, time)| *time).max()
+	}
+
+	/// Returns the last time of all reservations
+	fn get_horizon(&self) -> usize {
+		let fields = self.fields.iter().map(|(_, time)| *time);
+		let moves = self.moves.iter().map(|(_, _, time)| *time);
+		let goals = self.goals.values().copied();
+		fields.chain(moves).chain(goals).max().unwrap_or(0)
+	}
+}
+
+/// Entry of the open list, ordered by the lowest estimation first and the latest time on equal estimations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Open {
+	estimate: usize,
+	time: usize,
+	field: (usize, usize),
+}
+
+impl Ord for Open {
+	fn cmp(&self, other: &Self) -> Ordering {
+		other.estimate.cmp(&self.estimate).then(self.time.cmp(&other.time))
+	}
+}
+
+impl PartialOrd for Open {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+
+/// Calculates the fastest path from the start to the end around the moving obstacles
+///
+/// # Arguments
+///
+/// * `area` - The play field as a one-dimensional vector
+/// * `rows` - number of rows
+/// * `cols` - number of cols
+/// * `start` - start position (row, col)
+/// * `end` - end position (row, col)
+/// * `obstacles` - The moving obstacles with their schedules
+///
+/// # Result
+///
+/// The path from the start to the end with the time step of each field, waits repeat the field
+pub fn calculate(area: &[u64], rows: &usize, cols: &usize, start: (usize, usize), end: (usize, usize), obstacles: &[DynamicObstacle]) -> Vec<TimedField> {
+	let benchmark = Instant::now();
+	let mut reservations = ReservationTable::new();
+	for obstacle in obstacles {
+		reservations.reserve_obstacle(obstacle, rows, cols);
+	}
+
+	let distances = get_distances(area, rows, cols, end);
+	let path = a_star(area, rows, cols, start, end, &reservations, &distances);
+	if path.is_empty() { println!("Space-Time-A* Calc: No conneciton found"); }
+	println!("Space-Time-A* Calc: {:.6?}", benchmark.elapsed());
+	println!("Space-Time-A* Path length: {}", path.len());
+	println!("Space-Time-A* Waits: {}", path.windows(2).filter(|pair| pair[0] == pair[1]).count());
+
+	path.into_iter().enumerate().map(|(time, field)| (field, time)).collect()
+}
+
+/// Space-time A* for a single robot around the reservations
+///
+/// # Arguments
+///
+/// * `area` - The play field as a one-dimensional vector
+/// * `rows` - number of rows
+/// * `cols` - number of cols
+/// * `start` - start position (row, col)
+/// * `end` - end position (row, col)
+/// * `reservations` - Fields and moves the robot must not use
+/// * `distances` - The grassfire distances to the end as the heuristic
+///
+/// # Result
+///
+/// The path from the start to the end with one field per time step, empty if there is none
+pub fn a_star(area: &[u64], rows: &usize, cols: &usize, start: (usize, usize), end: (usize, usize), reservations: &ReservationTable, distances: &[usize]) -> Vec<(usize, usize)> {
+	let distance = |field: (usize, usize)| distances[(field.1 * rows) + field.0];
+	if distance(start) == usize::MAX || reservations.is_reserved(start, start, 0) {
+		return vec![];
+	}
+	// The robot stays on its end, so it may arrive only after the last reservation of it
+	let earliest = match reservations.get_last_reservation(end) {
+		Some(usize::MAX) => return vec![],
+		Some(time) => time + 1,
+		None => 0,
+	};
+	// After the last reservation the area is static, all later times are the same state
+	let horizon = reservations.get_horizon() + 1;
+
+	let mut parents: HashMap<TimedField, (usize, usize)> = HashMap::new();
+	let mut closed: HashSet<TimedField> = HashSet::new();
+	let mut open: BinaryHeap<Open> = BinaryHeap::new();
+	open.push(Open { estimate: distance(start), time: 0, field: start });
+
+	while let Some(Open { time, field, .. }) = open.pop() {
+		if field == end && time >= earliest {
+			let mut path = vec![field];
+			let mut current = (field, time);
+			while let Some(parent) = parents.get(&current) {
+				current = (*parent, current.1 - 1);
+				path.push(*parent);
+			}
+			path.reverse();
+			return path;
+		}
+		if !closed.insert((field, usize::min(time, horizon))) {
+			continue;
+		}
+
+		// Wait on the field or move to a free neighbour
+		let neighbours = [(0, 0), (-1, 0), (1, 0), (0, -1), (0, 1)].into_iter()
+			.map(|(dx, dy): (i64, i64)| (field.0 as i64 + dx, field.1 as i64 + dy))
+			.filter(|(x, y)| (0..*rows as i64).contains(x) && (0..*cols as i64).contains(y))
+			.map(|(x, y)| (x as usize, y as usize));
+		for next in neighbours {
+			if area[(next.1 * rows) + next.0] == u64::MAX || reservations.is_reserved(field, next, time + 1) || closed.contains(&(next, usize::min(time + 1, horizon))) {
+				continue;
+			}
+			// All paths to a field at a time have the same cost, the first parent is kept
+			if let Entry::Vacant(entry) = parents.entry((next, time + 1)) {
+				entry.insert(field);
+				open.push(Open { estimate: time + 1 + distance(next), time: time + 1, field: next });
+			}
+		}
+	}
+	vec![]
+}
+
+/// Calculates the grassfire distance with the 4-Neighborhood of all fields to the end, `usize::MAX` if unreachable
+pub fn get_distances(area: &[u64], rows: &usize, cols: &usize, end: (usize, usize)) -> Vec<usize> {
+	let mut distances = vec![usize::MAX; area.len()];
+	if area[(end.1 * rows) + end.0] == u64::MAX {
+		return distances;
+	}
+	distances[(end.1 * rows) + end.0] = 0;
+	let mut queue = VecDeque::from([end]);
+	while let Some(field) = queue.pop_front() {
+		let distance = distances[(field.1 * rows) + field.0];
+		for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+			let (x, y) = (field.0 as i64 + dx, field.1 as i64 + dy);
+			if x < 0 || y < 0 || x >= *rows as i64 || y >= *cols as i64 {
+				continue;
+			}
+			let next = (y as usize * rows) + x as usize;
+			if area[next] != u64::MAX && distances[next] == usize::MAX {
+				distances[next] = distance + 1;
+				queue.push_back((x as usize, y as usize));
+			}
+		}
+	}
+	distances
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// The robot lets an obstacle pass which crosses its direct way
+	#[test]
+	fn waits_for_a_crossing_obstacle() {
+		let (rows, cols) = (5, 5);
+		let area = vec![0; rows * cols];
+		let obstacle = DynamicObstacle { trajectory: (0..5).map(|row| (row, 2)).collect(), radius: 0 };
+
+		let path = calculate(&area, &rows, &cols, (2, 0), (2, 4), std::slice::from_ref(&obstacle));
+		assert_eq!(path.first(), Some(&((2, 0), 0)));
+		assert_eq!(path.last(), Some(&((2, 4), 5)));
+		for (index, (field, time)) in path.iter().enumerate() {
+			assert_eq!(*time, index);
+			assert!(!obstacle.get_fields(*time, &rows, &cols).contains(field), "Collision on {:?} at {}", field, time);
+		}
+	}
+}
@@ -0,0 +1,246 @@
+//! Time-parameterization of a path for a differential-drive robot
+//!
+//! The paths of all algorithms are purely geometric. To track them with the robot, each point gets a time,
+//! a forward velocity `v` and an angular velocity `ω`:
+//!
+//! 1. The curvature on each point limits the velocity, so no wheel exceeds the maximum velocity of the robot
+//!    and the lateral acceleration stays below its limit.
+//! 2. A forward pass limits the acceleration from the start, where the robot stands still.
+//! 3. A backward pass limits the deceleration to the end, where the robot stands still again.
+//! 4. The time between two points follows from the distance and the mean velocity.
+//!
+//! All distances are in fields of the area and all times in seconds, like in `robot`.
+
+use std::io::{self, Write};
+use crate::robot::{normalize_angle, DiffDrive};
+
+/// Limits of the robot for the trajectory
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrajectoryConfig {
+	/// The robot with the maximum wheel velocity and acceleration
+	pub robot: DiffDrive,
+	/// Maximum lateral acceleration `v² * |κ|` in curves
+	pub max_lateral_acceleration: f64,
+}
+
+impl Default for TrajectoryConfig {
+	fn default() -> Self {
+		let robot = DiffDrive::default();
+		TrajectoryConfig {
+			robot,
+			max_lateral_acceleration: robot.max_acceleration,
+		}
+	}
+}
+
+/// A time-stamped state of the robot on the trajectory
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Sample {
+	/// Time since the start in seconds
+	pub time: f64,
+	/// Position on the rows
+	pub x: f64,
+	/// Position on the columns
+	pub y: f64,
+	/// Orientation in radians, `0` points to increasing rows
+	pub heading: f64,
+	/// Forward velocity
+	pub velocity: f64,
+	/// Angular velocity, positive turns from the rows to the columns
+	pub angular_velocity: f64,
+}
+
+/// A time-parameterized path
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Trajectory {
+	/// The samples with increasing time, from the start to the end
+	pub samples: Vec<Sample>,
+}
+
+impl Trajectory {
+	/// Returns the time to drive the whole trajectory
+	pub fn duration(&self) -> f64 {
+		self.samples.last().map_or(0.0, |sample| sample.time)
+	}
+
+	/// Returns the state of the robot at the given time, interpolated between the samples
+	///
+	/// Before the start the first and after the end the last sample is returned.
+	pub fn at(&self, time: f64) -> Option<Sample> {
+		let next = self.samples.partition_point(|sample| sample.time < time);
+		if next == 0 {
+			return self.samples.first().copied();
+		}
+		if next == self.samples.len() {
+			return self.samples.last().copied();
+		}
+
+		let (a, b) = (self.samples[next - 1], self.samples[next]);
+		let t = (time - a.time) / (b.time - a.time);
+		let lerp = |v0: f64, v1: f64| v0 + ((v1 - v0) * t);
+		Some(Sample {
+			time,
+			x: lerp(a.x, b.x),
+			y: lerp(a.y, b.y),
+			heading: normalize_angle(a.heading + (normalize_angle(b.heading - a.heading) * t)),
+			velocity: lerp(a.velocity, b.velocity),
+			angular_velocity: lerp(a.angular_velocity, b.angular_velocity),
+		})
+	}
+
+	/// Samples the trajectory with a fixed time step for the controller, the end is always included
+	pub fn resample(&self, time_step: f64) -> Trajectory {
+		let steps = (self.duration() / time_step).ceil() as usize;
+		let samples = (0..=steps)
+			.filter_map(|step| self.at(f64::min(step as f64 * time_step, self.duration())))
+			.collect();
+		Trajectory { samples }
+	}
+
+	/// Writes the trajectory as CSV with a header line
+	///
+	/// # Arguments:
+	///
+	/// * `writer` - Where to write to, e.g. a `File`
+	///
+	/// # Result:
+	///
+	/// Error from writing
+	pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+		writeln!(writer, "time,x,y,heading,velocity,angular_velocity")?;
+		for sample in &self.samples {
+			writeln!(writer, "{:.6},{:.6},{:.6},{:.6},{:.6},{:.6}",
+				sample.time, sample.x, sample.y, sample.heading, sample.velocity, sample.angular_velocity)?;
+		}
+		Ok(())
+	}
+}
+
+/// Time-parameterizes a path with the velocity, acceleration and curvature limits of the robot
+///
+/// # Arguments:
+///
+/// * `path` - The points from the start to the end, e.g. from `smoothing::smooth`. Repeated points are ignored
+/// * `config` - The limits of the robot
+///
+/// # Result:
+///
+/// The trajectory which starts and ends with the robot standing still, a single segment gets a sample in the middle
+pub fn get_trajectory(path: &[(f64, f64)], config: &TrajectoryConfig) -> Trajectory {
+	let mut points: Vec<(f64, f64)> = vec![];
+	for point in path {
+		if points.last().is_none_or(|last| f64::hypot(point.0 - last.0, point.1 - last.1) > 1e-9) {
+			points.push(*point);
+		}
+	}
+	if points.is_empty() {
+		return Trajectory::default();
+	}
+	// Both ends stand still, so a single segment would never be driven: accelerate to the middle and brake from there
+	if points.len() == 2 {
+		points.insert(1, ((points[0].0 + points[1].0) / 2.0, (points[0].1 + points[1].1) / 2.0));
+	}
+
+	let distances: Vec<f64> = points.windows(2).map(|pair| f64::hypot(pair[1].0 - pair[0].0, pair[1].1 - pair[0].1)).collect();
+	let curvatures: Vec<f64> = (0..points.len()).map(|index| get_curvature(&points, index)).collect();
+	let headings: Vec<f64> = (0..points.len()).map(|index| {
+		let (from, to) = if index + 1 < points.len() { (index, index + 1) } else { (index.saturating_sub(1), index) };
+		(points[to].1 - points[from].1).atan2(points[to].0 - points[from].0)
+	}).collect();
+
+	// Curvature limits: The outer wheel must not exceed the maximum velocity, `v * (1 + |κ| * b / 2) <= max`
+	let robot = &config.robot;
+	let mut velocities: Vec<f64> = curvatures.iter().map(|curvature| {
+		let wheel_limit = robot.max_velocity / (1.0 + (curvature.abs() * robot.wheel_distance / 2.0));
+		let lateral_limit = if curvature.abs() > 1e-9 { (config.max_lateral_acceleration / curvature.abs()).sqrt() } else { f64::INFINITY };
+		f64::min(wheel_limit, lateral_limit)
+	}).collect();
+	velocities[0] = 0.0;
+	velocities[points.len() - 1] = 0.0;
+
+	// Acceleration from the start and deceleration to the end: `v1² = v0² + 2 * a * s`
+	for index in 1..points.len() {
+		let reachable = ((velocities[index - 1] * velocities[index - 1]) + (2.0 * robot.max_acceleration * distances[index - 1])).sqrt();
+		velocities[index] = f64::min(velocities[index], reachable);
+	}
+	for index in (0..points.len() - 1).rev() {
+		let reachable = ((velocities[index + 1] * velocities[index + 1]) + (2.0 * robot.max_acceleration * distances[index])).sqrt();
+		velocities[index] = f64::min(velocities[index], reachable);
+	}
+
+	let mut time = 0.0;
+	let samples = (0..points.len()).map(|index| {
+		if index > 0 {
+			time += 2.0 * distances[index - 1] / (velocities[index - 1] + velocities[index]);
+		}
+		Sample {
+			time,
+			x: points[index].0,
+			y: points[index].1,
+			heading: headings[index],
+			velocity: velocities[index],
+			angular_velocity: velocities[index] * curvatures[index],
+		}
+	}).collect();
+	Trajectory { samples }
+}
+
+/// Returns the signed curvature on the point through the circle with both neighbours, `0` on the start and end
+fn get_curvature(points: &[(f64, f64)], index: usize) -> f64 {
+	if index == 0 || index + 1 >= points.len() {
+		return 0.0;
+	}
+	let (a, b, c) = (points[index - 1], points[index], points[index + 1]);
+	let cross = ((b.0 - a.0) * (c.1 - b.1)) - ((b.1 - a.1) * (c.0 - b.0));
+	let lengths = f64::hypot(b.0 - a.0, b.1 - a.1) * f64::hypot(c.0 - b.0, c.1 - b.1) * f64::hypot(c.0 - a.0, c.1 - a.1);
+	if lengths < 1e-12 {
+		0.0
+	} else {
+		2.0 * cross / lengths
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{thread_rng, Rng};
+
+	/// The trajectory has to stand still on both ends and respect the wheel velocity and the acceleration
+	#[test]
+	fn trajectory_respects_the_limits() {
+		let mut rng = thread_rng();
+		let config = TrajectoryConfig::default();
+		for _ in 0..100 {
+			let path: Vec<(f64, f64)> = (0..50).map(|_| (rng.gen_range(0.0..100.0), rng.gen_range(0.0..100.0))).collect();
+			let trajectory = get_trajectory(&path, &config);
+			let samples = &trajectory.samples;
+			assert_eq!(samples.len(), path.len());
+			assert_eq!(samples[0].velocity, 0.0);
+			assert_eq!(samples[samples.len() - 1].velocity, 0.0);
+
+			for sample in samples {
+				assert!(config.robot.is_admissible(sample.velocity, sample.angular_velocity), "{:?}", sample);
+			}
+			for pair in samples.windows(2) {
+				let dt = pair[1].time - pair[0].time;
+				assert!(dt > 0.0);
+				assert!((pair[1].velocity - pair[0].velocity).abs() / dt <= config.robot.max_acceleration + 1e-6, "{:?}", pair);
+			}
+		}
+	}
+
+	/// A straight line, e.g. from `smoothing::shortcut` without obstacles, has to be driven in a finite time
+	#[test]
+	fn two_points_are_driven() {
+		let config = TrajectoryConfig::default();
+		let trajectory = get_trajectory(&[(0.0, 0.0), (0.0, 0.0), (30.0, 40.0)], &config);
+		let samples = &trajectory.samples;
+		assert_eq!(samples.len(), 3);
+		assert_eq!((samples[1].x, samples[1].y), (15.0, 20.0));
+		assert!(samples[1].velocity > 0.0 && samples[1].velocity <= config.robot.max_velocity);
+		assert_eq!((samples[0].velocity, samples[2].velocity), (0.0, 0.0));
+		// Accelerate over 25 and brake over 25: `t = 2 * s / v`
+		let duration = 4.0 * 25.0 / samples[1].velocity;
+		assert!((samples[2].time - duration).abs() < 1e-9, "{}", samples[2].time);
+	}
+}
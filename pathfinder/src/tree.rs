@@ -1,36 +1,273 @@
-/// This module handles different Tree-Pathfinder algorithms
-///
-/// # Rapidly-Exploring RandomTree Algorithm
-///
-/// 1. A random point on the area is choosen.
-/// 2. For each existing node, check if in the direction to the random point, a node can be added (no obstacle)
-/// 3. Use the one new node which is nearest to the random point
-
+//! This module handles different Tree-Pathfinder algorithms
+//!
+//! # Rapidly-Exploring RandomTree Algorithm
+//!
+//! 1. A random point on the area is choosen.
+//! 2. The nearest node of the tree to the random point is searched in a KD-Tree.
+//! 3. From there, a new node is added in the direction to the random point if there is no obstacle in between.
+//!
+//! Instead of straight lines, the nodes of RRT and RRT* can be connected with Dubins or Reeds-Shepp curves,
+//! then each node has a heading as well, see [`RrtConfig::steering`].
+//!
+//! The nodes keep continuous positions in grid coordinates, the middle of a field is `(row + 0.5, col + 0.5)`.
+//! Only the collision checks and the returned path and tree are rasterized to the fields of the area.
+//! With a vector world in [`RrtConfig::world`], the collision checks are done exactly against its shapes instead.
+
+use std::str::FromStr;
+use std::sync::Arc;
 use crate::{Instant, thread_rng, Rng};
+use crate::sampler::{Sampler, Sampling};
+use crate::kd_tree::KdTree;
+use crate::curves::Steering;
+use crate::robot::Pose;
+use crate::world::World;
 
 const MAX_NODES: usize = 16383;
 const STEP_DISTANCE: f32 = 100.0;
-const END_POSITION: i32 = 5; // radius around the end to catch the end position
+const END_POSITION: usize = 5; // radius around the end to catch the end position
 const REWIRE_DISTANCE_FACTOR: i32 = 2; // Check all nodes to rewire in the radius: `REWIRE_DISTANCE_FACTOR * STEP_DISTANCE`
 
+/// The radius around a new node in which RRT* checks the nodes for rewiring
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RewireRadius {
+	/// A fixed radius
+	Fixed(f32),
+	/// A shrinking radius `gamma * sqrt(log(n) / n)` for `n` nodes, but at most the step distance
+	Gamma(f32),
+}
+
+impl FromStr for RewireRadius {
+	type Err = String;
+
+	/// Parses a radius like `200` or `gamma:300`
+	fn from_str(value: &str) -> Result<Self, Self::Err> {
+		let parse = |val: &str| val.parse::<f32>().map_err(|_| format!("Invalid rewire radius: {}", value));
+		match value.split_once(':') {
+			Some(("gamma", gamma)) => Ok(RewireRadius::Gamma(parse(gamma)?)),
+			Some(_) => Err(format!("Invalid rewire radius: {}", value)),
+			None => Ok(RewireRadius::Fixed(parse(value)?)),
+		}
+	}
+}
+
+/// Configuration for the tree algorithms
+#[derive(Debug, Clone, PartialEq)]
+pub struct RrtConfig {
+	/// Maximum distance between a node and a new one
+	pub step_distance: f32,
+	/// Maximum number of nodes, by default `area / step_distance` but at most 16383
+	pub max_nodes: Option<usize>,
+	/// Radius around the end to catch the end position
+	pub goal_radius: usize,
+	/// Radius for the rewiring of RRT*
+	pub rewire_radius: RewireRadius,
+	/// Stop the calculation as soon as the end is reached the first time
+	pub stop_on_first_solution: bool,
+	/// Strategy to choose the random points
+	pub sampling: Sampling,
+	/// Function to connect two nodes, RRT-Connect always uses straight lines
+	pub steering: Steering,
+	/// Obstacles to check the lines and curves against exactly instead of the fields of the area
+	pub world: Option<Arc<World>>,
+}
+
+impl Default for RrtConfig {
+	fn default() -> Self {
+		RrtConfig {
+			step_distance: STEP_DISTANCE,
+			max_nodes: None,
+			goal_radius: END_POSITION,
+			rewire_radius: RewireRadius::Fixed(STEP_DISTANCE * REWIRE_DISTANCE_FACTOR as f32),
+			stop_on_first_solution: false,
+			sampling: Sampling::default(),
+			steering: Steering::default(),
+			world: None,
+		}
+	}
+}
+
+impl RrtConfig {
+	/// Checks the values the tree algorithms can not work with, the algorithms panic with this message
+	pub fn validate(&self) -> Result<(), String> {
+		if !(self.step_distance > 0.0 && self.step_distance.is_finite()) {
+			return Err(format!("The step distance has to be greater than 0: {}", self.step_distance));
+		}
+		// A radius or gamma of 0 or NaN would silently turn off the rewiring
+		match self.rewire_radius {
+			RewireRadius::Fixed(radius) if !(radius > 0.0 && radius.is_finite()) => Err(format!("The rewire radius has to be greater than 0: {}", radius)),
+			RewireRadius::Gamma(gamma) if !(gamma > 0.0 && gamma.is_finite()) => Err(format!("The rewire gamma has to be greater than 0: {}", gamma)),
+			_ => Ok(()),
+		}
+	}
+
+	/// Returns the maximum number of nodes for an area with the given number of fields
+	pub fn get_max_nodes(&self, area_size: usize) -> usize {
+		self.max_nodes.unwrap_or_else(|| usize::min(MAX_NODES, (area_size as f32 / self.step_distance) as usize))
+	}
+
+	/// Returns the rewire radius for a tree with the given number of nodes
+	pub fn get_rewire_radius(&self, nodes: usize) -> f32 {
+		match self.rewire_radius {
+			RewireRadius::Fixed(radius) => radius,
+			RewireRadius::Gamma(gamma) => {
+				let nodes = usize::max(2, nodes) as f32;
+				f32::min(gamma * (nodes.ln() / nodes).sqrt(), self.step_distance)
+			},
+		}
+	}
+}
+
+/// An edge of the tree as a tuple of two positions: ((x0, y0), (x1, y1))
+pub type Edge = ((usize, usize), (usize, usize));
+
 /// The result of a tree algorithm
 #[derive(Debug)]
 pub struct TreeResult {
 	/// The path from start to the end
 	pub path: Vec<(usize, usize)>,
+	/// The continuous positions of the nodes on the path from the end to the start
+	pub waypoints: Vec<(f64, f64)>,
 	/// The tree as lines/tuples: ((x0, y0), (x1, y1))
-	pub tree: Vec<((usize, usize), (usize, usize))>,
+	pub tree: Vec<Edge>,
 }
 
 /// Inner type representing a node
 #[derive(Debug, Clone)]
 struct Node {
-	/// Coordinates
-	pos: (usize, usize),
+	/// Continuous coordinates in the area
+	pos: (f64, f64),
 	// Parent node index
 	parent: usize,
 	// Distance to the start
 	distance: f32,
+	// Heading on this node if the nodes are connected with curves
+	heading: f64,
+}
+
+impl Node {
+	/// Pose of this node
+	fn pose(&self) -> Pose {
+		Pose::new(self.pos.0, self.pos.1, self.heading)
+	}
+}
+
+/// Inner type representing a tree: All nodes, their children, the spatial index over them and how they are connected
+#[derive(Debug)]
+struct Tree {
+	/// All nodes, the root is the first one
+	nodes: Vec<Node>,
+	/// Indexes of the children for each node
+	children: Vec<Vec<usize>>,
+	/// KD-Tree for nearest neighbour and radius queries
+	index: KdTree,
+	/// Function to connect a node with its parent
+	steering: Steering,
+}
+
+impl Tree {
+	/// Creates a new tree with the root on the given position
+	fn new(root: (f64, f64), capacity: usize, steering: Steering) -> Self {
+		let mut tree = Tree {
+			nodes: Vec::with_capacity(capacity),
+			children: Vec::with_capacity(capacity),
+			index: KdTree::with_capacity(capacity),
+			steering,
+		};
+		tree.push(Node { pos: root, parent: 0, distance: 0.0, heading: 0.0 });
+		tree
+	}
+
+	/// Appends a node to the tree and the index and returns its index
+	fn push(&mut self, node: Node) -> usize {
+		let key = self.nodes.len();
+		if key > 0 {
+			self.children[node.parent].push(key);
+		}
+		self.index.insert(node.pos, key);
+		self.nodes.push(node);
+		self.children.push(vec![]);
+		key
+	}
+
+	/// Connects the node to a new parent and updates the distance of all its descendants
+	///
+	/// # Arguments:
+	///
+	/// * `key` - Index of the node to rewire
+	/// * `parent` - Index of the new parent
+	/// * `distance` - New distance of the node to the start over the new parent
+	fn rewire(&mut self, key: usize, parent: usize, distance: f32) {
+		let old_parent = self.nodes[key].parent;
+		self.children[old_parent].retain(|child| *child != key);
+		self.children[parent].push(key);
+		self.nodes[key].parent = parent;
+
+		// Propagate the difference through the whole subtree, the edges below the node stay the same
+		let difference = distance - self.nodes[key].distance;
+		let mut stack = vec![key];
+		while let Some(current) = stack.pop() {
+			self.nodes[current].distance += difference;
+			stack.extend_from_slice(&self.children[current]);
+		}
+	}
+
+	/// For the return value we need only the position as a tuple ((x0, y0), (x1, y1)) to draw the tree/network
+	///
+	/// Curves are split into one edge per field.
+	fn edges(&self) -> impl Iterator<Item = Edge> + '_ {
+		(0..self.nodes.len()).flat_map(|key| {
+			let fields = self.get_fields(key);
+			fields.windows(2).map(|pair| (pair[0], pair[1])).collect::<Vec<Edge>>()
+		})
+	}
+
+	/// Returns the fields from the node to its parent, both included
+	///
+	/// For straight lines these are just the two positions, curves are sampled with one field per step.
+	fn get_fields(&self, key: usize) -> Vec<(usize, usize)> {
+		let node = &self.nodes[key];
+		let parent = &self.nodes[node.parent];
+		if self.steering == Steering::Line || key == 0 {
+			return vec![to_field(node.pos), to_field(parent.pos)];
+		}
+
+		// All poses lie inside the area, the curves are checked before a node is added
+		let mut fields: Vec<(usize, usize)> = vec![];
+		self.steering.curve(&parent.pose(), &node.pose()).sample(1.0).iter().rev()
+			.map(|pose| (pose.x as usize, pose.y as usize))
+			.for_each(|field| if fields.last() != Some(&field) { fields.push(field) });
+		fields
+	}
+
+	/// Returns all positions from the given node back to the root of the tree
+	///
+	/// # Arguments:
+	///
+	/// * `index` - Index of the node to start from
+	///
+	/// # Result:
+	///
+	/// The positions from the node to the root, both included. Curves are sampled with one field per step.
+	fn get_branch(&self, index: usize) -> Vec<(usize, usize)> {
+		let mut result = vec![to_field(self.nodes[index].pos)];
+		let mut current = index;
+		while current != 0 {
+			result.extend(self.get_fields(current).into_iter().skip(1));
+			current = self.nodes[current].parent;
+		}
+		result
+	}
+
+	/// Returns the continuous positions of all nodes from the given one back to the root, both included
+	fn get_positions(&self, index: usize) -> Vec<(f64, f64)> {
+		let mut result = vec![self.nodes[index].pos];
+		let mut current = index;
+		while current != 0 {
+			current = self.nodes[current].parent;
+			result.push(self.nodes[current].pos);
+		}
+		result
+	}
 }
 
 /// Use the Rapidly-Exploring RandomTree Algorithm: RRT
@@ -42,69 +279,52 @@ struct Node {
 /// * `cols` - number of cols
 /// * `start` - start position (row, col)
 /// * `end` - end position (row, col)
+/// * `config` - Configuration like the step distance, number of nodes, sampling, ...
 ///
 /// # Result
 ///
 /// A tuple with:
 /// * A Vector of tuples where each tuple represents a waypoint
 /// * A Vector of tuples where each tuple represents an edge of the tree
-pub fn rrt_v1(area: &mut [u64], rows: &usize, cols: &usize, start:(usize, usize), end:(usize, usize)) -> TreeResult {
+///
+/// # Panics
+///
+/// If the configuration is invalid, see [`RrtConfig::validate`]
+pub fn rrt_v1(area: &mut [u64], rows: &usize, cols: &usize, start:(usize, usize), end:(usize, usize), config: &RrtConfig) -> TreeResult {
 	let benchmark = Instant::now();
 	let mut rng = thread_rng();
+	let mut sampler = Sampler::new(config.sampling);
 	let mut found_end = false;
 
 	// Configuration
-	let max_nodes = usize::min(MAX_NODES, (area.len() as f32 / STEP_DISTANCE) as usize);
-	let mut nodes: Vec<Node> = Vec::with_capacity(max_nodes);
-	let finish_range = get_range(end, END_POSITION);
-
-	// Initialize with the start node
-	nodes.push(Node {
-		pos: start,
-		parent: 0,
-		distance: 0.0,
-	});
-
-	loop {
-		// Loop until we have filled the whole nodes vector
-		if nodes.len() == max_nodes { break; }
-
-		let mut last_distance = f32::MAX;
-		let mut new_node = Node {
-			pos: (0, 0),
-			parent: 0,
-			distance: 0.0,
-		};
-
+	if let Err(err) = config.validate() { panic!("{}", err) }
+	let max_nodes = config.get_max_nodes(area.len());
+	let mut tree = Tree::new(to_position(start), max_nodes, config.steering);
+	let finish_range = get_range(end, config.goal_radius);
+	let goal = to_position(end);
+
+	// Loop until we have filled the whole nodes vector
+	while tree.nodes.len() < max_nodes {
 		// 1. Get a random point on the area where to give the direction
-		let direction_node = (rng.gen_range(0..*rows), rng.gen_range(0..*cols));
-
-		// 2. Find the nearest collision free Node
-		nodes.iter().enumerate().for_each(|(key, node)| {
-			// Do not grow the tree from the end
-			if node.pos != end {
-				// The new node to check
-				let (new_pos, distance) = get_new_position(node.pos, direction_node, STEP_DISTANCE);
-
-				// Check if the new node may be a valid one and update it
-				if last_distance > distance && is_collision_free(area, rows, node, new_pos) {
-					last_distance = distance;
-
-					new_node.parent = key;
-					new_node.pos = if is_in_range(new_pos, finish_range) {
-						end
-					} else {
-						new_pos
-					};
-				}
-			}
-		});
+		let direction_node = to_position(sampler.sample(&mut rng, area, rows, cols, end));
+
+		// 2. Find the nearest Node, do not grow the tree from the end
+		let Some((parent, _)) = tree.index.nearest_where(direction_node, |key| tree.nodes[key].pos != goal) else { continue };
+
+		// 3. Add the new node if there is no obstacle in between
+		if let Some((new_pos, heading)) = get_extension(area, rows, config, &tree.nodes[parent], direction_node, finish_range, goal) {
+			let distance = get_cost(&config.steering, &tree.nodes[parent], new_pos, heading);
+			tree.push(Node {
+				pos: new_pos,
+				parent,
+				distance,
+				heading,
+			});
 
-		if last_distance < f32::MAX {
-			nodes.push(new_node.clone());
-			if new_node.pos == end {
+			if new_pos == goal {
 				found_end = true;
-				println!("RRT-V1 End reached within {}: {:.6?}", nodes.len(), benchmark.elapsed());
+				println!("RRT-V1 End reached within {}: {:.6?}", tree.nodes.len(), benchmark.elapsed());
+				if config.stop_on_first_solution { break; }
 			}
 		}
 	}
@@ -112,21 +332,16 @@ pub fn rrt_v1(area: &mut [u64], rows: &usize, cols: &usize, start:(usize, usize)
 	if !found_end { println!("RRT-V1 Calc: No conneciton found"); }
 	println!("RRT-V1 Calc: {:.6?}", benchmark.elapsed());
 
-	// For the return value we need only the position as a tuple ((x0, y0), (x1, y1)) to draw the tree/network
-	let result_nodes = nodes.iter().map(|node| (node.pos, nodes[node.parent].pos)).collect();
-
 	// Return the tuple of the path-vector and tree-vector
-	TreeResult {
-		path: find_path("RRT-V1", end, &nodes),
-		tree: result_nodes,
-	}
+	find_path("RRT-V1", goal, &tree)
 }
 
 /// Use the Rapidly-Exploring RandomTree Algorithm: RRT*
 ///
 /// Version 2:
 ///
-/// Every new Node is connected to the neares Neighbour and not to the one from which directed node was calculated.
+/// Every new Node is connected to the node in the surrounding with the shortest path to the start and not to the one
+/// from which the directed node was calculated.
 /// After connection the new node, every node in a given area around is checked if it may be rewired
 /// with the new one to have a more direct conneciton to the start.
 ///
@@ -137,94 +352,46 @@ pub fn rrt_v1(area: &mut [u64], rows: &usize, cols: &usize, start:(usize, usize)
 /// * `cols` - number of cols
 /// * `start` - start position (row, col)
 /// * `end` - end position (row, col)
+/// * `config` - Configuration like the step distance, number of nodes, sampling, ...
 ///
 /// # Result
 ///
 /// A tuple with:
 /// * A Vector of tuples where each tuple represents a waypoint
 /// * A Vector of tuples where each tuple represents an edge of the tree
-pub fn rrt_v2(area: &mut [u64], rows: &usize, cols: &usize, start:(usize, usize), end:(usize, usize)) -> TreeResult {
+///
+/// # Panics
+///
+/// If the configuration is invalid, see [`RrtConfig::validate`]
+pub fn rrt_v2(area: &mut [u64], rows: &usize, cols: &usize, start:(usize, usize), end:(usize, usize), config: &RrtConfig) -> TreeResult {
 	let benchmark = Instant::now();
 	let mut rng = thread_rng();
+	let mut sampler = Sampler::new(config.sampling);
 	let mut found_end = false;
 
 	// Configuration
-	let max_nodes = usize::min(MAX_NODES, (area.len() as f32 / STEP_DISTANCE) as usize);
-	let mut nodes: Vec<Node> = Vec::with_capacity(max_nodes);
-	let finish_range = get_range(end, END_POSITION);
-
-	// Initialize with the start node
-	nodes.push(Node {
-		pos: start,
-		parent: 0,
-		distance: 0.0,
-	});
-
-	loop {
-		// Loop until we have filled the whole nodes vector
-		if nodes.len() == max_nodes { break; }
-
-		let mut last_distance = f32::MAX;
-		let mut new_node = Node {
-			pos: (0, 0),
-			parent: 0,
-			distance: 0.0,
-		};
-
+	if let Err(err) = config.validate() { panic!("{}", err) }
+	let max_nodes = config.get_max_nodes(area.len());
+	let mut tree = Tree::new(to_position(start), max_nodes, config.steering);
+	let finish_range = get_range(end, config.goal_radius);
+	let goal = to_position(end);
+
+	// Loop until we have filled the whole nodes vector
+	while tree.nodes.len() < max_nodes {
 		// 1. Get a random point on the area where to give the direction
-		let direction_node = (rng.gen_range(0..*rows), rng.gen_range(0..*cols));
-
-		// 2. Find the nearest collision free Node
-		nodes.iter().for_each(|node| {
-			// Do not grow the tree from the end
-			if node.pos != end {
-				// The new node to check
-				let (new_pos, distance) = get_new_position(node.pos, direction_node, STEP_DISTANCE);
-
-				// Check if the new node may be a valid one and update it
-				if last_distance > distance && is_collision_free(area, rows, node, new_pos) {
-					last_distance = distance;
-
-					new_node.pos = if is_in_range(new_pos, finish_range) {
-						end
-					} else {
-						new_pos
-					};
-				}
-			}
-		});
-
-		if last_distance < f32::MAX {
-			// Find the nearest node to connect to
-			last_distance = f32::MAX;
-			nodes.iter().enumerate().for_each(|(key, node)| {
-				let (_, _, distance) = get_distances(new_node.pos, node.pos, nodes[key].distance);
-				if last_distance > distance && is_collision_free(area, rows, node, new_node.pos) {
-					last_distance = distance;
-					new_node.parent = key;
-					new_node.distance = distance;
-				}
-			});
+		let direction_node = to_position(sampler.sample(&mut rng, area, rows, cols, end));
 
-			// Append the node to the list
-			nodes.push(new_node.clone());
-			let new_node_index = nodes.len() - 1;
-			let check_range = get_range(new_node.pos, (STEP_DISTANCE as i32) * REWIRE_DISTANCE_FACTOR);
-
-			// Rewire all nodes
-			nodes.iter_mut().for_each(|node| {
-				if node.pos != new_node.pos && is_in_range(node.pos, check_range) {
-					let (_, _, distance) = get_distances(new_node.pos, node.pos, new_node.distance);
-					if node.distance > distance && is_collision_free(area, rows, node, new_node.pos) {
-						node.parent = new_node_index;
-						node.distance = distance;
-					}
-				}
-			});
+		// 2. Find the nearest Node, do not grow the tree from the end
+		let Some((nearest, _)) = tree.index.nearest_where(direction_node, |key| tree.nodes[key].pos != goal) else { continue };
 
-			if new_node.pos == end {
+		// 3. Add the new node if there is no obstacle in between, connect and rewire it
+		if let Some((new_pos, heading)) = get_extension(area, rows, config, &tree.nodes[nearest], direction_node, finish_range, goal) {
+			insert_and_rewire(area, rows, config, &mut tree, nearest, new_pos, heading, goal);
+
+			if new_pos == goal {
 				found_end = true;
-				println!("RRT-V2 End reached within {}: {:.6?}", nodes.len(), benchmark.elapsed());
+				println!("RRT-V2 End reached within {}: {:.6?}", tree.nodes.len(), benchmark.elapsed());
+				if config.stop_on_first_solution { break; }
 			}
 		}
 	}
@@ -232,23 +399,19 @@ pub fn rrt_v2(area: &mut [u64], rows: &usize, cols: &usize, start:(usize, usize)
 	if !found_end { println!("RRT-V2 Calc: No conneciton found"); }
 	println!("RRT-V2 Calc: {:.6?}", benchmark.elapsed());
 
-	// For the return value we need only the position as a tuple ((x0, y0), (x1, y1)) to draw the tree/network
-	let result_nodes = nodes.iter().map(|node| (node.pos, nodes[node.parent].pos)).collect();
-
 	// Return the tuple of the path-vector and tree-vector
-	TreeResult {
-		path: find_path("RRT-V2", end, &nodes),
-		tree: result_nodes,
-	}
+	find_path("RRT-V2", goal, &tree)
 }
 
 
 /// Use the Rapidly-Exploring RandomTree Algorithm: RRT*
 ///
-/// Version 3:
+/// Version 3: Informed RRT*
 ///
-/// Based on Version 2 with an optimized node algorithm.
-/// After the end is reached, only nodes inside an ellipsis, defined by the maximum values of the path, are taken into account.
+/// Based on Version 2 with an informed sampling of the random points.
+/// After the end is reached, the random points are sampled uniformly inside the ellipse with the start and end as focal points
+/// and the cost of the best path as the sum of the focal distances. Only points in there can improve the current path.
+/// The ellipse shrinks each time a better path is found.
 ///
 /// # Arguments
 ///
@@ -257,169 +420,351 @@ pub fn rrt_v2(area: &mut [u64], rows: &usize, cols: &usize, start:(usize, usize)
 /// * `cols` - number of cols
 /// * `start` - start position (row, col)
 /// * `end` - end position (row, col)
+/// * `config` - Configuration like the step distance, number of nodes, sampling, ...
 ///
 /// # Result
 ///
 /// A tuple with:
 /// * A Vector of tuples where each tuple represents a waypoint
 /// * A Vector of tuples where each tuple represents an edge of the tree
-pub fn rrt_v3(area: &mut [u64], rows: &usize, cols: &usize, start:(usize, usize), end:(usize, usize)) -> TreeResult {
+///
+/// # Panics
+///
+/// If the configuration is invalid, see [`RrtConfig::validate`]
+pub fn rrt_v3(area: &mut [u64], rows: &usize, cols: &usize, start:(usize, usize), end:(usize, usize), config: &RrtConfig) -> TreeResult {
 	let benchmark = Instant::now();
 	let mut rng = thread_rng();
-	let mut found_end = false;
+	let mut sampler = Sampler::new(config.sampling);
 
 	// Configuration
-	let max_nodes = usize::min(MAX_NODES, (area.len() as f32 / STEP_DISTANCE) as usize);
-	let mut nodes: Vec<Node> = Vec::with_capacity(max_nodes);
-	let finish_range = get_range(end, END_POSITION);
-
-	// Initialize with the start node
-	nodes.push(Node {
-		pos: start,
-		parent: 0,
-		distance: 0.0,
-	});
+	if let Err(err) = config.validate() { panic!("{}", err) }
+	let max_nodes = config.get_max_nodes(area.len());
+	let mut tree = Tree::new(to_position(start), max_nodes, config.steering);
+	let finish_range = get_range(end, config.goal_radius);
+	let goal = to_position(end);
 
-	loop {
-		// Loop until we have filled the whole nodes vector
-		if nodes.len() == max_nodes { break; }
-
-		let mut last_distance = f32::MAX;
-		let mut new_node = Node {
-			pos: (0, 0),
-			parent: 0,
-			distance: 0.0,
-		};
+	// All nodes which are on the end position
+	let mut end_nodes: Vec<usize> = vec![];
 
+	// Loop until we have filled the whole nodes vector
+	while tree.nodes.len() < max_nodes {
 		// 1. Get a random point on the area where to give the direction
-		let direction_node = (rng.gen_range(0..*rows), rng.gen_range(0..*cols));
-
-		// Get the path from the end to the start
-		let path = if found_end { find_path("", end, &nodes) } else { vec![] };
-
-		// 2. Find the nearest collision free Node
-		nodes.iter().for_each(|node| {
-			// Do not grow the tree from the end
-			if node.pos != end && in_elliptic_region(start, end, &path, node.pos) {
-				// The new node to check
-				let (new_pos, distance) = get_new_position(node.pos, direction_node, STEP_DISTANCE);
-
-				// Check if the new node may be a valid one and update it
-				if last_distance > distance && is_collision_free(area, rows, node, new_pos) {
-					last_distance = distance;
-
-					new_node.pos = if is_in_range(new_pos, finish_range) {
-						end
-					} else {
-						new_pos
-					};
-				}
-			}
-		});
-
-		if last_distance < f32::MAX {
-			// Find the nearest node to connect to
-			last_distance = f32::MAX;
-			nodes.iter().enumerate().for_each(|(key, node)| {
-				let (_, _, distance) = get_distances(new_node.pos, node.pos, nodes[key].distance);
-				if last_distance > distance && is_collision_free(area, rows, node, new_node.pos) {
-					last_distance = distance;
-					new_node.parent = key;
-					new_node.distance = distance;
-				}
-			});
+		//    As soon as the end is reached, only inside the ellipse defined by the best path
+		let best_cost = end_nodes.iter()
+			.map(|key| tree.nodes[*key].distance)
+			.min_by(|a, b| a.total_cmp(b));
+		let direction_node = match best_cost {
+			Some(cost) => get_informed_position(&mut rng, rows, cols, to_position(start), goal, cost),
+			None => to_position(sampler.sample(&mut rng, area, rows, cols, end)),
+		};
 
-			// Append the node to the list
-			nodes.push(new_node.clone());
-			let new_node_index = nodes.len() - 1;
-			let check_range = get_range(new_node.pos, (STEP_DISTANCE as i32) * REWIRE_DISTANCE_FACTOR);
-
-			// Rewire all nodes
-			nodes.iter_mut().for_each(|node| {
-				if node.pos != new_node.pos && is_in_range(node.pos, check_range) {
-					let (_, _, distance) = get_distances(new_node.pos, node.pos, new_node.distance);
-					if node.distance > distance && is_collision_free(area, rows, node, new_node.pos) {
-						node.parent = new_node_index;
-						node.distance = distance;
-					}
-				}
-			});
+		// 2. Find the nearest Node, do not grow the tree from the end
+		let Some((nearest, _)) = tree.index.nearest_where(direction_node, |key| tree.nodes[key].pos != goal) else { continue };
 
-			if new_node.pos == end {
-				found_end = true;
-				println!("RRT-V3 End reached within {}: {:.6?}", nodes.len(), benchmark.elapsed());
+		// 3. Add the new node if there is no obstacle in between, connect and rewire it
+		if let Some((new_pos, heading)) = get_extension(area, rows, config, &tree.nodes[nearest], direction_node, finish_range, goal) {
+			let new_node_index = insert_and_rewire(area, rows, config, &mut tree, nearest, new_pos, heading, goal);
+
+			if new_pos == goal {
+				end_nodes.push(new_node_index);
+				println!("RRT-V3 End reached within {}: {:.6?}", tree.nodes.len(), benchmark.elapsed());
+				if config.stop_on_first_solution { break; }
 			}
 		}
 	}
 
-	if !found_end { println!("RRT-V3 Calc: No conneciton found"); }
+	if end_nodes.is_empty() { println!("RRT-V3 Calc: No conneciton found"); }
 	println!("RRT-V3 Calc: {:.6?}", benchmark.elapsed());
 
-	// For the return value we need only the position as a tuple ((x0, y0), (x1, y1)) to draw the tree/network
-	let result_nodes = nodes.iter().map(|node| (node.pos, nodes[node.parent].pos)).collect();
-
 	// Return the tuple of the path-vector and tree-vector
+	find_path("RRT-V3", goal, &tree)
+}
+
+/// Use the Rapidly-Exploring RandomTree Algorithm: RRT-Connect
+///
+/// Two trees are grown, one from the start and one from the end.
+/// In each iteration one tree is extended towards a random point. The other tree then greedily
+/// tries to connect to the new node by repeated steps in its direction until it is reached or an obstacle blocks.
+/// Afterwards both trees swap their roles. The calculation always stops as soon as both trees are connected.
+///
+/// # Arguments
+///
+/// * `area` - The play field as a one-dimensional vector
+/// * `rows` - number of rows
+/// * `cols` - number of cols
+/// * `start` - start position (row, col)
+/// * `end` - end position (row, col)
+/// * `config` - Configuration like the step distance, number of nodes, sampling, ...
+///
+/// # Result
+///
+/// A tuple with:
+/// * A Vector of tuples where each tuple represents a waypoint
+/// * A Vector of tuples where each tuple represents an edge of both trees
+///
+/// # Panics
+///
+/// If the configuration is invalid, see [`RrtConfig::validate`]
+pub fn rrt_connect(area: &mut [u64], rows: &usize, cols: &usize, start:(usize, usize), end:(usize, usize), config: &RrtConfig) -> TreeResult {
+	let benchmark = Instant::now();
+	let mut rng = thread_rng();
+	let mut sampler = Sampler::new(config.sampling);
+
+	// Configuration
+	if let Err(err) = config.validate() { panic!("{}", err) }
+	let max_nodes = config.get_max_nodes(area.len());
+
+	// The first tree grows from the start, the second one from the end
+	let mut trees: [Tree; 2] = [
+		Tree::new(to_position(start), max_nodes, Steering::Line),
+		Tree::new(to_position(end), max_nodes, Steering::Line),
+	];
+
+	// Index of the connecting nodes: (start-tree, end-tree)
+	let mut connection: Option<(usize, usize)> = None;
+	let mut active = 0;
+
+	while connection.is_none() && trees[0].nodes.len() + trees[1].nodes.len() < max_nodes {
+		// 1. Get a random point on the area where to give the direction, the goal is the root of the other tree
+		let goal = if active == 0 { end } else { start };
+		let direction_node = to_position(sampler.sample(&mut rng, area, rows, cols, goal));
+
+		// 2. Extend the active tree one step into the direction
+		if let Some(new_index) = extend_tree(area, rows, config, &mut trees[active], direction_node) {
+			// 3. Try to connect the other tree to the new node
+			let target = trees[active].nodes[new_index].pos;
+			let budget = max_nodes.saturating_sub(trees[0].nodes.len() + trees[1].nodes.len());
+			if let Some(reached) = connect_tree(area, rows, config, &mut trees[1 - active], target, budget) {
+				connection = if active == 0 { Some((new_index, reached)) } else { Some((reached, new_index)) };
+				println!("RRT-Connect Trees connected within {}: {:.6?}", trees[0].nodes.len() + trees[1].nodes.len(), benchmark.elapsed());
+			}
+		}
+
+		// 4. Swap the trees
+		active = 1 - active;
+	}
+
+	if connection.is_none() { println!("RRT-Connect Calc: No conneciton found"); }
+	println!("RRT-Connect Calc: {:.6?}", benchmark.elapsed());
+
+	// Path from the end over the connection back to the start
+	let path_benchmark = Instant::now();
+	let mut path = vec![];
+	let mut waypoints = vec![];
+	if let Some((start_index, end_index)) = connection {
+		path = trees[1].get_branch(end_index);
+		path.reverse();
+		path.extend(trees[0].get_branch(start_index).into_iter().skip(1));
+		waypoints = trees[1].get_positions(end_index);
+		waypoints.reverse();
+		waypoints.extend(trees[0].get_positions(start_index).into_iter().skip(1));
+	}
+	println!("RRT-Connect Path-Calculation: {:.6?}", path_benchmark.elapsed());
+	println!("RRT-Connect Path length: {}", path.len());
+	println!("RRT-Connect Tree Edges: {}", trees[0].nodes.len() + trees[1].nodes.len());
+
 	TreeResult {
-		path: find_path("RRT-V3", end, &nodes),
-		tree: result_nodes,
+		path,
+		waypoints,
+		tree: trees.iter().flat_map(|tree| tree.edges()).collect(),
 	}
 }
 
-/// Checks if a point lies on the elliptic plane defined by the rotated bounding box of the path.
+/// Calculates the new node from the given one into the direction of the random point
 ///
-/// The rotation is defined by the start and end points.
-/// The Ellipse is defined by the min and max values from the path, the bounding box these values define.
+/// If the new point is inside of the range around the end, the end point is taken instead.
 ///
 /// # Arguments:
 ///
-/// * `start` - Start-Point
-/// * `end` - End-Point
-/// * `path` - The path from the end to the start
-/// * `pos` - The position to check
+/// * `area` - The area as a vector of u64 where every obstacle is u64::MAX
+/// * `rows` - Number of rows, where rows x cols is the size of the area
+/// * `config` - Configuration of the algorithm
+/// * `node` - The node from which the tree grows
+/// * `direction` - The point in which the tree should grow
+/// * `finish_range` - The range around the end, a Tuple of ((x0, x1), (y0, y1))
+/// * `end` - The End-Position as a tuple (x, y) in the middle of its field
 ///
 /// # Result:
 ///
-/// If the point lies on an ellipsis defined by the bounding box of the path
-fn in_elliptic_region(start: (usize, usize), end: (usize, usize), path: &[(usize, usize)], pos: (usize, usize)) -> bool {
-	// If there is no path, the point lies on the area...
-	if path.len() == 0 {
-		return true;
-	}
+/// The position and heading of the new node or `None` if an obstacle is in the way
+fn get_extension(area: &[u64], rows: &usize, config: &RrtConfig, node: &Node, direction: (f64, f64), finish_range: ((usize, usize), (usize, usize)), end: (f64, f64)) -> Option<((f64, f64), f64)> {
+	let (new_pos, heading) = match config.steering {
+		Steering::Line => (get_new_position(node.pos, direction, config.step_distance).0, 0.0),
+		steering => {
+			// Follow the curve to the random point, heading away from the node, for one step
+			let (direction_x, direction_y, _) = get_distances(node.pos, direction, 0.0);
+			let target = Pose::new(direction.0, direction.1, direction_y.atan2(direction_x) as f64);
+			let pose = steering.curve(&node.pose(), &target).pose_at(config.step_distance as f64);
+			pose.field(rows, &(area.len() / rows))?;
+			((pose.x, pose.y), pose.heading)
+		},
+	};
+	let new_pos = if is_in_range(to_field(new_pos), finish_range) { end } else { new_pos };
 
-	let (dist_x, dist_y ,_) = get_distances(start, end, 0.0);
-	let cx = start.0 + (dist_x as usize / 2);
-	let cy = start.1 + (dist_y as usize / 2);
-	let angle = dist_y.atan2(dist_x);
+	if new_pos != node.pos && is_collision_free(area, rows, config.world.as_deref(), &config.steering, node, new_pos, heading) {
+		Some((new_pos, heading))
+	} else {
+		None
+	}
+}
 
-	let rotate = |pos: &(usize, usize)| {
-		let (x, y, d) = get_distances(*pos, (cx, cy), 0.0);
-		let alpha = y.atan2(x) + angle;
-		( (d * alpha.cos()) as usize, (d * alpha.sin()) as usize )
+/// Adds a new node to the RRT* tree
+///
+/// The new node is connected to the node in the surrounding with the shortest distance to the start.
+/// Afterwards every node in the surrounding is rewired to the new node if this shortens its distance to the start.
+/// The shorter distance is propagated to all descendants of a rewired node.
+///
+/// # Arguments:
+///
+/// * `area` - The area as a vector of u64 where every obstacle is u64::MAX
+/// * `rows` - Number of rows, where rows x cols is the size of the area
+/// * `config` - Configuration of the algorithm
+/// * `tree` - The tree to add the node to
+/// * `nearest` - Index of the nearest node, which is used as the parent if there is no better one
+/// * `new_pos` - Position of the new node
+/// * `heading` - Heading of the new node, only used if the nodes are connected with curves
+/// * `end` - The End-Position, no node is connected to the end
+///
+/// # Result:
+///
+/// The index of the new node
+#[allow(clippy::too_many_arguments)]
+fn insert_and_rewire(area: &[u64], rows: &usize, config: &RrtConfig, tree: &mut Tree, nearest: usize, new_pos: (f64, f64), heading: f64, end: (f64, f64)) -> usize {
+	let neighbours = tree.index.within(new_pos, config.get_rewire_radius(tree.nodes.len()));
+
+	// Find the node with the shortest distance to the start to connect to
+	let mut new_node = Node {
+		pos: new_pos,
+		parent: nearest,
+		distance: get_cost(&config.steering, &tree.nodes[nearest], new_pos, heading),
+		heading,
 	};
+	neighbours.iter().for_each(|key| {
+		let node = &tree.nodes[*key];
+		// A straight line is the shortest connection, curves are only calculated if they may be shorter
+		if node.pos == end || new_node.distance <= get_distances(node.pos, new_pos, node.distance).2 {
+			return;
+		}
+		let distance = get_cost(&config.steering, node, new_pos, heading);
+		if new_node.distance > distance && is_collision_free(area, rows, config.world.as_deref(), &config.steering, node, new_pos, heading) {
+			new_node.parent = *key;
+			new_node.distance = distance;
+		}
+	});
 
-	// rotate the position and move it to (0,0)
-	let rotated = rotate(&pos);
-
-	// The dimension of the rotated rectangle around the path: ( (x_min, y_min), (x_max, y_max) )
-	let mut max: ((usize, usize), (usize, usize)) = ((usize::max_value(), usize::max_value()), (0, 0));
-	path.iter().for_each(|p| {
-		let rotated = rotate(p);
-		max = (
-			( usize::min(max.0.0, rotated.0), usize::min(max.0.1, rotated.1) ),
-			( usize::max(max.1.0, rotated.0), usize::max(max.1.1, rotated.1) ),
-		);
+	// Append the node to the tree
+	let new_node_index = tree.push(new_node.clone());
+	if new_pos == end {
+		return new_node_index;
+	}
+
+	// Rewire all nodes in the surrounding, the distances of their children are updated as well
+	neighbours.iter().for_each(|key| {
+		let node = &tree.nodes[*key];
+		if node.pos != new_pos && node.distance > get_distances(new_pos, node.pos, new_node.distance).2 {
+			let distance = get_cost(&config.steering, &new_node, node.pos, node.heading);
+			if node.distance > distance && is_collision_free(area, rows, config.world.as_deref(), &config.steering, &new_node, node.pos, node.heading) {
+				tree.rewire(*key, new_node_index, distance);
+			}
+		}
 	});
 
-	// An ellipsis is defined by: x^2 / a^2 + y^2 / b^2 = 1
-	// for values above 1.0 the point lies not in/on the ellipsis-plane
-	let x = rotated.0 as f32;
-	let y = rotated.1 as f32;
-	let a = (max.1.0 - max.0.0) as f32 / 2.0;
-	let b = (max.1.1 - max.0.1) as f32 / 2.0;
+	new_node_index
+}
+
+/// Extends the tree by one step from the nearest node into the direction of the given point
+///
+/// # Arguments:
+///
+/// * `area` - The area as a vector of u64 where every obstacle is u64::MAX
+/// * `rows` - Number of rows, where rows x cols is the size of the area
+/// * `config` - Configuration of the algorithm
+/// * `tree` - The tree to extend
+/// * `direction` - The point in which the tree should grow
+///
+/// # Result:
+///
+/// The index of the new node or `None` if an obstacle is in the way
+fn extend_tree(area: &[u64], rows: &usize, config: &RrtConfig, tree: &mut Tree, direction: (f64, f64)) -> Option<usize> {
+	let (parent, _) = tree.index.nearest(direction)?;
+
+	let (new_pos, _) = get_new_position(tree.nodes[parent].pos, direction, config.step_distance);
+	if new_pos == tree.nodes[parent].pos || !is_collision_free(area, rows, config.world.as_deref(), &Steering::Line, &tree.nodes[parent], new_pos, 0.0) {
+		return None;
+	}
+
+	let (_, _, distance) = get_distances(tree.nodes[parent].pos, new_pos, tree.nodes[parent].distance);
+	Some(tree.push(Node {
+		pos: new_pos,
+		parent,
+		distance,
+		heading: 0.0,
+	}))
+}
+
+/// Greedily extends the tree into the direction of the target until it is reached, an obstacle blocks the way or the budget is used up
+///
+/// # Arguments:
+///
+/// * `area` - The area as a vector of u64 where every obstacle is u64::MAX
+/// * `rows` - Number of rows, where rows x cols is the size of the area
+/// * `config` - Configuration of the algorithm
+/// * `tree` - The tree to extend
+/// * `target` - The position to connect the tree to
+/// * `budget` - Maximum number of nodes to add
+///
+/// # Result:
+///
+/// The index of the node on the target position or `None` if the target could not be reached
+fn connect_tree(area: &[u64], rows: &usize, config: &RrtConfig, tree: &mut Tree, target: (f64, f64), budget: usize) -> Option<usize> {
+	for _ in 0..budget {
+		let new_index = extend_tree(area, rows, config, tree, target)?;
+		if tree.nodes[new_index].pos == target {
+			return Some(new_index);
+		}
+	}
+	None
+}
+
+/// Returns a random point uniformly distributed inside the ellipse defined by the start and end as focal points.
+///
+/// The sum of the distances from any point on the ellipse to both focal points is the cost of the best known path.
+/// Every point outside of this ellipse can not be part of a shorter path.
+///
+/// # Arguments:
+///
+/// * `rng` - The random generator to use
+/// * `rows` - Number of rows, where rows x cols is the size of the area
+/// * `cols` - Number of columns, where rows x cols is the size of the area
+/// * `start` - Start-Point, first focal point
+/// * `end` - End-Point, second focal point
+/// * `best_cost` - Cost of the best known path from the start to the end
+///
+/// # Result:
+///
+/// A random point inside the ellipse and inside the area
+fn get_informed_position<R: Rng>(rng: &mut R, rows: &usize, cols: &usize, start: (f64, f64), end: (f64, f64), best_cost: f32) -> (f64, f64) {
+	let (dist_x, dist_y, min_cost) = get_distances(start, end, 0.0);
+	let center = (start.0 as f32 + (dist_x / 2.0), start.1 as f32 + (dist_y / 2.0));
+	let (sin, cos) = dist_y.atan2(dist_x).sin_cos();
 
-	//println!("max: {:?} -> {:?}", a, b);
+	// Semi-major axis along the line start-end and the semi-minor axis orthogonal to it
+	let radius_major = best_cost / 2.0;
+	let radius_minor = f32::sqrt(f32::max(0.0, (best_cost * best_cost) - (min_cost * min_cost))) / 2.0;
 
-	(x.sqrt() / a.sqrt()) + (y.sqrt() / b.sqrt()) <= 1.0
+	loop {
+		// Uniform point in the unit circle, scaled to the ellipse, rotated and moved to the center
+		let radius = rng.gen::<f32>().sqrt();
+		let alpha = rng.gen::<f32>() * std::f32::consts::TAU;
+		let x = radius * alpha.cos() * radius_major;
+		let y = radius * alpha.sin() * radius_minor;
+		let pos_x = center.0 + (x * cos) - (y * sin);
+		let pos_y = center.1 + (x * sin) + (y * cos);
+
+		if pos_x >= 0.0 && pos_y >= 0.0 && (pos_x as usize) < *rows && (pos_y as usize) < *cols {
+			return (pos_x as f64, pos_y as f64);
+		}
+	}
 }
 
 /// Get a range tuple around the given position
@@ -432,15 +777,15 @@ fn in_elliptic_region(start: (usize, usize), end: (usize, usize), path: &[(usize
 /// # Result
 ///
 /// Two tuples representing the range: ((x0, x1), (y0, y1))
-fn get_range(pos: (usize, usize), range: i32) -> ((usize, usize), (usize, usize)) {
+fn get_range(pos: (usize, usize), range: usize) -> ((usize, usize), (usize, usize)) {
 	 (
 		(
-			i32::max(0, pos.0 as i32 - range) as usize,
-			pos.0 + range as usize
+			pos.0.saturating_sub(range),
+			pos.0.saturating_add(range)
 		),
 		(
-			i32::max(0, pos.1 as i32 - range) as usize,
-			pos.1 + range as usize
+			pos.1.saturating_sub(range),
+			pos.1.saturating_add(range)
 		),
 	)
 }
@@ -470,9 +815,9 @@ fn is_in_range(pos: (usize, usize), range: ((usize, usize), (usize, usize))) ->
 /// # Result
 ///
 /// A tuple with the three distances: (X, Y, Direct)
-fn get_distances(p1: (usize, usize), p2: (usize, usize), offset: f32) -> (f32, f32, f32) {
-	let direction_x = p2.0 as f32 - p1.0 as f32;
-	let direction_y = p2.1 as f32 - p1.1 as f32;
+fn get_distances(p1: (f64, f64), p2: (f64, f64), offset: f32) -> (f32, f32, f32) {
+	let direction_x = (p2.0 - p1.0) as f32;
+	let direction_y = (p2.1 - p1.1) as f32;
 	let distance = f32::sqrt((direction_x * direction_x) + (direction_y * direction_y)) + offset;
 
 	(direction_x, direction_y, distance)
@@ -490,7 +835,7 @@ fn get_distances(p1: (usize, usize), p2: (usize, usize), offset: f32) -> (f32, f
 ///
 /// A tuple represents the new point, step_size apart from the start.
 /// The third parameter represents the distance between the start and the direction point
-fn get_new_position(start: (usize, usize), direction: (usize, usize), step_size: f32) -> ((usize, usize), f32) {
+fn get_new_position(start: (f64, f64), direction: (f64, f64), step_size: f32) -> ((f64, f64), f32) {
 	let (direction_x, direction_y, distance) = get_distances(start, direction, 0.0);
 
 	// If the direction-point is nearer than the new point, use the direction-point
@@ -498,31 +843,80 @@ fn get_new_position(start: (usize, usize), direction: (usize, usize), step_size:
 		return (direction, distance);
 	}
 
-	let angle = direction_y.atan2(direction_x);
-	let new_x = (start.0 as f32 + (step_size * angle.cos())) as usize;
-	let new_y = (start.1 as f32 + (step_size * angle.sin())) as usize;
+	let angle = (direction_y as f64).atan2(direction_x as f64);
+	let new_x = start.0 + (step_size as f64 * angle.cos());
+	let new_y = start.1 + (step_size as f64 * angle.sin());
 	((new_x, new_y), distance)
 }
 
-/// Checks the area if between the two given points is an obstacle.
-/// This is done by simply "draw" a line between and check the line-pixels on the area.
+/// Checks the area or the world if between the node and the new position is an obstacle.
 ///
 /// # Arguments:
 ///
 /// * `area` - The area as a vector of u64 where every obstacle is u64::MAX
 /// * `rows` - Number of rows, where rows x cols is the size of the area
-/// * `node` - Node from where the line to check starts
+/// * `world` - The obstacles to check against exactly instead of the area
+/// * `steering` - Function to connect the node with the new position
+/// * `node` - Node from where the line or curve to check starts
 /// * `new_pos` - A tuple represents the end point
+/// * `heading` - Heading on the end point, only used for curves
 ///
 /// # Result
 ///
 /// Returns if there is an obstacle between the two points
-fn is_collision_free(area: &[u64], rows: &usize, node: &Node, new_pos: (usize, usize)) -> bool {
-	let p1 = node.pos;
-	let p2 = (new_pos.0, new_pos.1);
+fn is_collision_free(area: &[u64], rows: &usize, world: Option<&World>, steering: &Steering, node: &Node, new_pos: (f64, f64), heading: f64) -> bool {
+	match (world, steering) {
+		(None, Steering::Line) => is_line_free(area, rows, to_field(node.pos), to_field(new_pos)),
+		(None, steering) => steering.curve(&node.pose(), &Pose::new(new_pos.0, new_pos.1, heading)).is_free(area, rows, &(area.len() / rows)),
+		(Some(world), Steering::Line) => world.is_segment_free(node.pos, new_pos),
+		(Some(world), steering) => {
+			// The curve has to stay inside the area, each piece between two samples is checked as a line
+			let poses = steering.curve(&node.pose(), &Pose::new(new_pos.0, new_pos.1, heading)).sample(1.0);
+			poses.iter().all(|pose| pose.field(rows, &(area.len() / rows)).is_some())
+				&& poses.windows(2).all(|pair| world.is_segment_free((pair[0].x, pair[0].y), (pair[1].x, pair[1].y)))
+		},
+	}
+}
+
+/// Returns the distance from the start to the new position over the given node
+///
+/// # Arguments:
+///
+/// * `steering` - Function to connect the node with the new position
+/// * `node` - Node from where the line or curve starts
+/// * `new_pos` - A tuple represents the end point
+/// * `heading` - Heading on the end point, only used for curves
+///
+/// # Result
+///
+/// The distance of the node plus the length of the line or curve
+fn get_cost(steering: &Steering, node: &Node, new_pos: (f64, f64), heading: f64) -> f32 {
+	match steering {
+		Steering::Line => get_distances(node.pos, new_pos, node.distance).2,
+		steering => node.distance + steering.curve(&node.pose(), &Pose::new(new_pos.0, new_pos.1, heading)).length() as f32,
+	}
+}
 
-	let dx = if p1.0 > p2.0 { p1.0 - p2.0 } else { p2.0 - p1.0 } as i64;
-	let dy = if p1.1 > p2.1 { p1.1 - p2.1 } else { p2.1 - p1.1 } as i64;
+/// Checks the area if between the two given points is an obstacle.
+/// This is done by simply "draw" a line between and check the line-pixels on the area.
+///
+/// # Arguments:
+///
+/// * `area` - The area as a vector of u64 where every obstacle is u64::MAX
+/// * `rows` - Number of rows, where rows x cols is the size of the area
+/// * `p1` - A tuple represents the start point
+/// * `p2` - A tuple represents the end point
+///
+/// # Result
+///
+/// Returns if there is no obstacle between and on the two points
+pub fn is_line_free(area: &[u64], rows: &usize, p1: (usize, usize), p2: (usize, usize)) -> bool {
+	if area[(p2.1 * rows) + p2.0] == u64::MAX {
+		return false;
+	}
+
+	let dx = p1.0.abs_diff(p2.0) as i64;
+	let dy = p1.1.abs_diff(p2.1) as i64;
 
 	let sx = if p1.0 < p2.0 { 1 } else { -1 } as i64;
 	let sy = if p1.1 < p2.1 { 1 } else { -1 } as i64;
@@ -563,40 +957,149 @@ fn is_collision_free(area: &[u64], rows: &usize, node: &Node, new_pos: (usize, u
 /// # Arguments:
 ///
 /// * `algorithm` - The name of the Algorithm (for benchmark logging)
-/// * `end` - The End-Position as a tuple (x, y)
-/// * `nodes` - All the nodes of the tree
+/// * `end` - The End-Position as a tuple (x, y) in the middle of its field
+/// * `tree` - The tree with all nodes
 ///
 /// # Result:
 ///
-/// The Path from the end to the start as tuples of coordinates [(x, y)]
-fn find_path(algorithm: &str, end: (usize, usize), nodes: &[Node]) -> Vec<(usize, usize)> {
+/// The Path from the end to the start as fields and as continuous waypoints, together with the edges of the tree
+fn find_path(algorithm: &str, end: (f64, f64), tree: &Tree) -> TreeResult {
 	let benchmark = Instant::now();
-	let mut result = vec![];
-
-	// 1. Find the end in the tree, then go backwards
-	let finish = nodes.iter().find(|&node| node.pos == end).unwrap_or(&nodes[0]);
-	if finish.pos == end {
-		result.push(finish.pos);
-
-		// 2. loop until the start is reached and color the area
-		let mut parent = finish.parent;
-		loop {
-			let node = &nodes[parent];
-			result.push(node.pos);
-			if node.parent == 0 {
-				break;
+
+	// 1. Find the end with the shortest distance in the tree, then go backwards to the start
+	let finish = tree.nodes.iter().enumerate()
+		.filter(|(_, node)| node.pos == end)
+		.min_by(|a, b| a.1.distance.total_cmp(&b.1.distance))
+		.map(|(key, _)| key);
+	let (path, waypoints) = match finish {
+		Some(key) => (tree.get_branch(key), tree.get_positions(key)),
+		None => (vec![], vec![]),
+	};
+
+	if !algorithm.is_empty() {
+		println!("{} Path-Calculation: {:.6?}", algorithm, benchmark.elapsed());
+		println!("{} Path length: {}", algorithm, path.len());
+		println!("{} Tree Edges: {}", algorithm, tree.nodes.len());
+	}
+	TreeResult {
+		path,
+		waypoints,
+		tree: tree.edges().collect(),
+	}
+}
+
+/// Returns the continuous position in the middle of the given field
+fn to_position(field: (usize, usize)) -> (f64, f64) {
+	(field.0 as f64 + 0.5, field.1 as f64 + 0.5)
+}
+
+/// Returns the field a continuous position lies on
+fn to_field(pos: (f64, f64)) -> (usize, usize) {
+	(f64::max(0.0, pos.0) as usize, f64::max(0.0, pos.1) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rand::rngs::StdRng;
+	use rand::SeedableRng;
+
+	/// After rewiring, the distance of every node has to be the sum of all edges back to the start
+	#[test]
+	fn rewire_updates_all_distances() {
+		let rows = 300;
+		let cols = 300;
+		let start = (10, 10);
+		let end = (290, 280);
+
+		// A wall with a gap on the bottom
+		let mut area = vec![0; rows * cols];
+		for col in 0..250 {
+			for row in 140..150 {
+				area[(col * rows) + row] = u64::MAX;
 			}
-			parent = node.parent;
 		}
-		result.push(nodes[0].pos);
+
+		let mut rng = thread_rng();
+		let config = RrtConfig::default();
+		let mut tree = Tree::new(to_position(start), 2000, config.steering);
+		let finish_range = get_range(end, config.goal_radius);
+		let goal = to_position(end);
+		while tree.nodes.len() < 2000 {
+			let direction_node = to_position((rng.gen_range(0..rows), rng.gen_range(0..cols)));
+			let Some((nearest, _)) = tree.index.nearest_where(direction_node, |key| tree.nodes[key].pos != goal) else { continue };
+			if let Some((new_pos, heading)) = get_extension(&area, &rows, &config, &tree.nodes[nearest], direction_node, finish_range, goal) {
+				insert_and_rewire(&area, &rows, &config, &mut tree, nearest, new_pos, heading, goal);
+			}
+		}
+
+		for (key, node) in tree.nodes.iter().enumerate() {
+			let mut distance = 0.0;
+			let mut current = key;
+			while current != 0 {
+				let parent = tree.nodes[current].parent;
+				distance += get_distances(tree.nodes[parent].pos, tree.nodes[current].pos, 0.0).2;
+				current = parent;
+			}
+			assert!((node.distance - distance).abs() < 0.01, "Node {} has distance {} but the edges sum up to {}", key, node.distance, distance);
+			assert!(tree.children[node.parent].contains(&key) || key == 0);
+		}
 	}
 
-	if algorithm.len() > 0 {
-		println!("{} Path-Calculation: {:.6?}", algorithm, benchmark.elapsed());
-		println!("{} Path length: {}", algorithm, result.len());
-		println!("{} Tree Edges: {}", algorithm, nodes.len());
+	/// A goal range at the border must not overflow
+	#[test]
+	fn goal_range_is_clamped() {
+		assert_eq!(get_range((2, usize::MAX), 5), ((0, 7), (usize::MAX - 5, usize::MAX)));
 	}
-	result
-}
 
+	/// A step distance of 0 would never add a node
+	#[test]
+	#[should_panic(expected = "The step distance has to be greater than 0")]
+	fn invalid_config_is_rejected() {
+		assert!(RrtConfig::default().validate().is_ok());
 
+		let config = RrtConfig { step_distance: 0.0, ..RrtConfig::default() };
+		assert!(config.validate().is_err());
+		rrt_v1(&mut vec![0; 100], &10, &10, (0, 0), (9, 9), &config);
+	}
+
+	/// Connecting the trees must not exceed the node limit, a far target is only reached with enough budget
+	#[test]
+	fn connect_tree_respects_the_budget() {
+		let area = vec![0; 100 * 100];
+		let config = RrtConfig { step_distance: 5.0, ..RrtConfig::default() };
+		let target = to_position((90, 90));
+
+		let mut tree = Tree::new(to_position((0, 0)), 10, Steering::Line);
+		assert_eq!(connect_tree(&area, &100, &config, &mut tree, target, 3), None);
+		assert_eq!(tree.nodes.len(), 4);
+
+		let mut tree = Tree::new(to_position((0, 0)), 1000, Steering::Line);
+		let reached = connect_tree(&area, &100, &config, &mut tree, target, 1000).unwrap();
+		assert_eq!(tree.nodes[reached].pos, target);
+		assert!(tree.nodes.len() > 4);
+	}
+
+	/// Informed samples lie inside the ellipse of the best cost and inside the area, on both sides of the line start-end
+	#[test]
+	fn informed_samples_are_inside_the_ellipse() {
+		let mut rng = StdRng::seed_from_u64(1);
+		// The ellipse reaches over the columns of the area
+		let (rows, cols) = (100, 70);
+		let (start, end) = ((20.0, 30.0), (80.0, 70.0));
+		let best_cost = 1.3 * f64::hypot(end.0 - start.0, end.1 - start.1);
+
+		let mut sides = (0, 0);
+		for _ in 0..2000 {
+			let pos = get_informed_position(&mut rng, &rows, &cols, start, end, best_cost as f32);
+			let cost = f64::hypot(pos.0 - start.0, pos.1 - start.1) + f64::hypot(pos.0 - end.0, pos.1 - end.1);
+			assert!(cost <= best_cost + 1e-3, "{:?} costs {} instead of at most {}", pos, cost, best_cost);
+			assert!(pos.0 >= 0.0 && pos.1 >= 0.0 && (pos.0 as usize) < rows && (pos.1 as usize) < cols, "{:?} is outside of the area", pos);
+
+			// Side of the line start-end by the cross product
+			let cross = ((end.0 - start.0) * (pos.1 - start.1)) - ((end.1 - start.1) * (pos.0 - start.0));
+			if cross > 0.0 { sides.0 += 1 } else { sides.1 += 1 }
+		}
+		assert!(sides.0 > 500 && sides.1 > 500, "The samples are not spread over the minor axis: {:?}", sides);
+	}
+}
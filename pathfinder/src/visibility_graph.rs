@@ -0,0 +1,257 @@
+//! Visibility graph for a vector world
+//!
+//! The shortest path around polygonal obstacles only bends on their corners. The corners of all shapes are moved
+//! outwards by the inflation, so a path can pass them without touching the shape:
+//!
+//! * Polygons: Each corner is moved along the bisector of its edges.
+//! * Circles: A regular polygon around the circle.
+//! * Segments: The corners of the rectangle around the segment.
+//!
+//! The start, the end and all inflated corners which lie inside the area and outside of the obstacles are the nodes of the graph.
+//! Two nodes are connected if the direct line between them keeps the inflation to all shapes, or at least the distance
+//! of its ends if the start or the end lies nearer to a shape. A* with the direct distance to the end as the heuristic
+//! finds the shortest path in this graph, which is the optimal path for a point robot with the inflation as clearance.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::f64::consts::TAU;
+
+use crate::Instant;
+use crate::smoothing::to_fields;
+use crate::tree::Edge;
+use crate::world::{Shape, World};
+
+const CIRCLE_CORNERS: usize = 16; // Number of corners of the polygon around a circle
+const TOLERANCE: f64 = 1e-6; // Edges between neighbouring corners keep exactly the inflation, up to rounding errors
+
+/// The result of the visibility graph
+#[derive(Debug)]
+pub struct VisibilityGraphResult {
+	/// The path from the end to the start as fields
+	pub path: Vec<(usize, usize)>,
+	/// The corners on the path from the end to the start
+	pub waypoints: Vec<(f64, f64)>,
+	/// Length of the path, `f64::INFINITY` if the end is not reachable
+	pub length: f64,
+	/// All edges of the graph as lines/tuples: ((x0, y0), (x1, y1))
+	pub graph: Vec<Edge>,
+}
+
+/// Entry of the open list, ordered by the lowest estimation first
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Open {
+	estimate: f64,
+	node: usize,
+}
+
+impl Eq for Open {}
+
+impl Ord for Open {
+	fn cmp(&self, other: &Self) -> Ordering {
+		other.estimate.total_cmp(&self.estimate)
+	}
+}
+
+impl PartialOrd for Open {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+/// Calculates the shortest path through the visibility graph of the world
+///
+/// # Arguments
+///
+/// * `world` - The obstacles
+/// * `rows` - number of rows
+/// * `cols` - number of cols
+/// * `start` - start position (row, col), the path starts in the middle of the field
+/// * `end` - end position (row, col), the path ends in the middle of the field
+/// * `inflation` - Distance of the corners to the shapes, about the clearance of the path. Has to be bigger than `0`
+///
+/// # Result
+///
+/// The shortest path, its length and the whole graph
+pub fn calculate(world: &World, rows: &usize, cols: &usize, start: (usize, usize), end: (usize, usize), inflation: f64) -> VisibilityGraphResult {
+	let benchmark = Instant::now();
+	let start_pos = (start.0 as f64 + 0.5, start.1 as f64 + 0.5);
+	let end_pos = (end.0 as f64 + 0.5, end.1 as f64 + 0.5);
+
+	// 1. Nodes: The start, the end and all usable corners
+	let mut nodes = vec![start_pos, end_pos];
+	nodes.extend(world.shapes.iter()
+		.flat_map(|shape| get_corners(shape, inflation))
+		.filter(|pos| pos.0 >= 0.0 && pos.1 >= 0.0 && pos.0 < *rows as f64 && pos.1 < *cols as f64)
+		.filter(|pos| world.signed_distance(*pos) >= inflation * 0.5));
+
+	// 2. Edges: All pairs of nodes with a free direct line which keeps the clearance
+	let clearances: Vec<f64> = nodes.iter().map(|pos| f64::min(inflation, world.signed_distance(*pos)) - TOLERANCE).collect();
+	let mut neighbours: Vec<Vec<(usize, f64)>> = vec![vec![]; nodes.len()];
+	for a in 0..nodes.len() {
+		for b in a + 1..nodes.len() {
+			if world.is_segment_free(nodes[a], nodes[b]) && world.segment_distance(nodes[a], nodes[b]) >= f64::min(clearances[a], clearances[b]) {
+				let distance = f64::hypot(nodes[b].0 - nodes[a].0, nodes[b].1 - nodes[a].1);
+				neighbours[a].push((b, distance));
+				neighbours[b].push((a, distance));
+			}
+		}
+	}
+	println!("Visibility-Graph Graph: {} nodes: {:.6?}", nodes.len(), benchmark.elapsed());
+
+	// 3. A* from the start to the end
+	let heuristic = |node: usize| f64::hypot(end_pos.0 - nodes[node].0, end_pos.1 - nodes[node].1);
+	let mut distances = vec![f64::INFINITY; nodes.len()];
+	let mut parents = vec![usize::MAX; nodes.len()];
+	let mut open: BinaryHeap<Open> = BinaryHeap::new();
+	distances[0] = 0.0;
+	open.push(Open { estimate: heuristic(0), node: 0 });
+
+	while let Some(Open { estimate, node }) = open.pop() {
+		if node == 1 {
+			break;
+		}
+		if estimate > distances[node] + heuristic(node) {
+			continue;
+		}
+		for (next, distance) in &neighbours[node] {
+			let distance = distances[node] + distance;
+			if distance < distances[*next] {
+				distances[*next] = distance;
+				parents[*next] = node;
+				open.push(Open { estimate: distance + heuristic(*next), node: *next });
+			}
+		}
+	}
+
+	if distances[1].is_infinite() { println!("Visibility-Graph Calc: No conneciton found"); }
+	println!("Visibility-Graph Calc: {:.6?}", benchmark.elapsed());
+
+	// Path from the end back to the start
+	let path_benchmark = Instant::now();
+	let mut waypoints = vec![];
+	if distances[1].is_finite() {
+		let mut current = 1;
+		waypoints.push(nodes[current]);
+		while current != 0 {
+			current = parents[current];
+			waypoints.push(nodes[current]);
+		}
+	}
+	let points: Vec<(f64, f64)> = waypoints.windows(2)
+		.flat_map(|pair| {
+			let samples = usize::max(1, (f64::hypot(pair[1].0 - pair[0].0, pair[1].1 - pair[0].1) * 2.0).ceil() as usize);
+			(0..=samples).map(move |i| {
+				let t = i as f64 / samples as f64;
+				(pair[0].0 + ((pair[1].0 - pair[0].0) * t), pair[0].1 + ((pair[1].1 - pair[0].1) * t))
+			})
+		})
+		.collect();
+	let path = to_fields(&points);
+
+	let graph: Vec<Edge> = neighbours.iter().enumerate()
+		.flat_map(|(a, list)| list.iter().filter(move |(b, _)| a < *b).map(move |(b, _)| (a, *b)))
+		.map(|(a, b)| (to_field(nodes[a], rows, cols), to_field(nodes[b], rows, cols)))
+		.collect();
+
+	println!("Visibility-Graph Path-Calculation: {:.6?}", path_benchmark.elapsed());
+	println!("Visibility-Graph Path length: {}", path.len());
+	println!("Visibility-Graph Graph Edges: {}", graph.len());
+
+	VisibilityGraphResult {
+		path,
+		waypoints,
+		length: distances[1],
+		graph,
+	}
+}
+
+/// Returns the corners of the shape, moved outwards by the inflation
+fn get_corners(shape: &Shape, inflation: f64) -> Vec<(f64, f64)> {
+	match shape {
+		Shape::Polygon(corners) => {
+			// Counter-clockwise polygons have the outside on the right of each edge
+			let area: f64 = (0..corners.len())
+				.map(|i| {
+					let (a, b) = (corners[i], corners[(i + 1) % corners.len()]);
+					(a.0 * b.1) - (b.0 * a.1)
+				})
+				.sum();
+			let orientation = if area >= 0.0 { 1.0 } else { -1.0 };
+			let normal = |a: (f64, f64), b: (f64, f64)| {
+				let length = f64::hypot(b.0 - a.0, b.1 - a.1);
+				(orientation * (b.1 - a.1) / length, orientation * (a.0 - b.0) / length)
+			};
+
+			(0..corners.len()).map(|i| {
+				let previous = corners[(i + corners.len() - 1) % corners.len()];
+				let (corner, next) = (corners[i], corners[(i + 1) % corners.len()]);
+				let (n1, n2) = (normal(previous, corner), normal(corner, next));
+				let bisector_length = f64::hypot(n1.0 + n2.0, n1.1 + n2.1);
+				if bisector_length < 1e-9 {
+					return (corner.0 + (n1.0 * inflation), corner.1 + (n1.1 * inflation));
+				}
+				let bisector = ((n1.0 + n2.0) / bisector_length, (n1.1 + n2.1) / bisector_length);
+				// Both edges are `inflation` away, but at most twice as far on sharp corners
+				let distance = inflation / f64::max(0.5, (bisector.0 * n1.0) + (bisector.1 * n1.1));
+				(corner.0 + (bisector.0 * distance), corner.1 + (bisector.1 * distance))
+			}).collect()
+		},
+		Shape::Circle { center, radius } => {
+			// The edges of the polygon touch the inflated circle
+			let distance = (radius + inflation) / (TAU / CIRCLE_CORNERS as f64 / 2.0).cos();
+			(0..CIRCLE_CORNERS).map(|i| {
+				let angle = TAU * i as f64 / CIRCLE_CORNERS as f64;
+				(center.0 + (distance * angle.cos()), center.1 + (distance * angle.sin()))
+			}).collect()
+		},
+		Shape::Segment(a, b) => {
+			let length = f64::hypot(b.0 - a.0, b.1 - a.1);
+			let (dx, dy) = if length < 1e-9 { (1.0, 0.0) } else { ((b.0 - a.0) / length, (b.1 - a.1) / length) };
+			let (along, across) = ((dx * inflation, dy * inflation), (-dy * inflation, dx * inflation));
+			vec![
+				(a.0 - along.0 + across.0, a.1 - along.1 + across.1),
+				(a.0 - along.0 - across.0, a.1 - along.1 - across.1),
+				(b.0 + along.0 + across.0, b.1 + along.1 + across.1),
+				(b.0 + along.0 - across.0, b.1 + along.1 - across.1),
+			]
+		},
+	}
+}
+
+/// Returns the field of a position inside the area
+fn to_field(pos: (f64, f64), rows: &usize, cols: &usize) -> (usize, usize) {
+	(usize::min(rows - 1, f64::max(0.0, pos.0) as usize), usize::min(cols - 1, f64::max(0.0, pos.1) as usize))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Around a square, the shortest path goes over its two corners
+	#[test]
+	fn shortest_path_around_a_square() {
+		let world: World = "polygon 10 10 20 10 20 20 10 20".parse().unwrap();
+		let result = calculate(&world, &30, &30, (5, 15), (25, 15), 0.01);
+
+		let optimum = f64::hypot(4.5, 4.5) + 10.0 + f64::hypot(5.5, 4.5);
+		assert!((result.length - optimum).abs() < 0.1, "Length {} instead of {}", result.length, optimum);
+		assert_eq!(result.waypoints.len(), 4);
+		assert_eq!(result.path.first(), Some(&(25, 15)));
+		assert_eq!(result.path.last(), Some(&(5, 15)));
+	}
+
+	/// Small circles next to the shortest lines on both sides: The path keeps the inflation to them instead of only not touching it
+	#[test]
+	fn edges_keep_the_inflation() {
+		let world: World = "polygon 10 10 20 10 20 20 10 20\ncircle 4.5 11.6 0.5\ncircle 4.5 19.4 0.5".parse().unwrap();
+		let inflation = 2.0;
+		let result = calculate(&world, &30, &30, (2, 15), (25, 15), inflation);
+
+		assert!(result.length.is_finite());
+		for pair in result.waypoints.windows(2) {
+			let ends = f64::min(world.signed_distance(pair[0]), world.signed_distance(pair[1]));
+			let distance = world.segment_distance(pair[0], pair[1]);
+			assert!(distance >= f64::min(inflation, ends) - 1e-6, "{:?} is only {} away", pair, distance);
+		}
+	}
+}
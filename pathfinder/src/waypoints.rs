@@ -0,0 +1,239 @@
+//! Reduces a path to a handful of waypoints for the path follower of the robot
+//!
+//! Like the EEROS `PathPlanner::add(x, y, turn_radius)`, every waypoint has a position and a radius
+//! with which the robot turns around the corner.
+//!
+//! 1. Consecutive steps in the same direction are merged (collinear merge).
+//! 2. Ramer-Douglas-Peucker keeps only the waypoints which deviate more than a tolerance from the direct line.
+//!    A direct line is only taken if it is free of obstacles as well.
+//! 3. On each corner, the largest turn radius up to a maximum is suggested where the arc is free of obstacles.
+
+use std::f64::consts::PI;
+use crate::robot::normalize_angle;
+use crate::tree::is_line_free;
+
+/// A waypoint for the path follower of the robot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Waypoint {
+	/// Position on the rows, in the middle of the field
+	pub x: f64,
+	/// Position on the columns, in the middle of the field
+	pub y: f64,
+	/// Radius to turn around this waypoint, `0` on the start, end and where no arc is free
+	pub turn_radius: f64,
+}
+
+/// Removes all waypoints where the path does not change its direction
+///
+/// # Arguments:
+///
+/// * `path` - The path as fields, in any direction
+///
+/// # Result:
+///
+/// The path with only the first, the last and the corner waypoints
+pub fn merge_collinear(path: &[(usize, usize)]) -> Vec<(usize, usize)> {
+	let mut result: Vec<(usize, usize)> = vec![];
+	for pos in path {
+		if result.last() == Some(pos) {
+			continue;
+		}
+		if result.len() >= 2 {
+			let a = result[result.len() - 2];
+			let b = result[result.len() - 1];
+			let (ux, uy) = (b.0 as i64 - a.0 as i64, b.1 as i64 - a.1 as i64);
+			let (vx, vy) = (pos.0 as i64 - b.0 as i64, pos.1 as i64 - b.1 as i64);
+			// Same direction if the cross product is zero and the dot product positive
+			if (ux * vy) - (uy * vx) == 0 && (ux * vx) + (uy * vy) > 0 {
+				result.pop();
+			}
+		}
+		result.push(*pos);
+	}
+	result
+}
+
+/// Simplifies the path with the Ramer-Douglas-Peucker algorithm
+///
+/// A part of the path is replaced by the direct line between its ends if no waypoint deviates more than the tolerance
+/// from it and the line is free of obstacles. Otherwise it is split on the waypoint with the largest deviation.
+///
+/// # Arguments:
+///
+/// * `area` - The area as a vector of u64 where every obstacle is u64::MAX
+/// * `rows` - Number of rows, where rows x cols is the size of the area
+/// * `path` - The path as fields, in any direction
+/// * `tolerance` - Maximum distance in fields of a removed waypoint to the line
+///
+/// # Result:
+///
+/// The remaining waypoints, the first and last one are kept
+pub fn simplify(area: &[u64], rows: &usize, path: &[(usize, usize)], tolerance: f64) -> Vec<(usize, usize)> {
+	if path.len() < 3 {
+		return path.to_vec();
+	}
+
+	let mut keep = vec![false; path.len()];
+	keep[0] = true;
+	keep[path.len() - 1] = true;
+
+	let mut stack = vec![(0, path.len() - 1)];
+	while let Some((first, last)) = stack.pop() {
+		if last - first < 2 {
+			continue;
+		}
+		let (index, deviation) = (first + 1..last)
+			.map(|index| (index, get_deviation(path[index], path[first], path[last])))
+			.max_by(|a, b| a.1.total_cmp(&b.1))
+			.unwrap_or((first + 1, 0.0));
+
+		if deviation > tolerance || !is_line_free(area, rows, path[first], path[last]) {
+			keep[index] = true;
+			stack.push((first, index));
+			stack.push((index, last));
+		}
+	}
+
+	path.iter().zip(keep).filter(|(_, keep)| *keep).map(|(pos, _)| *pos).collect()
+}
+
+/// Reduces the path to waypoints with a turn radius on each corner
+///
+/// # Arguments:
+///
+/// * `area` - The area as a vector of u64 where every obstacle is u64::MAX
+/// * `rows` - Number of rows, where rows x cols is the size of the area
+/// * `cols` - Number of columns, where rows x cols is the size of the area
+/// * `path` - The path as fields, the waypoints have the same order
+/// * `tolerance` - Maximum distance in fields of a removed waypoint to the simplified path
+/// * `max_radius` - Maximum turn radius in fields
+///
+/// # Result:
+///
+/// The waypoints with the suggested turn radius
+pub fn get_waypoints(area: &[u64], rows: &usize, cols: &usize, path: &[(usize, usize)], tolerance: f64, max_radius: f64) -> Vec<Waypoint> {
+	let points = simplify(area, rows, &merge_collinear(path), tolerance);
+	let positions: Vec<(f64, f64)> = points.iter().map(|pos| (pos.0 as f64 + 0.5, pos.1 as f64 + 0.5)).collect();
+
+	(0..positions.len()).map(|index| {
+		let turn_radius = if index == 0 || index + 1 == positions.len() {
+			0.0
+		} else {
+			get_turn_radius(area, rows, cols, positions[index - 1], positions[index], positions[index + 1], max_radius)
+		};
+		Waypoint { x: positions[index].0, y: positions[index].1, turn_radius }
+	}).collect()
+}
+
+/// Returns the distance of the point to the line through `start` and `end`
+fn get_deviation(point: (usize, usize), start: (usize, usize), end: (usize, usize)) -> f64 {
+	let (dx, dy) = (end.0 as f64 - start.0 as f64, end.1 as f64 - start.1 as f64);
+	let (px, py) = (point.0 as f64 - start.0 as f64, point.1 as f64 - start.1 as f64);
+	let length = f64::hypot(dx, dy);
+	if length == 0.0 {
+		f64::hypot(px, py)
+	} else {
+		((dx * py) - (dy * px)).abs() / length
+	}
+}
+
+/// Calculates the largest turn radius on the corner where the arc is free of obstacles
+///
+/// The arc touches both lines to the neighbours. It has to end at most in the middle of each line,
+/// so the arcs of two corners do not overlap. The radius is halved until the arc is free.
+///
+/// # Arguments:
+///
+/// * `area` - The area as a vector of u64 where every obstacle is u64::MAX
+/// * `rows` - Number of rows, where rows x cols is the size of the area
+/// * `cols` - Number of columns, where rows x cols is the size of the area
+/// * `previous` - The waypoint before the corner
+/// * `corner` - The corner
+/// * `next` - The waypoint after the corner
+/// * `max_radius` - Maximum turn radius
+///
+/// # Result:
+///
+/// The turn radius or `0` if no arc with at least one field radius is free
+fn get_turn_radius(area: &[u64], rows: &usize, cols: &usize, previous: (f64, f64), corner: (f64, f64), next: (f64, f64), max_radius: f64) -> f64 {
+	let length_in = f64::hypot(previous.0 - corner.0, previous.1 - corner.1);
+	let length_out = f64::hypot(next.0 - corner.0, next.1 - corner.1);
+	let u1 = ((previous.0 - corner.0) / length_in, (previous.1 - corner.1) / length_in);
+	let u2 = ((next.0 - corner.0) / length_out, (next.1 - corner.1) / length_out);
+
+	// Inner angle between both lines, a straight line has PI
+	let alpha = ((u1.0 * u2.0) + (u1.1 * u2.1)).clamp(-1.0, 1.0).acos();
+	if !(1e-6..=PI - 1e-6).contains(&alpha) {
+		return 0.0;
+	}
+
+	// The arc starts and ends `radius / tan(alpha / 2)` away from the corner
+	let tangent = (alpha / 2.0).tan();
+	let mut radius = f64::min(max_radius, f64::min(length_in, length_out) / 2.0 * tangent);
+
+	let bisector_length = f64::hypot(u1.0 + u2.0, u1.1 + u2.1);
+	let bisector = ((u1.0 + u2.0) / bisector_length, (u1.1 + u2.1) / bisector_length);
+	while radius >= 1.0 {
+		let distance = radius / tangent;
+		let center_distance = radius / (alpha / 2.0).sin();
+		let center = (corner.0 + (bisector.0 * center_distance), corner.1 + (bisector.1 * center_distance));
+		let start = (corner.0 + (u1.0 * distance), corner.1 + (u1.1 * distance));
+		let end = (corner.0 + (u2.0 * distance), corner.1 + (u2.1 * distance));
+
+		// Sample the arc with one point per field
+		let angle_start = (start.1 - center.1).atan2(start.0 - center.0);
+		let sweep = normalize_angle((end.1 - center.1).atan2(end.0 - center.0) - angle_start);
+		let samples = usize::max(2, (sweep.abs() * radius).ceil() as usize);
+		let fields: Option<Vec<(usize, usize)>> = (0..=samples)
+			.map(|i| {
+				let angle = angle_start + (sweep * i as f64 / samples as f64);
+				let (x, y) = (center.0 + (radius * angle.cos()), center.1 + (radius * angle.sin()));
+				(x >= 0.0 && y >= 0.0 && (x as usize) < *rows && (y as usize) < *cols).then_some((x as usize, y as usize))
+			})
+			.collect();
+
+		if fields.is_some_and(|fields| fields.windows(2).all(|pair| is_line_free(area, rows, pair[0], pair[1]))) {
+			return radius;
+		}
+		radius /= 2.0;
+	}
+	0.0
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::a_star;
+
+	/// Straight runs collapse, blocked shortcuts are never taken and the corners get a free arc
+	#[test]
+	fn waypoints_keep_the_path_free() {
+		let straight: Vec<(usize, usize)> = (0..10).map(|col| (5, col)).chain([(5, 9)]).chain((6..12).map(|row| (row, 9))).collect();
+		assert_eq!(merge_collinear(&straight), vec![(5, 0), (5, 9), (11, 9)]);
+		assert_eq!(merge_collinear(&[(1, 1), (1, 2), (1, 1)]), vec![(1, 1), (1, 2), (1, 1)]);
+
+		// Around a block: every deviation is below the tolerance, but the direct lines are blocked
+		let (rows, cols) = (40, 40);
+		let mut area = vec![0; rows * cols];
+		for col in 15..25 {
+			for row in 5..35 {
+				area[(col * rows) + row] = u64::MAX;
+			}
+		}
+		let path = a_star::calculate(&mut area.clone(), &rows, &cols, (20, 2), (20, 37));
+		let simplified = simplify(&area, &rows, &path, 1000.0);
+		assert!(simplified.len() > 2 && simplified.len() < path.len(), "{:?}", simplified);
+		assert_eq!((simplified.first(), simplified.last()), (path.first(), path.last()));
+		assert!(simplified.windows(2).all(|pair| is_line_free(&area, &rows, pair[0], pair[1])), "{:?}", simplified);
+
+		// A straight line has no corner, a right angle on a free area gets the largest radius
+		let free = vec![0; rows * cols];
+		assert_eq!(get_turn_radius(&free, &rows, &cols, (20.5, 0.5), (20.5, 20.5), (20.5, 39.5), 8.0), 0.0);
+		assert_eq!(get_turn_radius(&free, &rows, &cols, (0.5, 20.5), (20.5, 20.5), (20.5, 39.5), 8.0), 8.0);
+		// An obstacle inside the corner on the arc of the largest radius
+		let mut inner = free.clone();
+		inner[(22 * rows) + 18] = u64::MAX;
+		inner[(23 * rows) + 18] = u64::MAX;
+		assert_eq!(get_turn_radius(&inner, &rows, &cols, (0.5, 20.5), (20.5, 20.5), (20.5, 39.5), 8.0), 4.0);
+	}
+}
@@ -0,0 +1,317 @@
+//! Vector model of the obstacles for the vector-based planners
+//!
+//! The obstacles are polygons, circles and line segments in continuous grid coordinates, the same as the nodes of the trees:
+//! the field `(row, col)` covers `row..row + 1` and `col..col + 1`. A world in metres is converted with [`World::to_grid`].
+//!
+//! Lines are checked exactly against the shapes instead of the fields they cross.
+//! For the grid algorithms the world can be rasterized into the area, every field touched by a shape becomes an obstacle.
+//!
+//! # File format
+//!
+//! One shape per line, empty lines and lines starting with `#` are ignored:
+//!
+//! ```text
+//! polygon X0 Y0 X1 Y1 X2 Y2 ...
+//! circle X Y RADIUS
+//! segment X0 Y0 X1 Y1
+//! ```
+
+use std::str::FromStr;
+use crate::map::Map;
+
+const EPSILON: f64 = 1e-9;
+
+/// An obstacle
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+	/// A closed polygon, the corners in any order around it
+	Polygon(Vec<(f64, f64)>),
+	/// A filled circle
+	Circle {
+		/// Middle of the circle
+		center: (f64, f64),
+		/// Radius of the circle
+		radius: f64,
+	},
+	/// A thin wall between two points
+	Segment((f64, f64), (f64, f64)),
+}
+
+impl Shape {
+	/// Returns the distance of the point to the shape, negative if the point lies inside
+	pub fn signed_distance(&self, point: (f64, f64)) -> f64 {
+		match self {
+			Shape::Polygon(corners) => {
+				let distance = edges(corners)
+					.map(|(a, b)| get_segment_distance(point, a, b))
+					.fold(f64::INFINITY, f64::min);
+				if is_in_polygon(point, corners) { -distance } else { distance }
+			},
+			Shape::Circle { center, radius } => f64::hypot(point.0 - center.0, point.1 - center.1) - radius,
+			Shape::Segment(a, b) => get_segment_distance(point, *a, *b),
+		}
+	}
+
+	/// Checks if the line segment from `a` to `b` touches the shape
+	pub fn intersects_segment(&self, a: (f64, f64), b: (f64, f64)) -> bool {
+		match self {
+			Shape::Polygon(corners) => is_in_polygon(a, corners) || edges(corners).any(|(p, q)| is_intersecting(a, b, p, q)),
+			Shape::Circle { center, radius } => get_segment_distance(*center, a, b) <= *radius,
+			Shape::Segment(p, q) => is_intersecting(a, b, *p, *q),
+		}
+	}
+
+	/// Returns the shortest distance of the line segment from `a` to `b` to the shape, `0` if it touches the shape
+	pub fn segment_distance(&self, a: (f64, f64), b: (f64, f64)) -> f64 {
+		if self.intersects_segment(a, b) {
+			return 0.0;
+		}
+		match self {
+			Shape::Polygon(corners) => edges(corners).map(|(p, q)| get_segments_distance(a, b, p, q)).fold(f64::INFINITY, f64::min),
+			Shape::Circle { center, radius } => get_segment_distance(*center, a, b) - radius,
+			Shape::Segment(p, q) => get_segments_distance(a, b, *p, *q),
+		}
+	}
+
+	/// Returns the bounding box as `((min x, min y), (max x, max y))`
+	pub fn bounds(&self) -> ((f64, f64), (f64, f64)) {
+		match self {
+			Shape::Polygon(corners) => corners.iter().fold(
+				((f64::INFINITY, f64::INFINITY), (f64::NEG_INFINITY, f64::NEG_INFINITY)),
+				|(min, max), p| ((min.0.min(p.0), min.1.min(p.1)), (max.0.max(p.0), max.1.max(p.1))),
+			),
+			Shape::Circle { center, radius } => ((center.0 - radius, center.1 - radius), (center.0 + radius, center.1 + radius)),
+			Shape::Segment(a, b) => ((a.0.min(b.0), a.1.min(b.1)), (a.0.max(b.0), a.1.max(b.1))),
+		}
+	}
+
+	/// Checks if the shape touches the rectangle from `min` to `max`
+	///
+	/// Either an edge of the rectangle touches the shape or the shape lies completely inside the rectangle.
+	fn intersects_rectangle(&self, min: (f64, f64), max: (f64, f64)) -> bool {
+		let corners = [min, (max.0, min.1), max, (min.0, max.1)];
+		let inside = match self {
+			Shape::Polygon(corners) => corners[0],
+			Shape::Circle { center, .. } => *center,
+			Shape::Segment(a, _) => *a,
+		};
+		(min.0..=max.0).contains(&inside.0) && (min.1..=max.1).contains(&inside.1)
+			|| edges(&corners).any(|(a, b)| self.intersects_segment(a, b))
+	}
+}
+
+impl FromStr for Shape {
+	type Err = String;
+
+	/// Parses a shape like `polygon 0 0 10 0 5 5`, `circle 5 5 2` or `segment 0 0 10 10`
+	fn from_str(value: &str) -> Result<Self, Self::Err> {
+		let mut parts = value.split_whitespace();
+		let kind = parts.next().unwrap_or_default();
+		let numbers = parts
+			.map(|part| part.parse::<f64>().map_err(|_| format!("Invalid number in shape: {}", value)))
+			.collect::<Result<Vec<f64>, String>>()?;
+		match (kind, numbers.len()) {
+			("polygon", len) if len >= 6 && len.is_multiple_of(2) => Ok(Shape::Polygon(numbers.chunks(2).map(|p| (p[0], p[1])).collect())),
+			("circle", 3) if numbers[2] >= 0.0 => Ok(Shape::Circle { center: (numbers[0], numbers[1]), radius: numbers[2] }),
+			("segment", 4) => Ok(Shape::Segment((numbers[0], numbers[1]), (numbers[2], numbers[3]))),
+			_ => Err(format!("Invalid shape: {}", value)),
+		}
+	}
+}
+
+/// All obstacles of the world
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct World {
+	/// The obstacles
+	pub shapes: Vec<Shape>,
+}
+
+impl World {
+	/// Creates a world with the given obstacles
+	pub fn new(shapes: Vec<Shape>) -> Self {
+		World { shapes }
+	}
+
+	/// Loads the world from a file, see the module documentation for the format
+	pub fn load(path: &str) -> Result<Self, String> {
+		std::fs::read_to_string(path)
+			.map_err(|err| format!("Unable to read the world {}: {}", path, err))?
+			.parse()
+	}
+
+	/// Returns the distance of the point to the nearest obstacle, negative if it lies inside one
+	///
+	/// Without any obstacle the distance is infinite.
+	pub fn signed_distance(&self, point: (f64, f64)) -> f64 {
+		self.shapes.iter().map(|shape| shape.signed_distance(point)).fold(f64::INFINITY, f64::min)
+	}
+
+	/// Checks if the point does not touch any obstacle
+	pub fn is_free(&self, point: (f64, f64)) -> bool {
+		self.signed_distance(point) > EPSILON
+	}
+
+	/// Checks if the line segment from `a` to `b` does not touch any obstacle
+	pub fn is_segment_free(&self, a: (f64, f64), b: (f64, f64)) -> bool {
+		!self.shapes.iter().any(|shape| shape.intersects_segment(a, b))
+	}
+
+	/// Returns the distance of the line segment from `a` to `b` to the nearest obstacle, `0` if it touches one
+	///
+	/// Without any obstacle the distance is infinite.
+	pub fn segment_distance(&self, a: (f64, f64), b: (f64, f64)) -> f64 {
+		self.shapes.iter().map(|shape| shape.segment_distance(a, b)).fold(f64::INFINITY, f64::min)
+	}
+
+	/// Marks every field which is touched by an obstacle with `u64::MAX`
+	///
+	/// # Arguments:
+	///
+	/// * `area` - The area as a vector of u64, the other fields are not changed
+	/// * `rows` - Number of rows, where rows x cols is the size of the area
+	/// * `cols` - Number of columns, where rows x cols is the size of the area
+	pub fn rasterize(&self, area: &mut [u64], rows: &usize, cols: &usize) {
+		for shape in &self.shapes {
+			let (min, max) = shape.bounds();
+			if max.0 < 0.0 || max.1 < 0.0 || min.0 >= *rows as f64 || min.1 >= *cols as f64 {
+				continue;
+			}
+			let rows_range = f64::max(0.0, min.0.floor()) as usize..=usize::min(rows - 1, max.0.floor() as usize);
+			let cols_range = f64::max(0.0, min.1.floor()) as usize..=usize::min(cols - 1, max.1.floor() as usize);
+			for col in cols_range {
+				for row in rows_range.clone() {
+					if shape.intersects_rectangle((row as f64, col as f64), (row as f64 + 1.0, col as f64 + 1.0)) {
+						area[(col * rows) + row] = u64::MAX;
+					}
+				}
+			}
+		}
+	}
+
+	/// Converts a world in metres into grid coordinates of the given map
+	pub fn to_grid(&self, map: &Map) -> World {
+		let shapes = self.shapes.iter().map(|shape| match shape {
+			Shape::Polygon(corners) => Shape::Polygon(corners.iter().map(|p| map.to_grid(*p)).collect()),
+			Shape::Circle { center, radius } => Shape::Circle { center: map.to_grid(*center), radius: map.to_grid_distance(*radius) },
+			Shape::Segment(a, b) => Shape::Segment(map.to_grid(*a), map.to_grid(*b)),
+		}).collect();
+		World { shapes }
+	}
+}
+
+impl FromStr for World {
+	type Err = String;
+
+	/// Parses one shape per line, empty lines and comments starting with `#` are ignored
+	fn from_str(value: &str) -> Result<Self, Self::Err> {
+		let shapes = value.lines()
+			.map(str::trim)
+			.filter(|line| !line.is_empty() && !line.starts_with('#'))
+			.map(str::parse)
+			.collect::<Result<Vec<Shape>, String>>()?;
+		Ok(World { shapes })
+	}
+}
+
+/// Returns all edges of a closed polygon
+fn edges(corners: &[(f64, f64)]) -> impl Iterator<Item = ((f64, f64), (f64, f64))> + '_ {
+	(0..corners.len()).map(|i| (corners[i], corners[(i + 1) % corners.len()]))
+}
+
+/// Returns the z-component of the cross product `(a - o) x (b - o)`, positive if `b` is left of the line `o` to `a`
+fn cross(o: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+	((a.0 - o.0) * (b.1 - o.1)) - ((a.1 - o.1) * (b.0 - o.0))
+}
+
+/// Returns the shortest distance of the point to the line segment from `a` to `b`
+fn get_segment_distance(point: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+	let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+	let length = (dx * dx) + (dy * dy);
+	let t = if length < EPSILON { 0.0 } else { (((point.0 - a.0) * dx) + ((point.1 - a.1) * dy)) / length };
+	let t = t.clamp(0.0, 1.0);
+	f64::hypot(point.0 - (a.0 + (t * dx)), point.1 - (a.1 + (t * dy)))
+}
+
+/// Returns the shortest distance between the line segments `a` to `b` and `p` to `q` which do not intersect
+fn get_segments_distance(a: (f64, f64), b: (f64, f64), p: (f64, f64), q: (f64, f64)) -> f64 {
+	[get_segment_distance(a, p, q), get_segment_distance(b, p, q), get_segment_distance(p, a, b), get_segment_distance(q, a, b)]
+		.into_iter()
+		.fold(f64::INFINITY, f64::min)
+}
+
+/// Checks if the line segments `a` to `b` and `p` to `q` touch each other, including touching ends and collinear overlaps
+fn is_intersecting(a: (f64, f64), b: (f64, f64), p: (f64, f64), q: (f64, f64)) -> bool {
+	let d1 = cross(p, q, a);
+	let d2 = cross(p, q, b);
+	let d3 = cross(a, b, p);
+	let d4 = cross(a, b, q);
+	if ((d1 > EPSILON && d2 < -EPSILON) || (d1 < -EPSILON && d2 > EPSILON))
+		&& ((d3 > EPSILON && d4 < -EPSILON) || (d3 < -EPSILON && d4 > EPSILON)) {
+		return true;
+	}
+	// Touching ends or collinear segments
+	get_segment_distance(a, p, q) <= EPSILON
+		|| get_segment_distance(b, p, q) <= EPSILON
+		|| get_segment_distance(p, a, b) <= EPSILON
+		|| get_segment_distance(q, a, b) <= EPSILON
+}
+
+/// Checks with a ray to increasing rows if the point lies inside the polygon
+fn is_in_polygon(point: (f64, f64), corners: &[(f64, f64)]) -> bool {
+	edges(corners).fold(false, |inside, (a, b)| {
+		if (a.1 > point.1) != (b.1 > point.1) && point.0 < a.0 + ((point.1 - a.1) / (b.1 - a.1) * (b.0 - a.0)) {
+			!inside
+		} else {
+			inside
+		}
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Distances, intersections and the rasterization of each kind of shape
+	#[test]
+	fn shapes_are_checked_exactly() {
+		let world: World = "
+			# A square, a circle and a wall
+			polygon 10 10 20 10 20 20 10 20
+			circle 40 15 5
+			segment 30 30 30 40
+		".parse().unwrap();
+		assert_eq!(world.shapes.len(), 3);
+
+		assert!((world.signed_distance((15.0, 15.0)) + 5.0).abs() < 1e-9);
+		assert!((world.signed_distance((25.0, 15.0)) - 5.0).abs() < 1e-9);
+		assert!((world.signed_distance((30.0, 35.0))).abs() < 1e-9);
+		assert!(!world.is_free((40.0, 12.0)));
+
+		// Passes between the square and the circle, then crosses the corner of the square, then touches the wall
+		assert!(world.is_segment_free((27.0, 0.0), (27.0, 25.0)));
+		assert!(!world.is_segment_free((5.0, 15.0), (15.0, 5.0)));
+		assert!(!world.is_segment_free((25.0, 40.0), (30.0, 40.0)));
+		assert!(world.is_segment_free((25.0, 41.0), (35.0, 41.0)));
+		assert!(!world.is_segment_free((12.0, 12.0), (18.0, 18.0)));
+		assert!((world.segment_distance((27.0, 0.0), (27.0, 25.0)) - f64::hypot(3.0, 5.0)).abs() < 1e-9);
+		assert!((world.segment_distance((0.0, 5.0), (5.0, 0.0)) - f64::hypot(7.5, 7.5)).abs() < 1e-9);
+		assert_eq!(world.segment_distance((5.0, 15.0), (15.0, 5.0)), 0.0);
+		assert_eq!(World::default().segment_distance((0.0, 0.0), (1.0, 1.0)), f64::INFINITY);
+
+		// Every marked field touches a shape, its middle is at most half a diagonal away
+		let (rows, cols) = (50, 50);
+		let mut area = vec![0; rows * cols];
+		world.rasterize(&mut area, &rows, &cols);
+		for col in 0..cols {
+			for row in 0..rows {
+				let distance = world.signed_distance((row as f64 + 0.5, col as f64 + 0.5));
+				if area[(col * rows) + row] == u64::MAX {
+					assert!(distance <= std::f64::consts::FRAC_1_SQRT_2 + 1e-9, "Field {:?} is {} away", (row, col), distance);
+				} else {
+					assert!(distance >= 0.5 - 1e-9, "Field {:?} is only {} away", (row, col), distance);
+				}
+			}
+		}
+		assert!("circle 1 2".parse::<World>().is_err());
+	}
+}